@@ -0,0 +1,95 @@
+use rustomp::frame::{FrameReader, Header, HeaderRef, ParserConfig, Version};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{Cursor, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const ITERATIONS: usize = 100_000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn header() -> Header {
+    let mut header = Header::new();
+    header.push("destination", "/queue/orders".to_owned());
+    header.push("content-type", "application/json".to_owned());
+    header.push("content-length", "128".to_owned());
+    header.push("receipt", "message-12345".to_owned());
+    header.push("persistent", "true".to_owned());
+    header
+}
+
+fn format_per_header<W: Write>(header: &Header, mut w: W) {
+    for (k, v) in header.iter() {
+        let field_str = format!("{}: {}\n", k, v.join(","));
+        w.write_all(field_str.as_bytes()).unwrap();
+    }
+}
+
+fn run<F: FnMut(&mut Vec<u8>)>(name: &str, mut f: F) {
+    let mut buffer: Vec<u8> = Vec::with_capacity(1024);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        buffer.clear();
+        f(&mut buffer);
+    }
+    let elapsed = start.elapsed();
+    let allocated = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "{:<24} {:>8.1} ns/iter {:>6.2} allocs/iter",
+        name,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64,
+        allocated as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let header = header();
+
+    run("format_per_header", |buffer| {
+        format_per_header(&header, buffer)
+    });
+    run("Header::write_to", |buffer| {
+        header.write_to(buffer).unwrap();
+    });
+
+    let mut encoded = Vec::new();
+    header.write_to(&mut encoded).unwrap();
+    encoded.push(b'\n');
+
+    let mut frames = Vec::with_capacity(ITERATIONS * (encoded.len() + 134));
+    for _ in 0..ITERATIONS {
+        frames.extend_from_slice(b"SEND\n");
+        frames.extend_from_slice(&encoded);
+        frames.extend_from_slice(&[b'x'; 128]);
+        frames.push(0);
+    }
+    let reader = FrameReader::new(Cursor::new(frames));
+
+    run("FrameReader::read_frame", |_| {
+        reader.read_frame().unwrap();
+    });
+    run("HeaderRef::parse", |_| {
+        HeaderRef::parse(&encoded, Version::V1_2, ParserConfig::lenient())
+            .unwrap()
+            .unwrap();
+    });
+}
@@ -0,0 +1,194 @@
+use crate::frame::Header;
+use std::io;
+use std::io::Read;
+
+/// The header carrying a body's checksum as `<algorithm>:<hex digest>`, such as
+/// `crc32:cbf43926`. It is not part of STOMP; brokers pass it on like any other header.
+pub const CHECKSUM: &str = "checksum";
+
+/// A checksum or digest algorithm, identified in the `checksum` header by `Checksum::name`.
+/// The crate ships `Crc32`; implement this trait over a hashing library for MD5, SHA-256 and
+/// the like.
+pub trait Checksum {
+    fn name(&self) -> &str;
+
+    fn update(&mut self, input: &[u8]);
+
+    /// The digest of everything passed to `Checksum::update` so far.
+    fn finish(&self) -> Vec<u8>;
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// The CRC-32 used by zlib, gzip and PNG. Its digest is four bytes, most significant first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32 { crc: !0 }
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32::default()
+    }
+
+    pub fn value(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Checksum for Crc32 {
+    fn name(&self) -> &str {
+        "crc32"
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        for byte in input {
+            self.crc =
+                CRC32_TABLE[((self.crc ^ u32::from(*byte)) & 0xff) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        self.value().to_be_bytes().to_vec()
+    }
+}
+
+/// Sets the `checksum` header to the digest of `body`.
+pub fn write_header<C: Checksum>(header: &mut Header, mut checksum: C, body: &[u8]) {
+    checksum.update(body);
+    let value = format!("{}:{}", checksum.name(), to_hex(&checksum.finish()));
+    header.insert(CHECKSUM.to_owned(), vec![value]);
+}
+
+/// Computes a checksum over a body as the application reads it, so that a large body can be
+/// checked without being read twice. The digest is available once the body has been read to
+/// the end.
+pub struct ChecksumReader<R: Read, C: Checksum> {
+    inner: R,
+    checksum: C,
+    finished: bool,
+}
+
+impl<R: Read, C: Checksum> ChecksumReader<R, C> {
+    pub fn new(inner: R, checksum: C) -> Self {
+        ChecksumReader {
+            inner,
+            checksum,
+            finished: false,
+        }
+    }
+
+    /// The digest of the body, once it has been read to the end.
+    pub fn digest(&self) -> Option<Vec<u8>> {
+        if self.finished {
+            Some(self.checksum.finish())
+        } else {
+            None
+        }
+    }
+
+    /// Whether the body matches the `checksum` header of its frame. Returns `None` until the
+    /// body has been read to the end, and when the header is missing or names another
+    /// algorithm.
+    pub fn matches(&self, header: &Header) -> Option<bool> {
+        let digest = self.digest()?;
+        let (name, expected) = header.first(CHECKSUM)?.split_once(':')?;
+
+        if !name.eq_ignore_ascii_case(self.checksum.name()) {
+            return None;
+        }
+        Some(expected.eq_ignore_ascii_case(&to_hex(&digest)))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, C: Checksum> Read for ChecksumReader<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+
+        if bytes_read == 0 && !buf.is_empty() {
+            self.finished = true;
+        }
+        self.checksum.update(&buf[..bytes_read]);
+        Ok(bytes_read)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Command, FrameReader};
+    use std::io::Cursor;
+
+    #[test]
+    fn crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(0xcbf4_3926, crc.value());
+        assert_eq!(vec![0xcb, 0xf4, 0x39, 0x26], crc.finish());
+        assert_eq!(0, Crc32::new().value());
+    }
+
+    #[test]
+    fn verify_frame_body() {
+        let mut header = Header::new();
+        write_header(&mut header, Crc32::new(), b"123456789");
+        assert_eq!(Some("crc32:cbf43926"), header.first(CHECKSUM));
+
+        let input = b"SEND\nchecksum:CRC32:CBF43926\n\n123456789\0";
+        let reader = FrameReader::new(Cursor::new(&input[..]));
+        let frame = reader.read_frame().unwrap();
+        assert_eq!(Command::Send, frame.command);
+
+        let mut body = ChecksumReader::new(frame.body, Crc32::new());
+        let mut buffer = [0; 4];
+        body.read_exact(&mut buffer).unwrap();
+        assert_eq!(None, body.matches(&frame.header));
+
+        io::copy(&mut body, &mut io::sink()).unwrap();
+        assert_eq!(Some(true), body.matches(&frame.header));
+        assert_eq!(None, body.matches(&Header::new()));
+
+        let mut other = Header::new();
+        other.push(CHECKSUM, "crc32:00000000".to_owned());
+        assert_eq!(Some(false), body.matches(&other));
+        other.insert(CHECKSUM.to_owned(), vec!["sha256:cbf43926".to_owned()]);
+        assert_eq!(None, body.matches(&other));
+    }
+}
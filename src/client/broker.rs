@@ -0,0 +1,128 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str;
+
+/// The content of an ERROR frame sent by the broker. Brokers put a short summary in the
+/// `message` header and often more detail in the body; where the body follows a format used by
+/// a common broker, its cause is extracted into `reason` and `detail`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerError {
+    pub message: String,
+    /// A machine-readable cause, such as RabbitMQ's `NOT_FOUND`, an Artemis `AMQ` code or the
+    /// Java exception class reported by ActiveMQ.
+    pub reason: Option<String>,
+    /// The human-readable explanation that accompanied `reason`.
+    pub detail: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl BrokerError {
+    pub fn new(message: String, body: Vec<u8>) -> Self {
+        let cause = str::from_utf8(&body)
+            .ok()
+            .and_then(|body| body.lines().map(str::trim).find(|l| !l.is_empty()))
+            .and_then(|line| {
+                rabbitmq(line)
+                    .or_else(|| artemis(line))
+                    .or_else(|| java(line))
+            });
+        let (reason, detail) = match cause {
+            Some((reason, detail)) => (Some(reason.to_owned()), Some(detail.to_owned())),
+            None => (None, None),
+        };
+
+        BrokerError {
+            message,
+            reason,
+            detail,
+            body,
+        }
+    }
+}
+
+impl Display for BrokerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "{} ({})", self.message, reason),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// `NOT_FOUND - no queue 'a' in vhost '/'`
+fn rabbitmq(line: &str) -> Option<(&str, &str)> {
+    let (code, detail) = line.split_once(" - ")?;
+    let is_code = !code.is_empty() && code.bytes().all(|b| b.is_ascii_uppercase() || b == b'_');
+    Some((code, detail.trim())).filter(|_| is_code)
+}
+
+/// `AMQ229031: Unable to validate user from /127.0.0.1:50000`
+fn artemis(line: &str) -> Option<(&str, &str)> {
+    let (code, detail) = line.split_once(": ")?;
+    let digits = code.strip_prefix("AMQ")?;
+    let is_code = !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+    Some((code, detail.trim())).filter(|_| is_code)
+}
+
+/// `java.lang.SecurityException: User name [a] or password is invalid.`, the first line of the
+/// stack trace ActiveMQ sends.
+fn java(line: &str) -> Option<(&str, &str)> {
+    let (class, detail) = match line.split_once(": ") {
+        Some((class, detail)) => (class, detail.trim()),
+        None => (line, ""),
+    };
+    let is_class = class.contains('.')
+        && (class.ends_with("Exception") || class.ends_with("Error"))
+        && class
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'$' || b == b'_');
+    Some((class, detail)).filter(|_| is_class)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(body: &str) -> (Option<String>, Option<String>) {
+        let error = BrokerError::new("m".to_owned(), body.as_bytes().to_vec());
+        (error.reason, error.detail)
+    }
+
+    fn cause(reason: &str, detail: &str) -> (Option<String>, Option<String>) {
+        (Some(reason.to_owned()), Some(detail.to_owned()))
+    }
+
+    #[test]
+    fn parse_body() {
+        assert_eq!(
+            cause("NOT_FOUND", "no queue 'a' in vhost '/'"),
+            parse("NOT_FOUND - no queue 'a' in vhost '/'")
+        );
+        assert_eq!(
+            cause("AMQ229031", "Unable to validate user"),
+            parse("AMQ229031: Unable to validate user\n")
+        );
+        assert_eq!(
+            cause(
+                "java.lang.SecurityException",
+                "User name [a] or password is invalid."
+            ),
+            parse(
+                "\njava.lang.SecurityException: User name [a] or password is invalid.\n\tat \
+                 org.apache.activemq.security.JaasAuthenticationBroker.addConnection()\n"
+            )
+        );
+        assert_eq!((None, None), parse("Something went wrong - sorry"));
+        assert_eq!((None, None), parse(""));
+    }
+
+    #[test]
+    fn display() {
+        let error = BrokerError::new("not_found".to_owned(), b"NOT_FOUND - gone".to_vec());
+        assert_eq!("not_found (NOT_FOUND)", error.to_string());
+        assert_eq!(
+            "denied",
+            BrokerError::new("denied".to_owned(), vec![]).to_string()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// A token bucket limit on the rate of SEND frames, set with `ConnectOptions::rate_limit`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    per_second: f64,
+    burst: f64,
+    per_destination: bool,
+}
+
+impl RateLimit {
+    /// Allows `per_second` frames per second on average, and bursts of up to `burst` frames
+    /// after a quiet period.
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        RateLimit {
+            per_second: f64::from(per_second.max(1)),
+            burst: f64::from(burst.max(1)),
+            per_destination: false,
+        }
+    }
+
+    /// Applies the limit to each destination separately rather than to all frames together.
+    pub fn per_destination(mut self) -> Self {
+        self.per_destination = true;
+        self
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// The tokens in the bucket at `now`, counting those added since it was last updated.
+    fn refilled(&self, now: Instant, limit: &RateLimit) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * limit.per_second).min(limit.burst)
+    }
+}
+
+pub(super) struct RateLimiter {
+    limit: RateLimit,
+    buckets: BTreeMap<String, TokenBucket>,
+    swept: Instant,
+}
+
+impl RateLimiter {
+    pub(super) fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            buckets: BTreeMap::new(),
+            swept: Instant::now(),
+        }
+    }
+
+    /// Takes a token for a frame to `destination`, returning how long the caller must wait
+    /// before sending it. A token can be taken before it is available, so frames sent while the
+    /// bucket is empty queue up behind each other.
+    pub(super) fn acquire(&mut self, destination: &str, now: Instant) -> Duration {
+        let key = if self.limit.per_destination {
+            destination
+        } else {
+            ""
+        };
+        let limit = self.limit;
+
+        self.sweep(now);
+
+        let bucket = self
+            .buckets
+            .entry(key.to_owned())
+            .or_insert_with(|| TokenBucket {
+                tokens: limit.burst,
+                updated: now,
+            });
+
+        bucket.tokens = bucket.refilled(now, &limit);
+        bucket.updated = now;
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / limit.per_second)
+        }
+    }
+
+    /// Drops the buckets that have refilled, which are the same as new ones, rather than keep
+    /// one for every destination ever sent to. An empty bucket takes `burst / per_second` to
+    /// refill, so the buckets are only scanned once in that time.
+    fn sweep(&mut self, now: Instant) {
+        let limit = self.limit;
+        let interval = Duration::from_secs_f64(limit.burst / limit.per_second);
+
+        if now.saturating_duration_since(self.swept) < interval {
+            return;
+        }
+        self.swept = now;
+        self.buckets
+            .retain(|_, bucket| bucket.refilled(now, &limit) < limit.burst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn burst_then_rate() {
+        let mut limiter = RateLimiter::new(RateLimit::new(10, 2));
+        let now = Instant::now();
+
+        assert_eq!(Duration::from_secs(0), limiter.acquire("/queue/a", now));
+        assert_eq!(Duration::from_secs(0), limiter.acquire("/queue/b", now));
+        assert_eq!(Duration::from_millis(100), limiter.acquire("/queue/a", now));
+        assert_eq!(Duration::from_millis(200), limiter.acquire("/queue/a", now));
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(Duration::from_secs(0), limiter.acquire("/queue/a", later));
+    }
+
+    #[test]
+    fn per_destination() {
+        let mut limiter = RateLimiter::new(RateLimit::new(10, 1).per_destination());
+        let now = Instant::now();
+
+        assert_eq!(Duration::from_secs(0), limiter.acquire("/queue/a", now));
+        assert_eq!(Duration::from_secs(0), limiter.acquire("/queue/b", now));
+        assert_eq!(Duration::from_millis(100), limiter.acquire("/queue/a", now));
+    }
+
+    #[test]
+    fn refilled_buckets_are_dropped() {
+        let mut limiter = RateLimiter::new(RateLimit::new(10, 2).per_destination());
+        let now = Instant::now();
+
+        limiter.acquire("/queue/a", now);
+        assert_eq!(1, limiter.buckets.len());
+
+        // The bucket for /queue/a has refilled, but the buckets are only swept every 200ms.
+        limiter.acquire("/queue/b", now + Duration::from_millis(100));
+        assert_eq!(2, limiter.buckets.len());
+
+        let later = now + Duration::from_millis(200);
+        assert_eq!(Duration::from_secs(0), limiter.acquire("/queue/c", later));
+        assert_eq!(1, limiter.buckets.len());
+    }
+}
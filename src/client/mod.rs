@@ -0,0 +1,2214 @@
+mod broker;
+mod limit;
+mod startup;
+mod subscription;
+mod transaction;
+
+pub use broker::BrokerError;
+pub use limit::RateLimit;
+pub use startup::{Readiness, Startup};
+pub use subscription::{Messages, Subscription};
+pub use transaction::Transaction;
+
+use self::limit::RateLimiter;
+use crate::compression;
+use crate::compression::{Codec, CONTENT_ENCODING};
+use crate::content::Content;
+use crate::destination::Destination;
+use crate::frame::headers::{
+    ACCEPT_VERSION, ACK, CONTENT_LENGTH, DESTINATION, HOST, ID, LOGIN, MESSAGE, PASSCODE, RECEIPT,
+    RECEIPT_ID, SERVER, SESSION, SUBSCRIPTION, VERSION,
+};
+use crate::frame::{
+    AckMode, Body, Command, Frame, FrameReader, FrameWriter, Header, OwnedFrame, ReadError, Version,
+};
+use crate::heartbeat::{HeartBeat, Intervals};
+use crate::message;
+#[cfg(feature = "metrics")]
+use crate::metrics::FrameMetrics;
+use crate::subscription::{DuplicateSubscription, SubscriptionId, SubscriptionRegistry};
+use crate::transport::Transport;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::io;
+use std::io::Read;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DISCONNECT_LINGER: Duration = Duration::from_secs(1);
+
+/// Asks ActiveMQ and RabbitMQ respectively to deliver one message at a time to a subscription.
+const PREFETCH_ONE: &[(&str, &str)] = &[("activemq.prefetchSize", "1"), ("prefetch-count", "1")];
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    Read(ReadError),
+    Protocol(String),
+    Broker(BrokerError),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::ClientError::*;
+
+        match self {
+            Io(e) => write!(f, "{}", e),
+            Read(e) => write!(f, "{}", e),
+            Protocol(s) => write!(f, "protocol error: {}", s),
+            Broker(e) => write!(f, "broker error: {}", e),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClientError::Io(e) => Some(e),
+            ClientError::Read(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<ReadError> for ClientError {
+    fn from(e: ReadError) -> Self {
+        ClientError::Read(e)
+    }
+}
+
+impl From<DuplicateSubscription> for ClientError {
+    fn from(e: DuplicateSubscription) -> Self {
+        ClientError::Protocol(e.to_string())
+    }
+}
+
+/// A failed `Client::migrate`, with the client that still holds the session.
+pub struct MigrationError {
+    client: Box<Client>,
+    error: ClientError,
+}
+
+impl MigrationError {
+    pub fn error(&self) -> &ClientError {
+        &self.error
+    }
+
+    pub fn into_client(self) -> Client {
+        *self.client
+    }
+}
+
+impl Debug for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MigrationError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "migration failed: {}", self.error)
+    }
+}
+
+impl Error for MigrationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+pub struct ConnectOptions {
+    host: String,
+    versions: Vec<Version>,
+    login: Option<String>,
+    passcode: Option<String>,
+    heart_beat: HeartBeat,
+    infer_content_type: bool,
+    receipt_window: Option<usize>,
+    stall: Option<Stall>,
+    rate_limit: Option<RateLimit>,
+    codecs: Vec<Box<dyn Codec>>,
+    identity: Header,
+    identify_sends: bool,
+}
+
+impl ConnectOptions {
+    pub fn new<T: Into<String>>(host: T) -> Self {
+        ConnectOptions {
+            host: host.into(),
+            versions: vec![Version::V1_2],
+            login: None,
+            passcode: None,
+            heart_beat: HeartBeat::default(),
+            infer_content_type: true,
+            receipt_window: None,
+            stall: None,
+            rate_limit: None,
+            codecs: Vec::new(),
+            identity: Header::new(),
+            identify_sends: false,
+        }
+    }
+
+    /// Adds a header identifying the application to CONNECT, such as its name, version or
+    /// instance id, so that the broker can tell where a connection comes from. Brokers ignore
+    /// headers they do not know.
+    pub fn identity<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.identity.insert(name.into(), vec![value.into()]);
+        self
+    }
+
+    /// Whether `Client::send` also adds the `ConnectOptions::identity` headers to every SEND,
+    /// passing them on to consumers. A header the caller set is left as it is. Disabled by
+    /// default.
+    pub fn identify_sends(mut self, identify: bool) -> Self {
+        self.identify_sends = identify;
+        self
+    }
+
+    /// Requests a receipt for every SEND, SUBSCRIBE, UNSUBSCRIBE, ACK and NACK frame, and allows
+    /// at most `max` of them to be unconfirmed at a time. Once the window is full, the next frame
+    /// is only written after a RECEIPT has arrived for an earlier one.
+    pub fn receipt_window(mut self, max: usize) -> Self {
+        self.receipt_window = Some(max.max(1));
+        self
+    }
+
+    /// Offers `codec` for compressing frame bodies, after any codecs offered earlier. If the
+    /// broker accepts one of them, SEND bodies are compressed with it and MESSAGE bodies marked
+    /// with its `content-encoding` are decompressed by `Client::receive`. Otherwise bodies are
+    /// sent as they are.
+    pub fn compression<C: Codec + 'static>(mut self, codec: C) -> Self {
+        self.codecs.push(Box::new(codec));
+        self
+    }
+
+    /// Limits the rate at which `Client::send` writes frames. A send that would exceed the limit
+    /// blocks until it is allowed.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Calls `callback` when the oldest unconfirmed frame in the receipt window has waited for
+    /// longer than `threshold`, once for each frame that stalls. The window is checked whenever
+    /// the client writes or reads a frame, and by `Client::check_stall`.
+    pub fn on_stall<F>(mut self, threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(&WindowStats) + 'static,
+    {
+        self.stall = Some(Stall {
+            threshold,
+            callback: Box::new(callback),
+            reported: RefCell::new(None),
+        });
+        self
+    }
+
+    /// Sets the protocol versions offered in CONNECT, only 1.2 by default. Frames are encoded
+    /// according to the version the broker picks.
+    pub fn accept_versions(mut self, versions: &[Version]) -> Self {
+        self.versions = versions.to_vec();
+        self
+    }
+
+    /// Whether `Client::send` sets `content-type` from the body's type when the header is not
+    /// already set. Enabled by default.
+    pub fn infer_content_type(mut self, infer: bool) -> Self {
+        self.infer_content_type = infer;
+        self
+    }
+
+    /// Sets the heart-beat intervals offered in CONNECT. The client does not send or check
+    /// heart-beats itself; see `Client::heartbeat` and `heartbeat::HeartbeatMonitor`.
+    pub fn heart_beat(mut self, heart_beat: HeartBeat) -> Self {
+        self.heart_beat = heart_beat;
+        self
+    }
+
+    pub fn credentials<L: Into<String>, P: Into<String>>(mut self, login: L, passcode: P) -> Self {
+        self.login = Some(login.into());
+        self.passcode = Some(passcode.into());
+        self
+    }
+
+    fn header(&self) -> Header {
+        let mut header = Header::new();
+        header.push(ACCEPT_VERSION, Version::accept_version(&self.versions));
+        header.push(HOST, self.host.clone());
+        self.heart_beat.write_header(&mut header);
+
+        let names: Vec<&str> = self.codecs.iter().map(|c| c.name()).collect();
+        compression::write_offer(&mut header, &names);
+
+        if let Some(login) = &self.login {
+            header.push(LOGIN, login.clone());
+        }
+
+        if let Some(passcode) = &self.passcode {
+            header.push(PASSCODE, passcode.clone());
+        }
+
+        for (k, v) in &self.identity {
+            if !header.contains_key(k) {
+                header.insert(k.clone(), v.clone());
+            }
+        }
+        header
+    }
+}
+
+/// A blocking STOMP client over a single connection, TCP unless connected with
+/// `Client::connect_transport`.
+///
+/// Frames received with `Client::receive` borrow the client's input stream, so the next frame
+/// cannot be received until the previous frame has been dropped.
+pub struct Client {
+    reader: FrameReader<Box<dyn Transport>>,
+    writer: RefCell<FrameWriter<Box<dyn Transport>>>,
+    pending: RefCell<VecDeque<OwnedFrame>>,
+    subscriptions: RefCell<SubscriptionRegistry>,
+    next_receipt: Cell<u64>,
+    next_transaction: Cell<u64>,
+    receipt_window: Option<usize>,
+    unconfirmed: RefCell<VecDeque<Unconfirmed>>,
+    stall: Option<Stall>,
+    inactivity: RefCell<BTreeMap<String, Inactivity>>,
+    limiter: Option<RefCell<RateLimiter>>,
+    codec: Option<Box<dyn Codec>>,
+    #[cfg(feature = "metrics")]
+    metrics: RefCell<FrameMetrics>,
+    heartbeat: Intervals,
+    infer_content_type: bool,
+    identity: Option<Header>,
+    version: Version,
+    session: Option<String>,
+    server: Option<String>,
+    /// Whether the last read from the stream got past the start of a frame.
+    mid_frame: Cell<bool>,
+    /// Set once a read timed out part way through a frame, leaving the stream out of step.
+    lost_place: Cell<bool>,
+}
+
+impl Client {
+    /// Opens a TCP connection and performs the CONNECT/CONNECTED handshake. An ERROR frame in
+    /// response to CONNECT is returned as `ClientError::Broker`.
+    pub fn connect<A: ToSocketAddrs>(
+        addr: A,
+        options: ConnectOptions,
+    ) -> Result<Self, ClientError> {
+        Client::connect_transport(TcpStream::connect(addr)?, options)
+    }
+
+    /// Performs the CONNECT/CONNECTED handshake over an established connection, such as a TLS
+    /// session.
+    pub fn connect_transport<T: Transport + 'static>(
+        transport: T,
+        options: ConnectOptions,
+    ) -> Result<Self, ClientError> {
+        let mut writer = FrameWriter::new(transport.try_clone()?);
+        let reader = FrameReader::new(Box::new(transport) as Box<dyn Transport>);
+
+        let mut frame = Frame::new(Command::Connect, options.header(), Body::empty());
+        writer.write_frame(&mut frame)?;
+
+        let (version, session, server, heartbeat, codec) = {
+            let mut frame = reader.read_frame()?;
+
+            match frame.command {
+                Command::Connected => (),
+                Command::Error => return Err(broker_error(&mut frame)?),
+                _ => {
+                    return Err(ClientError::Protocol(format!(
+                        "expected CONNECTED, got {}",
+                        frame.command
+                    )))
+                }
+            }
+            (
+                frame
+                    .header
+                    .first(VERSION)
+                    .unwrap_or("1.0")
+                    .parse::<Version>()?,
+                frame.header.first(SESSION).map(str::to_owned),
+                frame.header.first(SERVER).map(str::to_owned),
+                options
+                    .heart_beat
+                    .negotiate(&HeartBeat::from_header(&frame.header)?),
+                frame
+                    .header
+                    .first(compression::COMPRESSION)
+                    .map(str::to_owned),
+            )
+        };
+        if !options.versions.contains(&version) {
+            return Err(ClientError::Protocol(format!(
+                "broker chose version {}, which was not offered",
+                version
+            )));
+        }
+        reader.set_version(version);
+        writer.set_version(version);
+
+        let mut codecs = options.codecs;
+        let codec = codec
+            .and_then(|name| codecs.iter().position(|c| c.name() == name))
+            .map(|i| codecs.swap_remove(i));
+
+        Ok(Client {
+            reader,
+            writer: RefCell::new(writer),
+            pending: RefCell::new(VecDeque::new()),
+            subscriptions: RefCell::new(SubscriptionRegistry::new()),
+            next_receipt: Cell::new(0),
+            next_transaction: Cell::new(0),
+            receipt_window: options.receipt_window,
+            unconfirmed: RefCell::new(VecDeque::new()),
+            stall: options.stall,
+            inactivity: RefCell::new(BTreeMap::new()),
+            limiter: options
+                .rate_limit
+                .map(|l| RefCell::new(RateLimiter::new(l))),
+            codec,
+            #[cfg(feature = "metrics")]
+            metrics: RefCell::new(FrameMetrics::default()),
+            heartbeat,
+            infer_content_type: options.infer_content_type,
+            identity: if options.identify_sends {
+                Some(options.identity)
+            } else {
+                None
+            },
+            version,
+            session,
+            server,
+            mid_frame: Cell::new(false),
+            lost_place: Cell::new(false),
+        })
+    }
+
+    /// The protocol version negotiated during CONNECT.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+
+    pub fn server(&self) -> Option<&str> {
+        self.server.as_deref()
+    }
+
+    /// The name of the compression codec negotiated during CONNECT.
+    pub fn compression(&self) -> Option<&str> {
+        self.codec.as_ref().map(|c| c.name())
+    }
+
+    /// The heart-beat intervals negotiated during CONNECT.
+    pub fn heartbeat(&self) -> Intervals {
+        self.heartbeat
+    }
+
+    /// Sends a heart-beat with `Transport::send_heartbeat`, a single EOL over TCP.
+    pub fn send_heartbeat(&self) -> Result<(), ClientError> {
+        self.writer.borrow_mut().get_mut().send_heartbeat()?;
+        Ok(())
+    }
+
+    /// Sends `body` to `destination`. The `content-length` header is set from the body unless
+    /// `header` already carries one, and so is `content-type` unless content type inference was
+    /// disabled with `ConnectOptions::infer_content_type`.
+    pub fn send<B: Content>(
+        &self,
+        destination: &Destination,
+        mut header: Header,
+        body: B,
+    ) -> Result<(), ClientError> {
+        header.insert(
+            DESTINATION.to_owned(),
+            vec![destination.as_str().to_owned()],
+        );
+
+        if self.infer_content_type {
+            body.write_content_type(&mut header);
+        }
+
+        if let Some(identity) = &self.identity {
+            for (k, v) in identity {
+                if !header.contains_key(k) {
+                    header.insert(k.clone(), v.clone());
+                }
+            }
+        }
+
+        if let Some(codec) = &self.codec {
+            if !body.as_bytes().is_empty() {
+                let compressed = codec.compress(body.as_bytes())?;
+                header.insert(CONTENT_ENCODING.to_owned(), vec![codec.name().to_owned()]);
+                return self.write_send(destination, header, &compressed);
+            }
+        }
+        self.write_send(destination, header, body.as_bytes())
+    }
+
+    fn write_send(
+        &self,
+        destination: &Destination,
+        header: Header,
+        body: &[u8],
+    ) -> Result<(), ClientError> {
+        if let Some(limiter) = &self.limiter {
+            let wait = limiter
+                .borrow_mut()
+                .acquire(destination.as_str(), Instant::now());
+
+            if wait > Duration::from_secs(0) {
+                thread::sleep(wait);
+            }
+        }
+        self.write(Command::Send, header, body)
+    }
+
+    /// Subscribes to `destination`. Messages for the subscription are read with
+    /// `Subscription::next_message`, and it is unsubscribed when dropped.
+    pub fn subscribe(
+        &self,
+        destination: &Destination,
+        ack: AckMode,
+    ) -> Result<Subscription<'_>, ClientError> {
+        let id = self.write_subscribe(destination, ack, None, &[])?;
+        Ok(Subscription::new(self, id))
+    }
+
+    /// Like `Client::subscribe`, but only returns once the broker has confirmed the subscription
+    /// with a RECEIPT. Frames received in the meantime are kept for `Client::receive`.
+    pub fn subscribe_with_receipt(
+        &self,
+        destination: &Destination,
+        ack: AckMode,
+    ) -> Result<Subscription<'_>, ClientError> {
+        let receipt = self.receipt_id();
+        let id = self.write_subscribe(destination, ack, Some(&receipt), &[])?;
+
+        if let Err(e) = self.wait_for_receipt(&receipt) {
+            self.subscriptions.borrow_mut().remove(&id);
+            return Err(e);
+        }
+        Ok(Subscription::new(self, id))
+    }
+
+    /// Takes a single message from `destination`: subscribes asking the broker to deliver one
+    /// message at a time, waits for a message, acknowledges it and unsubscribes. Returns `None`
+    /// if no data arrives within `timeout`, which bounds each wait rather than the whole call.
+    pub fn receive_one(
+        &self,
+        destination: &Destination,
+        timeout: Duration,
+    ) -> Result<Option<OwnedFrame>, ClientError> {
+        let id =
+            self.write_subscribe(destination, AckMode::ClientIndividual, None, PREFETCH_ONE)?;
+        let subscription = Subscription::new(self, id);
+
+        let message = match self.receive_within(subscription.id(), timeout)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        self.write_message_ack(Command::Ack, message.command, &message.header, None)?;
+        subscription.unsubscribe()?;
+        Ok(Some(message))
+    }
+
+    /// Consumes messages from `destination` until none arrives for `idle_timeout`, passing each
+    /// to `handler`. Messages are acknowledged as they are handled: with ACK if `handler` returns
+    /// true and with NACK otherwise. The subscription is removed before returning.
+    pub fn drain<F>(
+        &self,
+        destination: &Destination,
+        mut handler: F,
+        idle_timeout: Duration,
+    ) -> Result<Drained, ClientError>
+    where
+        F: FnMut(&OwnedFrame) -> bool,
+    {
+        let id = self.write_subscribe(destination, AckMode::ClientIndividual, None, &[])?;
+        let subscription = Subscription::new(self, id);
+        let mut drained = Drained::default();
+
+        while let Some(message) = self.receive_within(subscription.id(), idle_timeout)? {
+            let command = if handler(&message) {
+                drained.acked += 1;
+                Command::Ack
+            } else {
+                drained.nacked += 1;
+                Command::Nack
+            };
+            self.write_message_ack(command, message.command, &message.header, None)?;
+        }
+        subscription.unsubscribe()?;
+        Ok(drained)
+    }
+
+    pub fn unsubscribe(&self, id: &SubscriptionId) -> Result<(), ClientError> {
+        self.write_unsubscribe(id, None)
+    }
+
+    /// Like `Client::unsubscribe`, but only returns once the broker has confirmed with a RECEIPT.
+    /// Frames received in the meantime are kept for `Client::receive`.
+    pub fn unsubscribe_with_receipt(&self, id: &SubscriptionId) -> Result<(), ClientError> {
+        let receipt = self.receipt_id();
+        self.write_unsubscribe(id, Some(&receipt))?;
+        self.wait_for_receipt(&receipt)
+    }
+
+    /// Acknowledges a message, where `id` is the value of the MESSAGE frame's `ack` header.
+    pub fn ack(&self, id: &str) -> Result<(), ClientError> {
+        self.write_ack(Command::Ack, id, None)
+    }
+
+    pub fn nack(&self, id: &str) -> Result<(), ClientError> {
+        self.write_ack(Command::Nack, id, None)
+    }
+
+    /// Acknowledges a received MESSAGE frame, identifying it the way the negotiated protocol
+    /// version requires; see `message::ack_header`.
+    pub fn ack_message(&self, message: &Frame<'_>) -> Result<(), ClientError> {
+        self.write_message_ack(Command::Ack, message.command, &message.header, None)
+    }
+
+    /// Like `Client::ack_message`, for NACK. STOMP 1.0 has no NACK frame, so this fails on a
+    /// 1.0 connection.
+    pub fn nack_message(&self, message: &Frame<'_>) -> Result<(), ClientError> {
+        self.write_message_ack(Command::Nack, message.command, &message.header, None)
+    }
+
+    /// Starts a transaction. Frames sent through the returned handle are only processed by the
+    /// broker once it is committed.
+    pub fn begin(&self) -> Result<Transaction<'_>, ClientError> {
+        let id = self.next_transaction.get();
+        self.next_transaction.set(id + 1);
+
+        let id = format!("tx-{}", id);
+        self.write_transaction(Command::Begin, &id)?;
+        Ok(Transaction::new(self, id))
+    }
+
+    /// Reads the next frame sent by the broker. RECEIPT frames confirming frames in the receipt
+    /// window are consumed rather than returned.
+    pub fn receive(&self) -> Result<Frame<'_>, ClientError> {
+        let frame = self.next_frame()?;
+
+        #[cfg(feature = "metrics")]
+        let frame = self.measure(frame);
+
+        Ok(frame)
+    }
+
+    /// Body sizes and destinations of the frames sent and received so far.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> std::cell::Ref<'_, FrameMetrics> {
+        self.metrics.borrow()
+    }
+
+    /// Like `Client::receive_message`, with the body read, but returns `None` if no frame starts
+    /// within `timeout`. A timeout once a frame has started is returned as an error, and the
+    /// client can read no further frames, since part of the frame has been consumed.
+    fn receive_within(
+        &self,
+        id: &SubscriptionId,
+        timeout: Duration,
+    ) -> Result<Option<OwnedFrame>, ClientError> {
+        self.writer
+            .borrow()
+            .get_ref()
+            .set_read_timeout(Some(timeout))?;
+        let message = self
+            .receive_message(id)
+            .and_then(|frame| frame.into_owned().map_err(ClientError::from));
+        self.writer.borrow().get_ref().set_read_timeout(None)?;
+
+        match message {
+            Ok(message) => Ok(Some(message)),
+            Err(e) if is_timeout(&e) && !self.mid_frame.get() => Ok(None),
+            Err(e) => {
+                if is_timeout(&e) {
+                    self.lost_place.set(true);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Reads the next MESSAGE frame for subscription `id`, keeping any other frames for
+    /// `Client::receive`.
+    fn receive_message(&self, id: &SubscriptionId) -> Result<Frame<'_>, ClientError> {
+        let frame = self.next_message(id)?;
+
+        #[cfg(feature = "metrics")]
+        let frame = self.measure(frame);
+
+        Ok(frame)
+    }
+
+    /// Counts a received frame by destination now, and by body size once its body has been read.
+    #[cfg(feature = "metrics")]
+    fn measure<'a>(&'a self, frame: Frame<'a>) -> Frame<'a> {
+        self.metrics
+            .borrow_mut()
+            .record_received(frame.command, &frame.header, None);
+
+        if !matches!(frame.command, Command::Send | Command::Message) {
+            return frame;
+        }
+        let length = frame
+            .header
+            .first_ignore_case(CONTENT_LENGTH)
+            .and_then(|v| v.parse().ok());
+        let metrics = &self.metrics;
+        let body = frame.body.map_reader(|reader| MeteredReader {
+            reader,
+            metrics,
+            length,
+            read: 0,
+            recorded: false,
+        });
+        Frame::new(frame.command, frame.header, body)
+    }
+
+    /// Reads a frame from the stream, noting MESSAGE frames for `Client::check_inactivity`.
+    fn read_frame(&self) -> Result<Frame<'_>, ClientError> {
+        if self.lost_place.get() {
+            return Err(ClientError::Protocol(
+                "a read timed out part way through a frame".to_owned(),
+            ));
+        }
+        self.check_inactivity();
+        self.mid_frame.set(false);
+        self.reader.skip_eols()?;
+        self.mid_frame.set(true);
+        let frame = self.reader.read_frame()?;
+
+        if frame.command == Command::Message {
+            let mut inactivity = self.inactivity.borrow_mut();
+            let watch = frame
+                .header
+                .first(SUBSCRIPTION)
+                .and_then(|id| inactivity.get_mut(id));
+
+            if let Some(watch) = watch {
+                watch.last = Instant::now();
+                watch.reported = false;
+            }
+        }
+        Ok(frame)
+    }
+
+    fn next_frame(&self) -> Result<Frame<'_>, ClientError> {
+        let pending = self.pending.borrow_mut().pop_front();
+
+        if let Some(frame) = pending {
+            return self.replay(frame);
+        }
+
+        loop {
+            self.check_stall();
+            let frame = self.read_frame()?;
+
+            if self.confirm(&frame) {
+                continue;
+            }
+            return self.accept(frame);
+        }
+    }
+
+    fn next_message(&self, id: &SubscriptionId) -> Result<Frame<'_>, ClientError> {
+        let position = self
+            .pending
+            .borrow()
+            .iter()
+            .position(|f| is_message_for(f.command, &f.header, id));
+
+        if let Some(frame) = position.and_then(|i| self.pending.borrow_mut().remove(i)) {
+            return self.replay(frame);
+        }
+
+        loop {
+            self.check_stall();
+            let mut frame = self.read_frame()?;
+
+            match frame.command {
+                _ if is_message_for(frame.command, &frame.header, id) => return self.accept(frame),
+                Command::Error => return Err(broker_error(&mut frame)?),
+                _ if self.confirm(&frame) => (),
+                _ => self.pending.borrow_mut().push_back(frame.into_owned()?),
+            }
+        }
+    }
+
+    /// Returns a frame kept from an earlier read, with its body decompressed.
+    fn replay(&self, frame: OwnedFrame) -> Result<Frame<'_>, ClientError> {
+        let frame = self.decompress(frame)?;
+        let body = Body::new(io::Cursor::new(frame.body));
+        Ok(Frame::new(frame.command, frame.header, body))
+    }
+
+    /// Returns a frame just read from the stream, reading its body first if it has to be
+    /// decompressed.
+    fn accept<'a>(&self, frame: Frame<'a>) -> Result<Frame<'a>, ClientError> {
+        if self.codec.is_some() && frame.header.contains_key(CONTENT_ENCODING) {
+            let frame = self.decompress(frame.into_owned()?)?;
+            let body = Body::new(io::Cursor::new(frame.body));
+            return Ok(Frame::new(frame.command, frame.header, body));
+        }
+        Ok(frame)
+    }
+
+    /// Decompresses a body compressed with the negotiated codec.
+    fn decompress(&self, mut frame: OwnedFrame) -> Result<OwnedFrame, ClientError> {
+        let codec = match &self.codec {
+            Some(codec) => codec,
+            None => return Ok(frame),
+        };
+
+        if frame.header.first(CONTENT_ENCODING) == Some(codec.name()) {
+            frame.body = codec.decompress(&frame.body)?;
+            frame.header.remove(CONTENT_ENCODING);
+            frame.header.remove_ignore_case(CONTENT_LENGTH);
+        }
+        Ok(frame)
+    }
+
+    /// The number of frames in the receipt window that the broker has not yet confirmed.
+    pub fn unconfirmed(&self) -> usize {
+        self.unconfirmed.borrow().len()
+    }
+
+    /// The occupancy of the receipt window, or `None` if the client was not connected with
+    /// `ConnectOptions::receipt_window`.
+    pub fn window_stats(&self) -> Option<WindowStats> {
+        let max = self.receipt_window?;
+        let unconfirmed = self.unconfirmed.borrow();
+
+        Some(WindowStats {
+            unconfirmed: unconfirmed.len(),
+            max,
+            oldest: unconfirmed.front().map(|u| u.sent.elapsed()),
+        })
+    }
+
+    /// Calls the callbacks set with `Subscription::on_inactivity` for subscriptions that have
+    /// gone longer than their interval without a MESSAGE frame.
+    pub fn check_inactivity(&self) {
+        let now = Instant::now();
+        let mut inactive = Vec::new();
+
+        for (id, watch) in self.inactivity.borrow_mut().iter_mut() {
+            let silence = now.duration_since(watch.last);
+
+            if !watch.reported && silence >= watch.interval {
+                watch.reported = true;
+                inactive.push((
+                    SubscriptionId::new(id.as_str()),
+                    silence,
+                    watch.callback.clone(),
+                ));
+            }
+        }
+
+        for (id, silence, callback) in inactive {
+            callback(&id, silence);
+        }
+    }
+
+    pub(super) fn watch_inactivity(
+        &self,
+        id: &SubscriptionId,
+        interval: Duration,
+        callback: InactivityCallback,
+    ) {
+        let watch = Inactivity {
+            interval,
+            last: Instant::now(),
+            reported: false,
+            callback,
+        };
+        self.inactivity
+            .borrow_mut()
+            .insert(id.as_str().to_owned(), watch);
+    }
+
+    /// Calls the `ConnectOptions::on_stall` callback if the oldest unconfirmed frame has waited
+    /// for longer than the threshold and has not been reported yet.
+    pub fn check_stall(&self) {
+        let (stall, stats) = match (&self.stall, self.window_stats()) {
+            (Some(stall), Some(stats)) => (stall, stats),
+            _ => return,
+        };
+
+        match stats.oldest {
+            Some(age) if age >= stall.threshold => (),
+            _ => return,
+        }
+        let oldest = self.unconfirmed.borrow().front().map(|u| u.receipt.clone());
+
+        if *stall.reported.borrow() != oldest {
+            stall.reported.replace(oldest);
+            (stall.callback)(&stats);
+        }
+    }
+
+    /// Blocks until the broker has confirmed every frame in the receipt window. Frames received
+    /// in the meantime are kept for `Client::receive`.
+    pub fn wait_for_confirmations(&self) -> Result<(), ClientError> {
+        while self.unconfirmed() > 0 {
+            self.read_pending()?;
+        }
+        Ok(())
+    }
+
+    /// Sends DISCONNECT and waits for the broker's RECEIPT. The client then closes its writing
+    /// half of the connection and keeps reading for up to a second, until the broker closes its
+    /// side, so in-flight data is not lost to a connection reset. Frames received after
+    /// DISCONNECT are discarded.
+    pub fn disconnect(self) -> Result<(), ClientError> {
+        self.end_session()
+    }
+
+    fn end_session(&self) -> Result<(), ClientError> {
+        let receipt = self.receipt_id();
+        let mut header = Header::new();
+        header.push(RECEIPT, receipt.clone());
+        self.write(Command::Disconnect, header, &[])?;
+        self.wait_for_receipt(&receipt)?;
+
+        self.shutdown_write()?;
+        self.linger(DISCONNECT_LINGER)?;
+        self.shutdown_read()
+    }
+
+    /// Moves the session to the broker at `addr` at the time `at`, for planned maintenance of
+    /// the current broker. The new connection is made right away and every subscription is made
+    /// again on it under the same id, each confirmed with a RECEIPT. At `at`, this client
+    /// disconnects and the returned client takes over. Subscriptions are kept by id, as with
+    /// `Subscription::into_id`, and frames not yet returned by `Client::receive`, including
+    /// those that arrive before the old broker confirms the DISCONNECT, are carried over.
+    ///
+    /// The client in a `MigrationError` is the one that holds the session: this one if the new
+    /// session could not be set up, and the new one if disconnecting from the old broker failed.
+    pub fn migrate<A: ToSocketAddrs>(
+        self,
+        addr: A,
+        options: ConnectOptions,
+        at: Instant,
+    ) -> Result<Client, MigrationError> {
+        let client = match self.resubscribe_at(addr, options) {
+            Ok(client) => client,
+            Err(error) => {
+                return Err(MigrationError {
+                    client: Box::new(self),
+                    error,
+                })
+            }
+        };
+        let now = Instant::now();
+
+        if at > now {
+            thread::sleep(at - now);
+        }
+        let result = self.end_session();
+
+        let mut pending = self.pending.take();
+        pending.extend(client.pending.take());
+        client.pending.replace(pending);
+        client.inactivity.replace(self.inactivity.take());
+
+        match result {
+            Ok(()) => Ok(client),
+            Err(error) => Err(MigrationError {
+                client: Box::new(client),
+                error,
+            }),
+        }
+    }
+
+    /// Connects to `addr` and makes every subscription of this client again on the new
+    /// connection.
+    fn resubscribe_at<A: ToSocketAddrs>(
+        &self,
+        addr: A,
+        options: ConnectOptions,
+    ) -> Result<Client, ClientError> {
+        let client = Client::connect(addr, options)?;
+
+        for (id, entry) in self.subscriptions.borrow().iter() {
+            let receipt = client.receipt_id();
+            client.write_subscribe_as(
+                id.clone(),
+                &entry.destination,
+                entry.ack,
+                Some(&receipt),
+                &[],
+            )?;
+            client.wait_for_receipt(&receipt)?;
+        }
+        Ok(client)
+    }
+
+    /// Closes the reading half of the connection.
+    pub fn shutdown_read(&self) -> Result<(), ClientError> {
+        self.shutdown(Shutdown::Read)
+    }
+
+    /// Closes the writing half of the connection. The broker sees the end of the stream, and
+    /// frames can still be received.
+    pub fn shutdown_write(&self) -> Result<(), ClientError> {
+        self.shutdown(Shutdown::Write)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> Result<(), ClientError> {
+        match self.writer.borrow().get_ref().shutdown(how) {
+            Err(e) if e.kind() != io::ErrorKind::NotConnected => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Discards incoming data until the broker closes the connection or `timeout` passes.
+    fn linger(&self, timeout: Duration) -> Result<(), ClientError> {
+        let mut writer = self.writer.borrow_mut();
+        let stream = writer.get_mut();
+        let deadline = Instant::now() + timeout;
+        let mut buffer = [0; 512];
+
+        loop {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Ok(());
+            }
+            stream.set_read_timeout(Some(deadline - now))?;
+
+            match stream.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(())
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn write_subscribe(
+        &self,
+        destination: &Destination,
+        ack: AckMode,
+        receipt: Option<&str>,
+        extra: &[(&str, &str)],
+    ) -> Result<SubscriptionId, ClientError> {
+        let id = self.subscriptions.borrow_mut().next_id();
+        self.write_subscribe_as(id, destination, ack, receipt, extra)
+    }
+
+    fn write_subscribe_as(
+        &self,
+        id: SubscriptionId,
+        destination: &Destination,
+        ack: AckMode,
+        receipt: Option<&str>,
+        extra: &[(&str, &str)],
+    ) -> Result<SubscriptionId, ClientError> {
+        let mut header = Header::new();
+        header.push(ID, id.as_str().to_owned());
+        header.push(DESTINATION, destination.as_str().to_owned());
+        header.push(ACK, ack.to_string());
+
+        for (k, v) in extra {
+            header.push(*k, (*v).to_owned());
+        }
+
+        if let Some(receipt) = receipt {
+            header.push(RECEIPT, receipt.to_owned());
+        }
+        self.write(Command::Subscribe, header, &[])?;
+
+        self.subscriptions
+            .borrow_mut()
+            .insert(id.clone(), destination.clone(), ack)?;
+        Ok(id)
+    }
+
+    fn write_unsubscribe(
+        &self,
+        id: &SubscriptionId,
+        receipt: Option<&str>,
+    ) -> Result<(), ClientError> {
+        self.inactivity.borrow_mut().remove(id.as_str());
+
+        if self.subscriptions.borrow_mut().remove(id).is_none() {
+            return Err(ClientError::Protocol(format!(
+                "unknown subscription {}",
+                id
+            )));
+        }
+        let mut header = Header::new();
+        header.push(ID, id.as_str().to_owned());
+
+        if let Some(receipt) = receipt {
+            header.push(RECEIPT, receipt.to_owned());
+        }
+        self.write(Command::Unsubscribe, header, &[])
+    }
+
+    /// Reads frames until the RECEIPT for `receipt` arrives, keeping any other frames for
+    /// `Client::receive`. An ERROR frame is returned as `ClientError::Broker`.
+    fn wait_for_receipt(&self, receipt: &str) -> Result<(), ClientError> {
+        loop {
+            let mut frame = self.read_frame()?;
+
+            match frame.command {
+                Command::Receipt if frame.header.first(RECEIPT_ID) == Some(receipt) => {
+                    return Ok(())
+                }
+                Command::Error => return Err(broker_error(&mut frame)?),
+                _ if self.confirm(&frame) => (),
+                _ => self.pending.borrow_mut().push_back(frame.into_owned()?),
+            }
+        }
+    }
+
+    /// Reads one frame, which either confirms a frame in the receipt window or is kept for
+    /// `Client::receive`. An ERROR frame is returned as `ClientError::Broker`.
+    fn read_pending(&self) -> Result<(), ClientError> {
+        self.check_stall();
+        let mut frame = self.read_frame()?;
+
+        match frame.command {
+            Command::Error => Err(broker_error(&mut frame)?),
+            _ if self.confirm(&frame) => Ok(()),
+            _ => {
+                self.pending.borrow_mut().push_back(frame.into_owned()?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the frame confirmed by a RECEIPT from the receipt window, returning whether there
+    /// was one.
+    fn confirm(&self, frame: &Frame<'_>) -> bool {
+        if frame.command != Command::Receipt {
+            return false;
+        }
+        let receipt = frame.header.first(RECEIPT_ID);
+        let mut unconfirmed = self.unconfirmed.borrow_mut();
+
+        match unconfirmed
+            .iter()
+            .position(|u| Some(u.receipt.as_str()) == receipt)
+        {
+            Some(i) => {
+                unconfirmed.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn write_ack(
+        &self,
+        command: Command,
+        id: &str,
+        transaction: Option<&str>,
+    ) -> Result<(), ClientError> {
+        let mut header = Header::new();
+        header.push(ID, id.to_owned());
+        self.write_ack_header(command, header, transaction)
+    }
+
+    fn write_message_ack(
+        &self,
+        command: Command,
+        message: Command,
+        header: &Header,
+        transaction: Option<&str>,
+    ) -> Result<(), ClientError> {
+        if message != Command::Message {
+            return Err(ClientError::Protocol(format!(
+                "cannot acknowledge a {} frame",
+                message
+            )));
+        }
+
+        if command == Command::Nack && self.version == Version::V1_0 {
+            return Err(ClientError::Protocol("NACK requires STOMP 1.1".to_owned()));
+        }
+
+        match message::ack_header(header, self.version) {
+            Some(header) => self.write_ack_header(command, header, transaction),
+            None => Err(ClientError::Protocol(format!(
+                "MESSAGE frame lacks the headers to {} it under STOMP {}",
+                command, self.version
+            ))),
+        }
+    }
+
+    fn write_ack_header(
+        &self,
+        command: Command,
+        mut header: Header,
+        transaction: Option<&str>,
+    ) -> Result<(), ClientError> {
+        if let Some(transaction) = transaction {
+            header.push(transaction::TRANSACTION, transaction.to_owned());
+        }
+        self.write(command, header, &[])
+    }
+
+    fn write_transaction(&self, command: Command, id: &str) -> Result<(), ClientError> {
+        let mut header = Header::new();
+        header.push(transaction::TRANSACTION, id.to_owned());
+        self.write(command, header, &[])
+    }
+
+    fn receipt_id(&self) -> String {
+        let id = self.next_receipt.get();
+        self.next_receipt.set(id + 1);
+        format!("receipt-{}", id)
+    }
+
+    fn write(&self, command: Command, mut header: Header, body: &[u8]) -> Result<(), ClientError> {
+        let tracked = match self.receipt_window {
+            Some(max) if is_tracked(command) && !header.contains_key(RECEIPT) => {
+                while self.unconfirmed() >= max {
+                    self.read_pending()?;
+                }
+                let receipt = self.receipt_id();
+                header.push(RECEIPT, receipt.clone());
+                Some(receipt)
+            }
+            _ => None,
+        };
+
+        let mut frame = Frame::new(command, header, Body::new(body));
+        self.writer.borrow_mut().write_frame(&mut frame)?;
+
+        #[cfg(feature = "metrics")]
+        self.metrics.borrow_mut().record_sent(
+            frame.command,
+            &frame.header,
+            Some(body.len() as u64),
+        );
+
+        if let Some(receipt) = tracked {
+            self.unconfirmed.borrow_mut().push_back(Unconfirmed {
+                receipt,
+                sent: Instant::now(),
+            });
+        }
+        self.check_stall();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowStats {
+    /// Frames written but not yet confirmed by a RECEIPT.
+    pub unconfirmed: usize,
+    pub max: usize,
+    /// How long the oldest unconfirmed frame has been waiting.
+    pub oldest: Option<Duration>,
+}
+
+/// The messages handled by `Client::drain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Drained {
+    pub acked: u64,
+    pub nacked: u64,
+}
+
+/// A frame written in receipt window mode whose RECEIPT has not arrived yet.
+struct Unconfirmed {
+    receipt: String,
+    sent: Instant,
+}
+
+type InactivityCallback = Rc<dyn Fn(&SubscriptionId, Duration)>;
+
+/// The message interval expected of a subscription, see `Subscription::on_inactivity`.
+struct Inactivity {
+    interval: Duration,
+    last: Instant,
+    reported: bool,
+    callback: InactivityCallback,
+}
+
+struct Stall {
+    threshold: Duration,
+    callback: Box<dyn Fn(&WindowStats)>,
+    reported: RefCell<Option<String>>,
+}
+
+fn is_tracked(command: Command) -> bool {
+    matches!(
+        command,
+        Command::Send | Command::Subscribe | Command::Unsubscribe | Command::Ack | Command::Nack
+    )
+}
+
+fn is_message_for(command: Command, header: &Header, id: &SubscriptionId) -> bool {
+    command == Command::Message
+        && header
+            .get(SUBSCRIPTION)
+            .and_then(|v| v.first())
+            .map(|s| s.as_str())
+            == Some(id.as_str())
+}
+
+fn is_timeout(e: &ClientError) -> bool {
+    let e = match e {
+        ClientError::Io(e) | ClientError::Read(ReadError::Io(e)) => e,
+        _ => return false,
+    };
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn broker_error(frame: &mut Frame<'_>) -> Result<ClientError, ClientError> {
+    let message = frame.header.first(MESSAGE).unwrap_or_default().to_owned();
+    let mut body: Vec<u8> = Vec::new();
+    frame.body.read_to_end(&mut body)?;
+    Ok(ClientError::Broker(BrokerError::new(message, body)))
+}
+
+/// Counts the bytes read of a received body, and records them in the client's metrics when the
+/// body ends. A body dropped before its end is recorded by its `content-length`, if it has one.
+#[cfg(feature = "metrics")]
+struct MeteredReader<'a> {
+    reader: Box<dyn Read + 'a>,
+    metrics: &'a RefCell<FrameMetrics>,
+    length: Option<u64>,
+    read: u64,
+    recorded: bool,
+}
+
+#[cfg(feature = "metrics")]
+impl MeteredReader<'_> {
+    fn record(&mut self, size: u64) {
+        if !self.recorded {
+            self.recorded = true;
+            self.metrics.borrow_mut().received.record(size);
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Read for MeteredReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+
+        if n == 0 && !buf.is_empty() {
+            self.record(self.read);
+        }
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for MeteredReader<'_> {
+    fn drop(&mut self) {
+        if let Some(length) = self.length {
+            self.record(length);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::str;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+
+    pub(super) fn read_all(frame: &mut Frame<'_>) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+        frame.body.read_to_end(&mut buffer).unwrap();
+        buffer
+    }
+
+    pub(super) fn server<F>(handler: F) -> (String, JoinHandle<()>)
+    where
+        F: FnOnce(&FrameReader<TcpStream>, &TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let writer = stream.try_clone().unwrap();
+            let reader = FrameReader::new(stream);
+            handler(&reader, &writer);
+        });
+        (addr, handle)
+    }
+
+    pub(super) fn reply(
+        writer: &TcpStream,
+        command: Command,
+        header: &[(&str, &str)],
+        body: &[u8],
+    ) {
+        let mut h = Header::new();
+
+        for (k, v) in header {
+            h.push(*k, (*v).to_owned());
+        }
+        let mut frame = Frame::new(command, h, Body::new(body));
+        frame.write_to(writer).unwrap();
+    }
+
+    pub(super) fn accept_connect(reader: &FrameReader<TcpStream>, writer: &TcpStream) {
+        let frame = reader.read_frame().unwrap();
+        assert_eq!(Command::Connect, frame.command);
+        assert_eq!(Some("1.2"), frame.header.first("accept-version"));
+        drop(frame);
+        reply(
+            writer,
+            Command::Connected,
+            &[("version", "1.2"), ("session", "s-1"), ("server", "test")],
+            &[],
+        );
+    }
+
+    #[test]
+    fn connect() {
+        let (addr, handle) = server(|reader, writer| {
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("/"), frame.header.first("host"));
+            assert_eq!(Some("guest"), frame.header.first("login"));
+            drop(frame);
+            reply(writer, Command::Connected, &[("version", "1.2")], &[]);
+        });
+        let options = ConnectOptions::new("/").credentials("guest", "secret");
+        let client = Client::connect(addr, options).unwrap();
+        assert_eq!(Version::V1_2, client.version());
+        assert_eq!(None, client.session());
+        assert_eq!(Intervals::default(), client.heartbeat());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connect_transport() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            assert_eq!(Command::Send, reader.read_frame().unwrap().command);
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+        let client = Client::connect_transport(stream, ConnectOptions::new("/")).unwrap();
+        assert_eq!(Some("s-1"), client.session());
+
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), "hello").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn identity() {
+        let (addr, handle) = server(|reader, writer| {
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("billing"), frame.header.first("app-name"));
+            assert_eq!(Some("/"), frame.header.first("host"));
+            drop(frame);
+            reply(writer, Command::Connected, &[("version", "1.2")], &[]);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("billing"), frame.header.first("app-name"));
+            assert_eq!(vec!["i-2".to_owned()], frame.header["instance-id"]);
+        });
+        let options = ConnectOptions::new("/")
+            .identity("app-name", "billing")
+            .identity("instance-id", "i-1")
+            .identity("host", "ignored")
+            .identify_sends(true);
+        let client = Client::connect(addr, options).unwrap();
+
+        let mut header = Header::new();
+        header.push("instance-id", "i-2".to_owned());
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, header, "hello").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connect_heartbeat() {
+        let (addr, handle) = server(|reader, writer| {
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("1000,2000"), frame.header.first("heart-beat"));
+            drop(frame);
+            reply(
+                writer,
+                Command::Connected,
+                &[("version", "1.2"), ("heart-beat", "3000,500")],
+                &[],
+            );
+
+            let mut buffer = [0; 1];
+            let mut stream = writer;
+            stream.read_exact(&mut buffer).unwrap();
+            assert_eq!(b"\n", &buffer);
+        });
+        let heart_beat = HeartBeat::new(Duration::from_millis(1000), Duration::from_millis(2000));
+        let options = ConnectOptions::new("/").heart_beat(heart_beat);
+        let client = Client::connect(addr, options).unwrap();
+
+        let intervals = client.heartbeat();
+        assert_eq!(Some(Duration::from_millis(1000)), intervals.send);
+        assert_eq!(Some(Duration::from_millis(3000)), intervals.receive);
+        client.send_heartbeat().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connect_older_version() {
+        let (addr, handle) = server(|reader, writer| {
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("1.1,1.2"), frame.header.first("accept-version"));
+            drop(frame);
+            reply(writer, Command::Connected, &[("version", "1.1")], &[]);
+        });
+        let options = ConnectOptions::new("/").accept_versions(&[Version::V1_1, Version::V1_2]);
+        let client = Client::connect(addr, options).unwrap();
+        assert_eq!(Version::V1_1, client.version());
+        assert_eq!(Version::V1_1, client.writer.borrow().version());
+        assert_eq!(Version::V1_1, client.reader.version());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn ack_message_by_version() {
+        let (addr, handle) = server(|reader, writer| {
+            drop(reader.read_frame().unwrap());
+            reply(writer, Command::Connected, &[("version", "1.1")], &[]);
+            reply(
+                writer,
+                Command::Message,
+                &[
+                    ("subscription", "sub-0"),
+                    ("message-id", "m-1"),
+                    ("ack", "a-1"),
+                ],
+                &[],
+            );
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Nack, frame.command);
+            assert_eq!(Some("m-1"), frame.header.first("message-id"));
+            assert_eq!(Some("sub-0"), frame.header.first("subscription"));
+            assert_eq!(None, frame.header.first("id"));
+        });
+        let options = ConnectOptions::new("/").accept_versions(&[Version::V1_1]);
+        let client = Client::connect(addr, options).unwrap();
+
+        let frame = client.receive().unwrap();
+        client.nack_message(&frame).unwrap();
+        drop(frame);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connect_error() {
+        let (addr, handle) = server(|reader, writer| {
+            drop(reader.read_frame().unwrap());
+            reply(
+                writer,
+                Command::Error,
+                &[("message", "bad credentials")],
+                b"denied",
+            );
+        });
+        let result = Client::connect(addr, ConnectOptions::new("/"));
+
+        match result {
+            Err(ClientError::Broker(e)) => {
+                assert_eq!("bad credentials", e.message);
+                assert_eq!(b"denied".to_vec(), e.body);
+            }
+            _ => panic!("expected a broker error"),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn send_subscribe_ack() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let mut frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Send, frame.command);
+            assert_eq!(Some("/queue/a"), frame.header.first("destination"));
+            assert_eq!(Some("5"), frame.header.first("content-length"));
+            assert_eq!(
+                Some("application/octet-stream"),
+                frame.header.first("content-type")
+            );
+            assert_eq!(b"hello".to_vec(), read_all(&mut frame));
+            drop(frame);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Subscribe, frame.command);
+            assert_eq!(Some("sub-0"), frame.header.first("id"));
+            assert_eq!(Some("client"), frame.header.first("ack"));
+            drop(frame);
+
+            reply(
+                writer,
+                Command::Message,
+                &[
+                    ("subscription", "sub-0"),
+                    ("message-id", "m-1"),
+                    ("ack", "a-1"),
+                    ("destination", "/queue/a"),
+                ],
+                b"world",
+            );
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Ack, frame.command);
+            assert_eq!(Some("a-1"), frame.header.first("id"));
+            drop(frame);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Unsubscribe, frame.command);
+            assert_eq!(Some("sub-0"), frame.header.first("id"));
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        assert_eq!(Some("s-1"), client.session());
+        assert_eq!(Some("test"), client.server());
+
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), b"hello").unwrap();
+        let subscription = client.subscribe(&destination, AckMode::Client).unwrap();
+
+        let mut frame = client.receive().unwrap();
+        assert_eq!(Command::Message, frame.command);
+        assert_eq!("world", str::from_utf8(&read_all(&mut frame)).unwrap());
+        let ack = frame.header.first("ack").unwrap().to_owned();
+        drop(frame);
+
+        client.ack(&ack).unwrap();
+        client.unsubscribe(subscription.id()).unwrap();
+        assert!(client.unsubscribe(subscription.id()).is_err());
+        drop(subscription);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn receive_one() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Subscribe, frame.command);
+            assert_eq!(Some("client-individual"), frame.header.first("ack"));
+            assert_eq!(Some("1"), frame.header.first("activemq.prefetchSize"));
+            drop(frame);
+
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0"), ("ack", "a-1")],
+                b"job",
+            );
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Ack, frame.command);
+            assert_eq!(Some("a-1"), frame.header.first("id"));
+            drop(frame);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Unsubscribe, frame.command);
+            assert_eq!(Some("sub-0"), frame.header.first("id"));
+            drop(frame);
+
+            assert_eq!(Command::Subscribe, reader.read_frame().unwrap().command);
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Unsubscribe, frame.command);
+            assert_eq!(Some("sub-1"), frame.header.first("id"));
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/jobs").unwrap();
+
+        let message = client
+            .receive_one(&destination, Duration::from_secs(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(b"job".to_vec(), message.body);
+
+        let message = client
+            .receive_one(&destination, Duration::from_millis(50))
+            .unwrap();
+        assert!(message.is_none());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn receive_one_timeout_mid_frame() {
+        let (done, finished) = mpsc::channel::<()>();
+        let (addr, handle) = server(move |reader, writer| {
+            accept_connect(reader, writer);
+            assert_eq!(Command::Subscribe, reader.read_frame().unwrap().command);
+
+            let mut stream = writer;
+            stream
+                .write_all(b"MESSAGE\nsubscription:sub-0\ncontent-length:10\n\nhello")
+                .unwrap();
+            finished.recv().unwrap();
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/jobs").unwrap();
+
+        let error = client
+            .receive_one(&destination, Duration::from_millis(50))
+            .unwrap_err();
+        assert!(is_timeout(&error));
+        assert!(matches!(client.receive(), Err(ClientError::Protocol(_))));
+        done.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn drain() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            assert_eq!(Command::Subscribe, reader.read_frame().unwrap().command);
+
+            for (ack, body) in [("a-1", "ok"), ("a-2", "bad"), ("a-3", "ok")].iter() {
+                reply(
+                    writer,
+                    Command::Message,
+                    &[("subscription", "sub-0"), ("ack", ack)],
+                    body.as_bytes(),
+                );
+            }
+
+            for (command, ack) in [
+                (Command::Ack, "a-1"),
+                (Command::Nack, "a-2"),
+                (Command::Ack, "a-3"),
+            ]
+            .iter()
+            {
+                let frame = reader.read_frame().unwrap();
+                assert_eq!(*command, frame.command);
+                assert_eq!(Some(*ack), frame.header.first("id"));
+            }
+            assert_eq!(Command::Unsubscribe, reader.read_frame().unwrap().command);
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/jobs").unwrap();
+
+        let drained = client
+            .drain(
+                &destination,
+                |message| message.body == b"ok",
+                Duration::from_millis(100),
+            )
+            .unwrap();
+        assert_eq!(
+            Drained {
+                acked: 2,
+                nacked: 1
+            },
+            drained
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn subscribe_with_receipt() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Subscribe, frame.command);
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0")],
+                b"early",
+            );
+            reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Unsubscribe, frame.command);
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+
+            reply(
+                writer,
+                Command::Error,
+                &[
+                    ("message", "no such subscription"),
+                    ("receipt-id", &receipt),
+                ],
+                &[],
+            );
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        let subscription = client
+            .subscribe_with_receipt(&destination, AckMode::Auto)
+            .unwrap();
+
+        let mut frame = client.receive().unwrap();
+        assert_eq!(Command::Message, frame.command);
+        assert_eq!(b"early".to_vec(), read_all(&mut frame));
+        drop(frame);
+
+        let result = client.unsubscribe_with_receipt(subscription.id());
+        assert!(matches!(result, Err(ClientError::Broker(_))));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn receipt_window() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let mut receipts = Vec::new();
+
+            for _ in 0..2 {
+                let frame = reader.read_frame().unwrap();
+                assert_eq!(Command::Send, frame.command);
+                receipts.push(frame.header.first("receipt").unwrap().to_owned());
+            }
+
+            thread::sleep(Duration::from_millis(100));
+            writer.set_nonblocking(true).unwrap();
+            let error = writer.peek(&mut [0; 1]).unwrap_err();
+            assert_eq!(io::ErrorKind::WouldBlock, error.kind());
+            writer.set_nonblocking(false).unwrap();
+
+            reply(writer, Command::Message, &[("subscription", "sub-0")], &[]);
+
+            for receipt in receipts {
+                reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+            }
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Send, frame.command);
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+            reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+        });
+        let options = ConnectOptions::new("/").receipt_window(2);
+        let client = Client::connect(addr, options).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+
+        for _ in 0..3 {
+            client.send(&destination, Header::new(), "hello").unwrap();
+        }
+        assert_eq!(2, client.unconfirmed());
+
+        client.wait_for_confirmations().unwrap();
+        assert_eq!(0, client.unconfirmed());
+
+        let frame = client.receive().unwrap();
+        assert_eq!(Command::Message, frame.command);
+        drop(frame);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn window_stats_and_stall() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Ack, frame.command);
+            let ack_receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+            reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+            reply(
+                writer,
+                Command::Receipt,
+                &[("receipt-id", &ack_receipt)],
+                &[],
+            );
+        });
+        let stalls = Rc::new(Cell::new(0));
+        let counter = stalls.clone();
+        let options = ConnectOptions::new("/").receipt_window(4).on_stall(
+            Duration::from_millis(50),
+            move |stats| {
+                assert_eq!(1, stats.unconfirmed);
+                counter.set(counter.get() + 1);
+            },
+        );
+        let client = Client::connect(addr, options).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+
+        client.send(&destination, Header::new(), "hello").unwrap();
+        let stats = client.window_stats().unwrap();
+        assert_eq!(1, stats.unconfirmed);
+        assert_eq!(4, stats.max);
+        assert!(stats.oldest.is_some());
+
+        thread::sleep(Duration::from_millis(60));
+        client.check_stall();
+        client.check_stall();
+        assert_eq!(1, stalls.get());
+
+        client.ack("a-1").unwrap();
+        client.wait_for_confirmations().unwrap();
+        assert_eq!(None, client.window_stats().unwrap().oldest);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn send_rate_limit() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            for _ in 0..3 {
+                let frame = reader.read_frame().unwrap();
+                assert_eq!(Command::Send, frame.command);
+            }
+        });
+        let options = ConnectOptions::new("/").rate_limit(RateLimit::new(20, 1));
+        let client = Client::connect(addr, options).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+
+        let started = Instant::now();
+        for _ in 0..3 {
+            client.send(&destination, Header::new(), "hello").unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        handle.join().unwrap();
+    }
+
+    /// Reverses the body, which is enough to tell whether it went through the codec.
+    struct Reverse;
+
+    impl Codec for Reverse {
+        fn name(&self) -> &str {
+            "reverse"
+        }
+
+        fn compress(&self, input: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(input.iter().rev().cloned().collect())
+        }
+
+        fn decompress(&self, input: &[u8]) -> io::Result<Vec<u8>> {
+            self.compress(input)
+        }
+    }
+
+    #[test]
+    fn compression() {
+        let (addr, handle) = server(|reader, writer| {
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("reverse"), frame.header.first("compression"));
+            drop(frame);
+            reply(
+                writer,
+                Command::Connected,
+                &[("version", "1.2"), ("compression", "reverse")],
+                &[],
+            );
+
+            let mut frame = reader.read_frame().unwrap();
+            assert_eq!(Some("reverse"), frame.header.first("content-encoding"));
+            assert_eq!(b"olleh".to_vec(), read_all(&mut frame));
+            drop(frame);
+
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0"), ("content-encoding", "reverse")],
+                b"dlrow",
+            );
+        });
+        let options = ConnectOptions::new("/").compression(Reverse);
+        let client = Client::connect(addr, options).unwrap();
+        assert_eq!(Some("reverse"), client.compression());
+
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), "hello").unwrap();
+
+        let mut frame = client.receive().unwrap();
+        assert_eq!(b"world".to_vec(), read_all(&mut frame));
+        assert!(!frame.header.contains_key("content-encoding"));
+        drop(frame);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn compression_not_accepted() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let mut frame = reader.read_frame().unwrap();
+            assert!(!frame.header.contains_key("content-encoding"));
+            assert_eq!(b"hello".to_vec(), read_all(&mut frame));
+        });
+        let options = ConnectOptions::new("/").compression(Reverse);
+        let client = Client::connect(addr, options).unwrap();
+        assert_eq!(None, client.compression());
+
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), "hello").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            drop(reader.read_frame().unwrap());
+            reply(
+                writer,
+                Command::Message,
+                &[("destination", "/queue/a"), ("content-length", "3")],
+                b"abc",
+            );
+            reply(
+                writer,
+                Command::Message,
+                &[("destination", "/queue/a")],
+                b"abcdefg",
+            );
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), "hello").unwrap();
+        drop(client.receive().unwrap());
+
+        let mut body = Vec::new();
+        let mut frame = client.receive().unwrap();
+        frame.body.read_to_end(&mut body).unwrap();
+        drop(frame);
+
+        let metrics = client.metrics();
+        assert_eq!(Some(5), metrics.sent.mean());
+        assert_eq!(2, metrics.received.count());
+        assert_eq!(Some(5), metrics.received.mean());
+        assert_eq!(Some(3), metrics.destinations.get("/queue/a"));
+        drop(metrics);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn send_content_type() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(
+                Some("text/plain;charset=utf-8"),
+                frame.header.first("content-type")
+            );
+            drop(frame);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Some("text/csv"), frame.header.first("content-type"));
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), "hello").unwrap();
+
+        let mut header = Header::new();
+        header.push("content-type", "text/csv".to_owned());
+        client.send(&destination, header, "a,b").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn send_without_content_type_inference() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(None, frame.header.first("content-type"));
+        });
+        let options = ConnectOptions::new("/").infer_content_type(false);
+        let client = Client::connect(addr, options).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        client.send(&destination, Header::new(), "hello").unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn disconnect() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Disconnect, frame.command);
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0")],
+                b"late",
+            );
+            reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+
+            let mut buffer = [0; 1];
+            let mut stream = writer;
+            assert_eq!(0, stream.read(&mut buffer).unwrap());
+            reply(writer, Command::Message, &[("subscription", "sub-0")], &[]);
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        client.disconnect().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn migrate() {
+        let (old, old_handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            assert_eq!(Command::Subscribe, reader.read_frame().unwrap().command);
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0")],
+                b"old",
+            );
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Disconnect, frame.command);
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0")],
+                b"late",
+            );
+            reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+        });
+        let (new, new_handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Subscribe, frame.command);
+            assert_eq!(Some("sub-0"), frame.header.first("id"));
+            assert_eq!(Some("/queue/a"), frame.header.first("destination"));
+            let receipt = frame.header.first("receipt").unwrap().to_owned();
+            drop(frame);
+            reply(
+                writer,
+                Command::Message,
+                &[("subscription", "sub-0")],
+                b"new",
+            );
+            reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+
+            let mut frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Send, frame.command);
+            assert_eq!(b"after".to_vec(), read_all(&mut frame));
+        });
+        let client = Client::connect(old, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        let id = client
+            .subscribe(&destination, AckMode::Auto)
+            .unwrap()
+            .into_id();
+        client.read_pending().unwrap();
+
+        let at = Instant::now() + Duration::from_millis(20);
+        let client = client.migrate(new, ConnectOptions::new("/"), at).unwrap();
+        assert!(Instant::now() >= at);
+
+        for body in [&b"old"[..], b"late", b"new"].iter() {
+            let mut frame = client.receive_message(&id).unwrap();
+            assert_eq!(body.to_vec(), read_all(&mut frame));
+        }
+        client
+            .send(&destination, Header::new(), &b"after"[..])
+            .unwrap();
+        old_handle.join().unwrap();
+        new_handle.join().unwrap();
+    }
+
+    #[test]
+    fn migrate_failure() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            let mut frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Send, frame.command);
+            assert_eq!(b"still here".to_vec(), read_all(&mut frame));
+        });
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+
+        let error = client
+            .migrate(closed, ConnectOptions::new("/"), Instant::now())
+            .err()
+            .unwrap();
+        assert!(matches!(error.error(), ClientError::Io(_)));
+        let client = error.into_client();
+        let destination = Destination::new("/queue/a").unwrap();
+        client
+            .send(&destination, Header::new(), &b"still here"[..])
+            .unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown_write() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let mut buffer = [0; 1];
+            let mut stream = writer;
+            assert_eq!(0, stream.read(&mut buffer).unwrap());
+            reply(writer, Command::Message, &[("subscription", "sub-0")], &[]);
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        client.shutdown_write().unwrap();
+
+        let frame = client.receive().unwrap();
+        assert_eq!(Command::Message, frame.command);
+        drop(frame);
+        client.shutdown_read().unwrap();
+        handle.join().unwrap();
+    }
+}
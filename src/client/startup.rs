@@ -0,0 +1,115 @@
+use crate::client::{Client, ClientError, ConnectOptions};
+use crate::destination::Destination;
+use crate::frame::AckMode;
+use crate::subscription::SubscriptionId;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A handle reporting whether a `Startup` has finished. It can be cloned into a health probe
+/// running on another thread.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn set(&self, ready: bool) {
+        self.0.store(ready, Ordering::SeqCst)
+    }
+}
+
+/// Connects and establishes a set of subscriptions, each confirmed by a RECEIPT, before
+/// reporting ready. Subscriptions are made in the order they were added.
+#[derive(Default)]
+pub struct Startup {
+    subscriptions: Vec<(Destination, AckMode)>,
+    readiness: Readiness,
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Startup::default()
+    }
+
+    pub fn subscribe(mut self, destination: Destination, ack: AckMode) -> Self {
+        self.subscriptions.push((destination, ack));
+        self
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.clone()
+    }
+
+    /// Returns the connected client and the subscription ids, in the order the subscriptions
+    /// were added.
+    pub fn connect<A: ToSocketAddrs>(
+        self,
+        addr: A,
+        options: ConnectOptions,
+    ) -> Result<(Client, Vec<SubscriptionId>), ClientError> {
+        self.readiness.set(false);
+        let client = Client::connect(addr, options)?;
+        let mut ids: Vec<SubscriptionId> = Vec::with_capacity(self.subscriptions.len());
+
+        for (destination, ack) in &self.subscriptions {
+            ids.push(client.subscribe_with_receipt(destination, *ack)?.into_id());
+        }
+        self.readiness.set(true);
+        Ok((client, ids))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::test::{accept_connect, reply, server};
+    use crate::frame::Command;
+
+    #[test]
+    fn startup() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            for id in &["sub-0", "sub-1"] {
+                let frame = reader.read_frame().unwrap();
+                assert_eq!(Command::Subscribe, frame.command);
+                assert_eq!(*id, frame.header["id"][0]);
+                let receipt = frame.header["receipt"][0].clone();
+                drop(frame);
+                reply(writer, Command::Receipt, &[("receipt-id", &receipt)], &[]);
+            }
+        });
+        let startup = Startup::new()
+            .subscribe(Destination::new("/queue/a").unwrap(), AckMode::Client)
+            .subscribe(Destination::new("/topic/b").unwrap(), AckMode::Auto);
+        let readiness = startup.readiness();
+        assert!(!readiness.is_ready());
+
+        let (_client, ids) = startup.connect(addr, ConnectOptions::new("/")).unwrap();
+        assert!(readiness.is_ready());
+        assert_eq!(
+            vec![SubscriptionId::new("sub-0"), SubscriptionId::new("sub-1")],
+            ids
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn startup_failure() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            drop(reader.read_frame().unwrap());
+            reply(writer, Command::Error, &[("message", "forbidden")], &[]);
+        });
+        let startup =
+            Startup::new().subscribe(Destination::new("/queue/a").unwrap(), AckMode::Client);
+        let readiness = startup.readiness();
+
+        assert!(startup.connect(addr, ConnectOptions::new("/")).is_err());
+        assert!(!readiness.is_ready());
+        handle.join().unwrap();
+    }
+}
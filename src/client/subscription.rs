@@ -0,0 +1,192 @@
+use crate::client::{Client, ClientError};
+use crate::frame::{Frame, OwnedFrame};
+use crate::subscription::SubscriptionId;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A subscription made with `Client::subscribe`. MESSAGE frames are routed to it by their
+/// `subscription` header; other frames read while waiting for one are kept for
+/// `Client::receive`. A subscription dropped without `Subscription::unsubscribe` is
+/// unsubscribed.
+pub struct Subscription<'a> {
+    client: &'a Client,
+    id: SubscriptionId,
+    finished: bool,
+}
+
+impl<'a> Subscription<'a> {
+    pub(super) fn new(client: &'a Client, id: SubscriptionId) -> Self {
+        Subscription {
+            client,
+            id,
+            finished: false,
+        }
+    }
+
+    pub fn id(&self) -> &SubscriptionId {
+        &self.id
+    }
+
+    /// Calls `callback` with the subscription id and the time since the last MESSAGE frame for
+    /// the subscription when more than `interval` passes without one, once for each silent
+    /// period. This is checked whenever the client reads a frame, and by
+    /// `Client::check_inactivity`.
+    pub fn on_inactivity<F>(&self, interval: Duration, callback: F)
+    where
+        F: Fn(&SubscriptionId, Duration) + 'static,
+    {
+        self.client
+            .watch_inactivity(&self.id, interval, Rc::new(callback));
+    }
+
+    /// Reads the next MESSAGE frame for this subscription. An ERROR frame is returned as
+    /// `ClientError::Broker`.
+    pub fn next_message(&self) -> Result<Frame<'a>, ClientError> {
+        self.client.receive_message(&self.id)
+    }
+
+    /// Iterates over the MESSAGE frames for this subscription, with their bodies read. The
+    /// iterator ends after the first error.
+    pub fn messages(&self) -> Messages<'_, 'a> {
+        Messages {
+            subscription: self,
+            done: false,
+        }
+    }
+
+    /// Releases the subscription without unsubscribing, leaving it to be managed by id with
+    /// `Client::unsubscribe`.
+    pub fn into_id(mut self) -> SubscriptionId {
+        self.finished = true;
+        self.id.clone()
+    }
+
+    pub fn unsubscribe(mut self) -> Result<(), ClientError> {
+        self.finished = true;
+        self.client.unsubscribe(&self.id)
+    }
+}
+
+impl<'a> Drop for Subscription<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.client.unsubscribe(&self.id);
+        }
+    }
+}
+
+pub struct Messages<'s, 'a> {
+    subscription: &'s Subscription<'a>,
+    done: bool,
+}
+
+impl<'s, 'a> Iterator for Messages<'s, 'a> {
+    type Item = Result<OwnedFrame, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self
+            .subscription
+            .next_message()
+            .and_then(|frame| frame.into_owned().map_err(ClientError::from));
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::client::test::{accept_connect, read_all, reply, server};
+    use crate::client::{Client, ConnectOptions};
+    use crate::destination::Destination;
+    use crate::frame::{AckMode, Command};
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn route_messages() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            for _ in 0..2 {
+                assert_eq!(Command::Subscribe, reader.read_frame().unwrap().command);
+            }
+            reply(writer, Command::Message, &[("subscription", "sub-1")], b"b");
+            reply(writer, Command::Message, &[("subscription", "sub-0")], b"a");
+            reply(writer, Command::Message, &[("subscription", "sub-0")], b"c");
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Unsubscribe, frame.command);
+            assert_eq!(vec!["sub-1".to_owned()], frame.header["id"]);
+            drop(frame);
+
+            let frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Unsubscribe, frame.command);
+            assert_eq!(vec!["sub-0".to_owned()], frame.header["id"]);
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        let a = client.subscribe(&destination, AckMode::Auto).unwrap();
+        let b = client.subscribe(&destination, AckMode::Auto).unwrap();
+        assert_eq!("sub-0", a.id().as_str());
+
+        let mut frame = a.next_message().unwrap();
+        assert_eq!(b"a".to_vec(), read_all(&mut frame));
+        drop(frame);
+
+        let bodies: Vec<Vec<u8>> = a.messages().take(1).map(|f| f.unwrap().body).collect();
+        assert_eq!(vec![b"c".to_vec()], bodies);
+
+        let mut frame = b.next_message().unwrap();
+        assert_eq!(b"b".to_vec(), read_all(&mut frame));
+        drop(frame);
+
+        b.unsubscribe().unwrap();
+        drop(a);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn inactivity() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+            assert_eq!(Command::Subscribe, reader.read_frame().unwrap().command);
+            reply(writer, Command::Message, &[("subscription", "sub-0")], b"a");
+            assert_eq!(Command::Unsubscribe, reader.read_frame().unwrap().command);
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+        let subscription = client.subscribe(&destination, AckMode::Auto).unwrap();
+
+        let alerts = Rc::new(Cell::new(0));
+        let counter = alerts.clone();
+        subscription.on_inactivity(Duration::from_millis(20), move |id, silence| {
+            assert_eq!("sub-0", id.as_str());
+            assert!(silence >= Duration::from_millis(20));
+            counter.set(counter.get() + 1);
+        });
+
+        thread::sleep(Duration::from_millis(30));
+        client.check_inactivity();
+        client.check_inactivity();
+        assert_eq!(1, alerts.get());
+
+        drop(subscription.next_message().unwrap());
+        client.check_inactivity();
+        assert_eq!(1, alerts.get());
+
+        thread::sleep(Duration::from_millis(30));
+        client.check_inactivity();
+        assert_eq!(2, alerts.get());
+
+        subscription.unsubscribe().unwrap();
+        thread::sleep(Duration::from_millis(30));
+        client.check_inactivity();
+        assert_eq!(2, alerts.get());
+        handle.join().unwrap();
+    }
+}
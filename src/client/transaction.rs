@@ -0,0 +1,142 @@
+use crate::client::{Client, ClientError};
+use crate::content::Content;
+use crate::destination::Destination;
+use crate::frame::{Command, Frame, Header};
+
+pub(super) use crate::frame::headers::TRANSACTION;
+
+/// A transaction started with `Client::begin`. Frames sent through it are part of the
+/// transaction until it is committed. A transaction dropped without `Transaction::commit` is
+/// aborted.
+pub struct Transaction<'a> {
+    client: &'a Client,
+    id: String,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub(super) fn new(client: &'a Client, id: String) -> Self {
+        Transaction {
+            client,
+            id,
+            finished: false,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Sends a message as part of the transaction; see `Client::send`.
+    pub fn send<B: Content>(
+        &self,
+        destination: &Destination,
+        mut header: Header,
+        body: B,
+    ) -> Result<(), ClientError> {
+        header.insert(TRANSACTION.to_owned(), vec![self.id.clone()]);
+        self.client.send(destination, header, body)
+    }
+
+    pub fn ack(&self, id: &str) -> Result<(), ClientError> {
+        self.client.write_ack(Command::Ack, id, Some(&self.id))
+    }
+
+    pub fn nack(&self, id: &str) -> Result<(), ClientError> {
+        self.client.write_ack(Command::Nack, id, Some(&self.id))
+    }
+
+    /// Acknowledges a received MESSAGE frame as part of the transaction; see
+    /// `Client::ack_message`.
+    pub fn ack_message(&self, message: &Frame<'_>) -> Result<(), ClientError> {
+        self.client.write_message_ack(
+            Command::Ack,
+            message.command,
+            &message.header,
+            Some(&self.id),
+        )
+    }
+
+    pub fn nack_message(&self, message: &Frame<'_>) -> Result<(), ClientError> {
+        self.client.write_message_ack(
+            Command::Nack,
+            message.command,
+            &message.header,
+            Some(&self.id),
+        )
+    }
+
+    pub fn commit(mut self) -> Result<(), ClientError> {
+        self.finished = true;
+        self.client.write_transaction(Command::Commit, &self.id)
+    }
+
+    pub fn abort(mut self) -> Result<(), ClientError> {
+        self.finished = true;
+        self.client.write_transaction(Command::Abort, &self.id)
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.client.write_transaction(Command::Abort, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::client::test::{accept_connect, server};
+    use crate::client::{Client, ConnectOptions};
+    use crate::destination::Destination;
+    use crate::frame::{Command, Header};
+
+    fn transaction(frame: &crate::frame::Frame<'_>) -> Option<String> {
+        frame
+            .header
+            .get("transaction")
+            .and_then(|v| v.first())
+            .cloned()
+    }
+
+    #[test]
+    fn commit() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            let expected = [Command::Begin, Command::Send, Command::Ack, Command::Commit];
+
+            for command in expected.iter() {
+                let frame = reader.read_frame().unwrap();
+                assert_eq!(*command, frame.command);
+                assert_eq!(Some("tx-0".to_owned()), transaction(&frame));
+            }
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        let destination = Destination::new("/queue/a").unwrap();
+
+        let tx = client.begin().unwrap();
+        assert_eq!("tx-0", tx.id());
+        tx.send(&destination, Header::new(), "hello").unwrap();
+        tx.ack("a-1").unwrap();
+        tx.commit().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn abort_on_drop() {
+        let (addr, handle) = server(|reader, writer| {
+            accept_connect(reader, writer);
+
+            for command in [Command::Begin, Command::Abort].iter() {
+                let frame = reader.read_frame().unwrap();
+                assert_eq!(*command, frame.command);
+                assert_eq!(Some("tx-0".to_owned()), transaction(&frame));
+            }
+        });
+        let client = Client::connect(addr, ConnectOptions::new("/")).unwrap();
+        drop(client.begin().unwrap());
+        handle.join().unwrap();
+    }
+}
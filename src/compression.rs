@@ -0,0 +1,52 @@
+use crate::frame::Header;
+use std::io;
+
+/// The CONNECT header listing the codecs a client accepts, and the CONNECTED header naming the
+/// codec the server chose. Neither is part of STOMP; a peer that does not know them ignores them,
+/// and frames are then sent uncompressed.
+pub const COMPRESSION: &str = "compression";
+/// The header naming the codec a frame body was compressed with.
+pub const CONTENT_ENCODING: &str = "content-encoding";
+
+/// A compression algorithm, identified to the peer by `Codec::name`. The crate does not ship any
+/// codecs; implement this trait over a compression library.
+pub trait Codec {
+    fn name(&self) -> &str;
+
+    fn compress(&self, input: &[u8]) -> io::Result<Vec<u8>>;
+
+    fn decompress(&self, input: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Writes the `compression` header offering `names`, in order of preference.
+pub fn write_offer(header: &mut Header, names: &[&str]) {
+    if !names.is_empty() {
+        header.insert(COMPRESSION.to_owned(), vec![names.join(",")]);
+    }
+}
+
+/// Picks the first of `supported` that the peer offered in its `compression` header.
+pub fn negotiate<'a>(header: &Header, supported: &[&'a str]) -> Option<&'a str> {
+    let offered = header.get(COMPRESSION).and_then(|v| v.first())?;
+
+    supported
+        .iter()
+        .find(|name| offered.split(',').any(|o| o.trim() == **name))
+        .copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn offer_and_negotiate() {
+        let mut header = Header::new();
+        write_offer(&mut header, &["zstd", "gzip"]);
+        assert_eq!(vec!["zstd,gzip".to_owned()], header[COMPRESSION]);
+
+        assert_eq!(Some("gzip"), negotiate(&header, &["deflate", "gzip"]));
+        assert_eq!(None, negotiate(&header, &["deflate"]));
+        assert_eq!(None, negotiate(&Header::new(), &["gzip"]));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::frame::Header;
+
+pub use crate::frame::headers::CONTENT_TYPE;
+pub const TEXT_PLAIN: &str = "text/plain;charset=utf-8";
+pub const OCTET_STREAM: &str = "application/octet-stream";
+pub const APPLICATION_JSON: &str = "application/json";
+
+/// A typed message body. The content type is used for the `content-type` header when the
+/// header is not already set.
+pub trait Content {
+    fn content_type(&self) -> &'static str;
+
+    fn as_bytes(&self) -> &[u8];
+
+    /// Sets the `content-type` header from `Content::content_type`, unless it is already present.
+    fn write_content_type(&self, header: &mut Header) {
+        if !header.contains_key(CONTENT_TYPE) {
+            header.insert(
+                CONTENT_TYPE.to_owned(),
+                vec![self.content_type().to_owned()],
+            );
+        }
+    }
+}
+
+/// A body holding already serialized JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> Content for Json<T> {
+    fn content_type(&self) -> &'static str {
+        APPLICATION_JSON
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_ref().as_bytes()
+    }
+}
+
+impl Content for str {
+    fn content_type(&self) -> &'static str {
+        TEXT_PLAIN
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl Content for String {
+    fn content_type(&self) -> &'static str {
+        TEXT_PLAIN
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        String::as_bytes(self)
+    }
+}
+
+impl Content for [u8] {
+    fn content_type(&self) -> &'static str {
+        OCTET_STREAM
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<const N: usize> Content for [u8; N] {
+    fn content_type(&self) -> &'static str {
+        OCTET_STREAM
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Content for Vec<u8> {
+    fn content_type(&self) -> &'static str {
+        OCTET_STREAM
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<T: Content + ?Sized> Content for &T {
+    fn content_type(&self) -> &'static str {
+        (**self).content_type()
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        (**self).as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn content_types() {
+        assert_eq!(TEXT_PLAIN, "hello".content_type());
+        assert_eq!(TEXT_PLAIN, "hello".to_owned().content_type());
+        assert_eq!(OCTET_STREAM, b"hello".content_type());
+        assert_eq!(OCTET_STREAM, b"hello".to_vec().content_type());
+        assert_eq!(APPLICATION_JSON, Json("{}").content_type());
+        assert_eq!(b"{}", Json("{}").as_bytes());
+    }
+
+    #[test]
+    fn write_content_type_keeps_override() {
+        let mut header = Header::new();
+        "hello".write_content_type(&mut header);
+        assert_eq!(vec![TEXT_PLAIN.to_owned()], header[CONTENT_TYPE]);
+
+        let mut header = Header::new();
+        header.push(CONTENT_TYPE, "text/csv".to_owned());
+        "hello".write_content_type(&mut header);
+        assert_eq!(vec!["text/csv".to_owned()], header[CONTENT_TYPE]);
+    }
+}
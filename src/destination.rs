@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const TOPIC_PREFIXES: [&str; 2] = ["/topic/", "/temp-topic/"];
+const QUEUE_PREFIXES: [&str; 3] = ["/queue/", "/amq/queue/", "/temp-queue/"];
+const TEMP_PREFIXES: [&str; 2] = ["/temp-queue/", "/temp-topic/"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DestinationError {
+    reason: &'static str,
+}
+
+impl Display for DestinationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid destination: {}", self.reason)
+    }
+}
+
+impl Error for DestinationError {}
+
+/// A validated destination name. Repeated leading slashes are collapsed, so `//queue/a` and
+/// `/queue/a` name the same destination. Kind detection follows the `/queue/`, `/topic/` and
+/// `/temp-queue/` prefix conventions shared by ActiveMQ, Artemis and RabbitMQ.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Destination(String);
+
+impl Destination {
+    pub fn new<T: AsRef<str>>(name: T) -> Result<Self, DestinationError> {
+        let name = name.as_ref();
+
+        if name.trim().is_empty() {
+            return Err(DestinationError {
+                reason: "empty name",
+            });
+        }
+
+        if name.chars().any(|c| c == '\0' || c == '\r' || c == '\n') {
+            return Err(DestinationError {
+                reason: "name contains a NULL, carriage return or newline",
+            });
+        }
+        let trimmed = name.trim_start_matches('/');
+
+        if trimmed.len() < name.len() {
+            Ok(Destination(format!("/{}", trimmed)))
+        } else {
+            Ok(Destination(trimmed.to_owned()))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_topic(&self) -> bool {
+        TOPIC_PREFIXES.iter().any(|p| self.0.starts_with(p))
+    }
+
+    pub fn is_queue(&self) -> bool {
+        QUEUE_PREFIXES.iter().any(|p| self.0.starts_with(p))
+    }
+
+    pub fn is_temp(&self) -> bool {
+        TEMP_PREFIXES.iter().any(|p| self.0.starts_with(p))
+    }
+}
+
+impl AsRef<str> for Destination {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Destination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Destination {
+    type Err = DestinationError;
+
+    fn from_str(s: &str) -> Result<Destination, DestinationError> {
+        Destination::new(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize_leading_slashes() {
+        let destination = Destination::new("///queue/a").unwrap();
+        assert_eq!("/queue/a", destination.as_str());
+
+        let destination = Destination::new("a.b").unwrap();
+        assert_eq!("a.b", destination.as_str());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(Destination::new("").is_err());
+        assert!(Destination::new("  ").is_err());
+        assert!(Destination::new("/queue/a\nb").is_err());
+        assert!(Destination::new("/queue/a\0").is_err());
+    }
+
+    #[test]
+    fn kind() {
+        let queue = Destination::new("/queue/a").unwrap();
+        assert!(queue.is_queue() && !queue.is_topic() && !queue.is_temp());
+
+        let topic = Destination::new("/topic/a").unwrap();
+        assert!(topic.is_topic() && !topic.is_queue() && !topic.is_temp());
+
+        let temp = Destination::new("/temp-queue/a").unwrap();
+        assert!(temp.is_queue() && temp.is_temp());
+
+        let exchange = Destination::new("/exchange/amq.direct/a").unwrap();
+        assert!(!exchange.is_queue() && !exchange.is_topic());
+    }
+}
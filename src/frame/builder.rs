@@ -0,0 +1,394 @@
+use crate::content::TEXT_PLAIN;
+use crate::destination::Destination;
+use crate::frame::headers::{
+    ACCEPT_VERSION, ACK, CONTENT_LENGTH, CONTENT_TYPE, DESTINATION, HEART_BEAT, HOST, ID, MESSAGE,
+    MESSAGE_ID, RECEIPT, RECEIPT_ID, SERVER, SESSION, SUBSCRIPTION, TRANSACTION, VERSION,
+};
+use crate::frame::{AckMode, Body, Command, Frame, Header, Version};
+use crate::heartbeat::HeartBeat;
+use crate::message;
+use crate::subscription::SubscriptionId;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    MissingHeader {
+        command: Command,
+        header: &'static str,
+    },
+    UnexpectedBody(Command),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingHeader { command, header } => {
+                write!(f, "{} frame requires a {} header", command, header)
+            }
+            BuildError::UnexpectedBody(command) => {
+                write!(f, "{} frame must not have a body", command)
+            }
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// The headers STOMP 1.2 requires for each command.
+pub fn required_headers(command: Command) -> &'static [&'static str] {
+    required_headers_for(command, Version::V1_2)
+}
+
+/// The headers `version` requires for each command. STOMP 1.0 has no NACK, so it gets the same
+/// headers as ACK, and an UNSUBSCRIBE needs either `id` or `destination`, so it has none.
+pub fn required_headers_for(command: Command, version: Version) -> &'static [&'static str] {
+    use self::Command::*;
+
+    match (command, version) {
+        (Connect, Version::V1_0) | (Stomp, Version::V1_0) | (Connected, Version::V1_0) => &[],
+        (Connect, _) | (Stomp, _) => &[ACCEPT_VERSION, HOST],
+        (Connected, _) => &[VERSION],
+        (Send, _) => &[DESTINATION],
+        (Subscribe, Version::V1_0) => &[DESTINATION],
+        (Subscribe, _) => &[DESTINATION, ID],
+        (Unsubscribe, Version::V1_0) => &[],
+        (Unsubscribe, _) => &[ID],
+        (Ack, Version::V1_0) | (Nack, Version::V1_0) => &[MESSAGE_ID],
+        (Ack, Version::V1_1) | (Nack, Version::V1_1) => &[MESSAGE_ID, SUBSCRIPTION],
+        (Ack, Version::V1_2) | (Nack, Version::V1_2) => &[ID],
+        (Begin, _) | (Commit, _) | (Abort, _) => &[TRANSACTION],
+        (Message, Version::V1_0) => &[DESTINATION, MESSAGE_ID],
+        (Message, _) => &[DESTINATION, MESSAGE_ID, SUBSCRIPTION],
+        (Receipt, _) => &[RECEIPT_ID],
+        (Disconnect, _) | (Error, _) => &[],
+    }
+}
+
+/// Whether STOMP 1.2 allows a frame with this command to have a body.
+pub fn allows_body(command: Command) -> bool {
+    matches!(command, Command::Send | Command::Message | Command::Error)
+}
+
+pub struct FrameBuilder<'a> {
+    command: Command,
+    header: Header,
+    body: Option<Body<'a>>,
+    version: Version,
+}
+
+impl<'a> FrameBuilder<'a> {
+    pub fn new(command: Command) -> Self {
+        FrameBuilder {
+            command,
+            header: Header::new(),
+            body: None,
+            version: Version::default(),
+        }
+    }
+
+    /// Sets the protocol version whose required headers `FrameBuilder::build` checks, 1.2 by
+    /// default.
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets a header, replacing any value it already had.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.header.insert(key.into(), vec![value.into()]);
+        self
+    }
+
+    pub fn body(mut self, body: Body<'a>) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Sets the body from bytes, along with a matching `content-length` header.
+    pub fn body_bytes(self, body: &'a [u8]) -> Self {
+        self.header(CONTENT_LENGTH, body.len().to_string())
+            .body(Body::new(body))
+    }
+
+    fn for_message(command: Command, message: &Header, version: Version) -> Option<Self> {
+        let mut builder = FrameBuilder::new(command).version(version);
+        builder.header = message::ack_header(message, version)?;
+        Some(builder)
+    }
+
+    pub fn build(self) -> Result<Frame<'a>, BuildError> {
+        for header in required_headers_for(self.command, self.version) {
+            if !self.header.contains_key(header) {
+                return Err(BuildError::MissingHeader {
+                    command: self.command,
+                    header,
+                });
+            }
+        }
+
+        if self.body.is_some() && !allows_body(self.command) {
+            return Err(BuildError::UnexpectedBody(self.command));
+        }
+        let body = self.body.unwrap_or_else(Body::empty);
+        Ok(Frame::new(self.command, self.header, body))
+    }
+}
+
+impl<'a> Frame<'a> {
+    pub fn builder(command: Command) -> FrameBuilder<'a> {
+        FrameBuilder::new(command)
+    }
+
+    pub fn connect<T: Into<String>>(host: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Connect)
+            .header(ACCEPT_VERSION, Version::V1_2.as_str())
+            .header(HOST, host)
+    }
+
+    /// A CONNECT frame offering `versions`. A 1.0 server ignores the headers it does not know.
+    pub fn connect_versions<T: Into<String>>(host: T, versions: &[Version]) -> FrameBuilder<'a> {
+        let oldest = versions.iter().min().copied().unwrap_or_default();
+
+        FrameBuilder::new(Command::Connect)
+            .header(ACCEPT_VERSION, Version::accept_version(versions))
+            .header(HOST, host)
+            .version(oldest)
+    }
+
+    pub fn stomp<T: Into<String>>(host: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Stomp)
+            .header(ACCEPT_VERSION, Version::V1_2.as_str())
+            .header(HOST, host)
+    }
+
+    pub fn send(destination: &Destination) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Send).header(DESTINATION, destination.as_str())
+    }
+
+    pub fn subscribe(
+        id: &SubscriptionId,
+        destination: &Destination,
+        ack: AckMode,
+    ) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Subscribe)
+            .header(ID, id.as_str())
+            .header(DESTINATION, destination.as_str())
+            .header(ACK, ack.to_string())
+    }
+
+    pub fn unsubscribe(id: &SubscriptionId) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Unsubscribe).header(ID, id.as_str())
+    }
+
+    /// A STOMP 1.2 ACK frame for the MESSAGE frame whose `ack` header is `id`. Only
+    /// `Frame::ack_message` and `Frame::nack_message` identify the message the way earlier
+    /// versions require.
+    pub fn ack<T: Into<String>>(id: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Ack).header(ID, id)
+    }
+
+    /// A STOMP 1.2 NACK frame; see `Frame::ack`.
+    pub fn nack<T: Into<String>>(id: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Nack).header(ID, id)
+    }
+
+    /// An ACK frame for a received MESSAGE frame with header `message`, identifying it the way
+    /// `version` requires; see `message::ack_header`.
+    pub fn ack_message(message: &Header, version: Version) -> Option<FrameBuilder<'a>> {
+        FrameBuilder::for_message(Command::Ack, message, version)
+    }
+
+    pub fn nack_message(message: &Header, version: Version) -> Option<FrameBuilder<'a>> {
+        FrameBuilder::for_message(Command::Nack, message, version)
+    }
+
+    pub fn begin<T: Into<String>>(transaction: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Begin).header(TRANSACTION, transaction)
+    }
+
+    pub fn commit<T: Into<String>>(transaction: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Commit).header(TRANSACTION, transaction)
+    }
+
+    pub fn abort<T: Into<String>>(transaction: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Abort).header(TRANSACTION, transaction)
+    }
+
+    /// A DISCONNECT frame asking for a RECEIPT, after which the server has processed every
+    /// earlier frame.
+    pub fn disconnect<T: Into<String>>(receipt: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Disconnect).header(RECEIPT, receipt)
+    }
+
+    /// The CONNECTED frame a server answers CONNECT with.
+    pub fn connected(
+        version: Version,
+        heart_beat: HeartBeat,
+        session: Option<&str>,
+        server: Option<&str>,
+    ) -> FrameBuilder<'a> {
+        let mut builder = FrameBuilder::new(Command::Connected)
+            .header(VERSION, version.as_str())
+            .header(HEART_BEAT, heart_beat.to_string());
+
+        if let Some(session) = session {
+            builder = builder.header(SESSION, session);
+        }
+
+        if let Some(server) = server {
+            builder = builder.header(SERVER, server);
+        }
+        builder
+    }
+
+    pub fn receipt<T: Into<String>>(receipt_id: T) -> FrameBuilder<'a> {
+        FrameBuilder::new(Command::Receipt).header(RECEIPT_ID, receipt_id)
+    }
+
+    /// An ERROR frame with a short description in its `message` header and details in the body.
+    pub fn error<T: Into<String>>(message: T, body: &'a [u8]) -> FrameBuilder<'a> {
+        let builder = FrameBuilder::new(Command::Error).header(MESSAGE, message);
+
+        if body.is_empty() {
+            builder
+        } else {
+            builder.header(CONTENT_TYPE, TEXT_PLAIN).body_bytes(body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+    use std::time::Duration;
+
+    #[test]
+    fn build_send() {
+        let destination = Destination::new("/queue/a").unwrap();
+        let mut frame = Frame::send(&destination)
+            .header("receipt", "r-1")
+            .body_bytes(b"hello")
+            .build()
+            .unwrap();
+
+        assert_eq!(Command::Send, frame.command);
+        assert_eq!(vec!["/queue/a".to_owned()], frame.header["destination"]);
+        assert_eq!(vec!["5".to_owned()], frame.header["content-length"]);
+
+        let mut buffer: Vec<u8> = Vec::new();
+        frame.body.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"hello".to_vec(), buffer);
+    }
+
+    #[test]
+    fn build_subscribe() {
+        let destination = Destination::new("/queue/a").unwrap();
+        let frame = Frame::subscribe(&SubscriptionId::new("0"), &destination, AckMode::Client)
+            .build()
+            .unwrap();
+        assert_eq!(vec!["0".to_owned()], frame.header["id"]);
+        assert_eq!(vec!["client".to_owned()], frame.header["ack"]);
+    }
+
+    #[test]
+    fn build_missing_header() {
+        let result = Frame::builder(Command::Subscribe)
+            .header("destination", "/queue/a")
+            .build();
+        assert_eq!(
+            Some(BuildError::MissingHeader {
+                command: Command::Subscribe,
+                header: "id"
+            }),
+            result.err()
+        );
+    }
+
+    #[test]
+    fn build_unexpected_body() {
+        let result = Frame::begin("tx-1").body_bytes(b"hello").build();
+        assert_eq!(
+            Some(BuildError::UnexpectedBody(Command::Begin)),
+            result.err()
+        );
+    }
+
+    #[test]
+    fn build_client_frames() {
+        assert!(Frame::connect("/").build().is_ok());
+        assert!(Frame::stomp("/").build().is_ok());
+        assert!(Frame::unsubscribe(&SubscriptionId::new("0"))
+            .build()
+            .is_ok());
+        assert!(Frame::ack("a-1").build().is_ok());
+        assert!(Frame::nack("a-1").build().is_ok());
+        assert!(Frame::commit("tx-1").build().is_ok());
+        assert!(Frame::abort("tx-1").build().is_ok());
+        assert!(Frame::disconnect("r-1").build().is_ok());
+    }
+
+    #[test]
+    fn build_for_version() {
+        let frame = Frame::connect_versions("/", &[Version::V1_0, Version::V1_1])
+            .build()
+            .unwrap();
+        assert_eq!(vec!["1.0,1.1".to_owned()], frame.header["accept-version"]);
+
+        let mut message = Header::new();
+        message.push("message-id", "m-1".to_owned());
+        message.push("subscription", "0".to_owned());
+
+        let frame = Frame::ack_message(&message, Version::V1_1)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(Command::Ack, frame.command);
+        assert_eq!(vec!["m-1".to_owned()], frame.header["message-id"]);
+        assert!(Frame::nack_message(&message, Version::V1_2).is_none());
+
+        let result = Frame::builder(Command::Ack)
+            .header("id", "a-1")
+            .version(Version::V1_1)
+            .build();
+        assert_eq!(
+            Some(BuildError::MissingHeader {
+                command: Command::Ack,
+                header: "message-id"
+            }),
+            result.err()
+        );
+        assert!(Frame::builder(Command::Subscribe)
+            .header("destination", "/queue/a")
+            .version(Version::V1_0)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn build_server_frames() {
+        let heart_beat = HeartBeat::new(Duration::from_millis(0), Duration::from_millis(500));
+        let frame = Frame::connected(Version::V1_1, heart_beat, Some("s-1"), None)
+            .build()
+            .unwrap();
+        assert_eq!(vec!["1.1".to_owned()], frame.header["version"]);
+        assert_eq!(vec!["0,500".to_owned()], frame.header["heart-beat"]);
+        assert_eq!(vec!["s-1".to_owned()], frame.header["session"]);
+        assert!(!frame.header.contains_key("server"));
+
+        let frame = Frame::receipt("r-1").build().unwrap();
+        assert_eq!(vec!["r-1".to_owned()], frame.header["receipt-id"]);
+
+        let mut frame = Frame::error("bad frame", b"details").build().unwrap();
+        assert_eq!(vec!["bad frame".to_owned()], frame.header["message"]);
+        assert_eq!(vec!["7".to_owned()], frame.header["content-length"]);
+        let mut buffer: Vec<u8> = Vec::new();
+        frame.body.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"details".to_vec(), buffer);
+
+        let frame = Frame::error("bad frame", b"").build().unwrap();
+        assert!(!frame.header.contains_key("content-type"));
+    }
+}
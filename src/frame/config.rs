@@ -0,0 +1,73 @@
+/// How strictly `FrameReader` holds input to the specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParserConfig {
+    strict: bool,
+    lowercase_names: bool,
+}
+
+impl ParserConfig {
+    /// Rejects malformed escape sequences, whitespace around header names or before header
+    /// values, repeated `content-length` headers, NULL bytes in the header block, and bytes
+    /// between a `content-length` body and its NULL. The specification requires invalid escape
+    /// sequences to be treated as fatal errors. The input is still consumed up to the offending
+    /// frame's NULL, so reading can continue with the next frame.
+    pub fn strict() -> Self {
+        ParserConfig {
+            strict: true,
+            ..ParserConfig::default()
+        }
+    }
+
+    /// Accepts what can be made sense of: invalid escape sequences are decoded as the escaped
+    /// character, whitespace is trimmed, and the first `content-length` header wins. A frame cut
+    /// short by a NULL in its header block is skipped, and bytes between a `content-length` body
+    /// and its NULL are discarded. This is the default.
+    pub fn lenient() -> Self {
+        ParserConfig {
+            strict: false,
+            ..ParserConfig::default()
+        }
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Lowercases header names as they are read. STOMP header names are case-sensitive, so by
+    /// default they are kept as sent; `Header::get_ignore_case` looks them up regardless.
+    pub fn lowercase_names(mut self, lowercase: bool) -> Self {
+        self.lowercase_names = lowercase;
+        self
+    }
+
+    pub fn lowercases_names(&self) -> bool {
+        self.lowercase_names
+    }
+}
+
+/// Bounds on the parts of a frame that `FrameReader` reads. A frame that exceeds one fails with
+/// `ReadError::LimitExceeded`, after which the input stream is left partway through the frame
+/// and reading cannot continue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Bytes in the command line, including its EOL.
+    pub max_command: u64,
+    /// Bytes in the header block, including the blank line that ends it.
+    pub max_header: u64,
+    /// Header lines in the header block, counting repeated headers.
+    pub max_headers_count: usize,
+    /// Bytes in a body, checked against its `content-length` header when it has one, and as the
+    /// body is read otherwise.
+    pub max_body: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_command: 1024,
+            max_header: 1024 * 1000,
+            max_headers_count: usize::MAX,
+            max_body: u64::MAX,
+        }
+    }
+}
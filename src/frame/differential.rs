@@ -0,0 +1,186 @@
+//! Differential testing of the two header parsers: the streaming one behind `FrameReader` and
+//! the zero-copy `HeaderRef::parse`. Both must accept the same header blocks, produce the same
+//! headers from them, and reject the rest with the same kind of error.
+
+use crate::frame::{Header, HeaderRef, Limits, ParserConfig, ReadError, Version};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::{BufReader, Cursor};
+use std::mem;
+
+/// A header block the parsers disagree on, with what each made of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub input: Vec<u8>,
+    pub version: Version,
+    pub config: ParserConfig,
+    pub streaming: String,
+    pub zero_copy: String,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "parsers disagree on {:?} ({}, {:?}): streaming {}, zero-copy {}",
+            String::from_utf8_lossy(&self.input),
+            self.version,
+            self.config,
+            self.streaming,
+            self.zero_copy
+        )
+    }
+}
+
+type Parsed = Result<(Header, usize), ReadError>;
+
+/// Parses the header block at the start of `input` with both parsers. Errors are compared by
+/// variant only. An input without a complete block is not compared, since the streaming parser
+/// takes the end of the stream as the end of the block.
+pub fn compare(input: &[u8], version: Version, config: ParserConfig) -> Result<(), Divergence> {
+    let zero_copy = match HeaderRef::parse(input, version, config) {
+        Ok(None) => return Ok(()),
+        Ok(Some((header, consumed))) => Ok((header.to_header(), consumed)),
+        Err(e) => Err(e),
+    };
+    let streaming = streaming(input, version, config);
+    let agree = match (&streaming, &zero_copy) {
+        (Ok(a), Ok(b)) => a == b,
+        (Err(a), Err(b)) => mem::discriminant(a) == mem::discriminant(b),
+        _ => false,
+    };
+
+    if agree {
+        return Ok(());
+    }
+    Err(Divergence {
+        input: input.to_vec(),
+        version,
+        config,
+        streaming: format!("{:?}", streaming),
+        zero_copy: format!("{:?}", zero_copy),
+    })
+}
+
+/// Compares the parsers over `iterations` header blocks generated from `seed`, under each
+/// protocol version and in lenient, strict and name-lowercasing configurations. Returns the
+/// blocks they disagree on.
+pub fn fuzz(seed: u64, iterations: usize) -> Vec<Divergence> {
+    let configs = [
+        ParserConfig::lenient(),
+        ParserConfig::strict(),
+        ParserConfig::lenient().lowercase_names(true),
+    ];
+    let mut random = Random(seed.max(1));
+    let mut divergences = Vec::new();
+
+    for _ in 0..iterations {
+        let input = random.header_block();
+
+        for version in Version::ALL.iter() {
+            for config in configs.iter() {
+                if let Err(divergence) = compare(&input, *version, *config) {
+                    divergences.push(divergence);
+                }
+            }
+        }
+    }
+    divergences
+}
+
+fn streaming(input: &[u8], version: Version, config: ParserConfig) -> Parsed {
+    let mut reader = BufReader::new(Cursor::new(input));
+    let header = Header::read_from(
+        &mut reader,
+        &mut Vec::new(),
+        version,
+        config,
+        Limits::default(),
+        None,
+    )?;
+    let consumed = reader.get_ref().position() as usize - reader.buffer().len();
+    Ok((header, consumed))
+}
+
+/// Pieces that header blocks are made of, chosen for the rules they exercise: separators,
+/// escapes, whitespace, line endings, NULL, multi-byte and invalid UTF-8, and the one header
+/// with rules of its own.
+const PIECES: &[&[u8]] = &[
+    b"a",
+    b"B",
+    b":",
+    b"\\",
+    b"\\n",
+    b"\\c",
+    b"\\r",
+    b"\\t",
+    b"\\\\",
+    b" ",
+    b"\t",
+    b"\r",
+    b"\r\n",
+    b"\n",
+    b"\0",
+    "\u{e9}".as_bytes(),
+    b"\xff",
+    b"content-length",
+    b"Content-Length",
+];
+
+/// A xorshift generator, so that a seed always produces the same inputs.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+
+    fn header_block(&mut self) -> Vec<u8> {
+        let mut block = Vec::new();
+
+        for _ in 0..self.next(4) {
+            for _ in 0..self.next(8) {
+                block.extend_from_slice(PIECES[self.next(PIECES.len())]);
+            }
+            block.push(b'\n');
+        }
+        block.push(b'\n');
+        block
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compare_block() {
+        let input = b"a:b\\c\r\nContent-Length: 1\r\n\r\nbody";
+        assert_eq!(
+            Ok(()),
+            compare(input, Version::V1_2, ParserConfig::lenient())
+        );
+        assert_eq!(
+            Ok(()),
+            compare(input, Version::V1_2, ParserConfig::strict())
+        );
+        assert_eq!(
+            Ok(()),
+            compare(b"a:b\n", Version::V1_2, ParserConfig::lenient())
+        );
+    }
+
+    #[test]
+    fn parsers_agree() {
+        let divergences = fuzz(0x5eed, 5000);
+        assert!(
+            divergences.is_empty(),
+            "{} divergences, first: {}",
+            divergences.len(),
+            divergences[0]
+        );
+    }
+}
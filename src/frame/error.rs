@@ -1,3 +1,166 @@
 use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::num::ParseIntError;
+use std::str::Utf8Error;
 
-pub type ReadError = Box<dyn Error>;
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Utf8(Utf8Error),
+    EmptyCommand,
+    InvalidCommand {
+        got: String,
+    },
+    /// Header line `line_no` of the header block, counting from 1, has no colon.
+    MalformedHeaderLine {
+        line_no: usize,
+    },
+    EmptyHeaderName,
+    /// An escape sequence the specification does not define, rejected in strict mode.
+    InvalidEscapeSequence {
+        sequence: String,
+    },
+    /// Whitespace around a header name or before its value, rejected in strict mode.
+    UntrimmedWhitespace {
+        header: String,
+    },
+    /// More than one `content-length` header, rejected in strict mode.
+    RepeatedContentLength,
+    InvalidContentLength {
+        value: String,
+        source: ParseIntError,
+    },
+    InvalidAckMode {
+        got: String,
+    },
+    UnknownVersion {
+        got: String,
+    },
+    InvalidHeartBeat {
+        got: String,
+    },
+    PreviousBodyUnfinished,
+    /// A NULL byte ended the frame before the end of its header block.
+    NullInHeader,
+    /// Bytes other than the NULL terminator followed the `content-length` bytes of a body.
+    ContentLengthMismatch {
+        content_length: u64,
+        trailing: u64,
+    },
+    /// The stream ended after `received` bytes of a `content-length` body, or before the NULL
+    /// terminator that should follow it.
+    UnterminatedBody {
+        content_length: u64,
+        received: u64,
+    },
+    /// A body without a `content-length` header ran past `Limits::max_body` without reaching
+    /// its NULL terminator.
+    BodyTooLarge {
+        limit: u64,
+    },
+    /// A part of the frame is larger than its bound in `Limits`.
+    LimitExceeded {
+        kind: LimitKind,
+        limit: u64,
+    },
+}
+
+/// The bound in `Limits` that a frame exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    Command,
+    Header,
+    HeadersCount,
+    Body,
+}
+
+impl Display for LimitKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::LimitKind::*;
+
+        match self {
+            Command => write!(f, "command line"),
+            Header => write!(f, "header block"),
+            HeadersCount => write!(f, "number of headers"),
+            Body => write!(f, "body"),
+        }
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::ReadError::*;
+
+        match self {
+            Io(e) => write!(f, "{}", e),
+            Utf8(e) => write!(f, "{}", e),
+            EmptyCommand => write!(f, "empty command"),
+            InvalidCommand { got } => write!(f, "invalid command {}", got),
+            MalformedHeaderLine { line_no } => write!(f, "header line {} has no colon", line_no),
+            EmptyHeaderName => write!(f, "empty header field name"),
+            InvalidEscapeSequence { sequence } => write!(f, "invalid escape sequence {}", sequence),
+            UntrimmedWhitespace { header } => {
+                write!(f, "untrimmed whitespace in header {}", header)
+            }
+            RepeatedContentLength => write!(f, "repeated content-length header"),
+            InvalidContentLength { value, .. } => write!(f, "invalid content-length {}", value),
+            InvalidAckMode { got } => write!(f, "invalid ack mode {}", got),
+            UnknownVersion { got } => write!(f, "unknown protocol version {}", got),
+            InvalidHeartBeat { got } => write!(f, "invalid heart-beat {}", got),
+            PreviousBodyUnfinished => write!(f, "previous frame body has not been read or closed"),
+            NullInHeader => write!(f, "NULL byte in header block"),
+            ContentLengthMismatch {
+                content_length,
+                trailing,
+            } => write!(
+                f,
+                "{} bytes between the {} byte body and the NULL terminator",
+                trailing, content_length
+            ),
+            UnterminatedBody {
+                content_length,
+                received,
+            } => write!(
+                f,
+                "stream ended after {} bytes of a {} byte body, before its NULL terminator",
+                received, content_length
+            ),
+            BodyTooLarge { limit } => write!(f, "body exceeds the limit of {} bytes", limit),
+            LimitExceeded { kind, limit } => write!(f, "{} exceeds the limit of {}", kind, limit),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use self::ReadError::*;
+
+        match self {
+            Io(e) => Some(e),
+            Utf8(e) => Some(e),
+            InvalidContentLength { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Unwraps a `ReadError` that a body reader passed through `io::Error`.
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<ReadError>()) {
+            if let Some(Ok(e)) = e.into_inner().map(|inner| inner.downcast::<ReadError>()) {
+                return *e;
+            }
+            unreachable!("checked to be a ReadError");
+        }
+        ReadError::Io(e)
+    }
+}
+
+impl From<Utf8Error> for ReadError {
+    fn from(e: Utf8Error) -> Self {
+        ReadError::Utf8(e)
+    }
+}
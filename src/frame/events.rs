@@ -0,0 +1,34 @@
+/// Input that lenient parsing repaired or discarded instead of failing on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParserEvent<'a> {
+    /// An escape sequence the protocol version does not define, decoded as the escaped
+    /// character. Holds the raw header name or value.
+    InvalidEscape(&'a str),
+    /// Whitespace trimmed from around a header name or from before its value.
+    UntrimmedWhitespace { header: &'a str },
+    /// A header that appeared more than once. Only its first value counts.
+    RepeatedHeader { header: &'a str },
+    /// A frame whose header block ended at a NULL, skipped entirely.
+    TruncatedFrame,
+    /// Bytes between a `content-length` body and the NULL terminator, dropped.
+    TrailingBytes { content_length: u64, discarded: u64 },
+}
+
+/// Receives a `ParserEvent` whenever a lenient `FrameReader` or `SyncFrameReader` repairs or
+/// discards input, so that malformed traffic can be counted or logged. Strict readers fail
+/// instead, and report nothing.
+pub trait ParserEvents {
+    fn on_event(&self, event: &ParserEvent<'_>);
+}
+
+impl<F: Fn(&ParserEvent<'_>)> ParserEvents for F {
+    fn on_event(&self, event: &ParserEvent<'_>) {
+        self(event)
+    }
+}
+
+pub(super) fn notify(events: Option<&dyn ParserEvents>, event: ParserEvent<'_>) {
+    if let Some(events) = events {
+        events.on_event(&event);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::frame::headers::CONTENT_LENGTH;
+use crate::frame::{string, Header, ParserConfig, ReadError, Version, EOL, NULL};
+use std::borrow::Cow;
+use std::str;
+
+/// A header block parsed in place from a buffer that already holds it. Names and values borrow
+/// from the buffer, and only those containing escape sequences are allocated, so parsing costs
+/// one allocation for the list of entries.
+///
+/// Lookups ignore the case of names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderRef<'a> {
+    entries: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+impl<'a> HeaderRef<'a> {
+    /// Parses the header lines at the start of `buffer` up to the blank line that ends them,
+    /// returning the header and the number of bytes taken, including the blank line. Returns
+    /// `None` if `buffer` does not hold the whole block yet. Input is checked as `FrameReader`
+    /// checks a header block, including the `ParserConfig` options.
+    pub fn parse(
+        buffer: &'a [u8],
+        version: Version,
+        config: ParserConfig,
+    ) -> Result<Option<(HeaderRef<'a>, usize)>, ReadError> {
+        let mut entries = Vec::new();
+        let mut position = 0;
+
+        loop {
+            let end = match memchr::memchr2(EOL, NULL, &buffer[position..]) {
+                Some(i) if buffer[position + i] == NULL => return Err(ReadError::NullInHeader),
+                Some(i) => position + i,
+                None => return Ok(None),
+            };
+            let mut line = str::from_utf8(&buffer[position..end])?;
+            position = end + 1;
+
+            if version.allows_crlf() {
+                line = line.strip_suffix('\r').unwrap_or(line);
+            }
+
+            if line.is_empty() {
+                return Ok(Some((HeaderRef { entries }, position)));
+            }
+            let (name, value) = line.split_once(':').ok_or(ReadError::MalformedHeaderLine {
+                line_no: entries.len() + 1,
+            })?;
+
+            let (name, value) = if config.is_strict() {
+                if name.trim() != name || value.trim_start() != value {
+                    return Err(ReadError::UntrimmedWhitespace {
+                        header: name.trim().to_owned(),
+                    });
+                }
+                (
+                    string::decode_strict(name, version)?,
+                    string::decode_strict(value, version)?,
+                )
+            } else {
+                (
+                    string::decode(name.trim(), version),
+                    string::decode(value.trim_start(), version),
+                )
+            };
+
+            if config.is_strict()
+                && name.eq_ignore_ascii_case(CONTENT_LENGTH)
+                && entries
+                    .iter()
+                    .any(|(k, _)| k.eq_ignore_ascii_case(CONTENT_LENGTH))
+            {
+                return Err(ReadError::RepeatedContentLength);
+            }
+
+            if name.is_empty() {
+                return Err(ReadError::EmptyHeaderName);
+            }
+            let name = if config.lowercases_names() {
+                Cow::Owned(name.to_lowercase())
+            } else {
+                name
+            };
+            entries.push((name, value));
+        }
+    }
+
+    /// The first value of the header `name`, which is the one that counts when it is repeated.
+    pub fn get<'s>(&'s self, name: &'s str) -> Option<&'s str> {
+        self.get_all(name).next()
+    }
+
+    pub fn get_all<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s str> + 's {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_ref())
+    }
+
+    /// The entries in the order they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_ref(), v.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Copies the entries into an owned `Header`.
+    pub fn to_header(&self) -> Header {
+        let mut header = Header::new();
+
+        for (k, v) in self.iter() {
+            header.push(k, v.to_owned());
+        }
+        header
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_borrows() {
+        let buffer = b"destination:/queue/a\r\nContent-Type:text/plain\nx:a\\cb\nx:c\n\nbody";
+        let (header, taken) = HeaderRef::parse(buffer, Version::V1_2, ParserConfig::lenient())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(buffer.len() - 4, taken);
+        assert_eq!(4, header.len());
+        assert_eq!(Some("text/plain"), header.get("content-type"));
+        assert_eq!(Some("a:b"), header.get("x"));
+        assert_eq!(vec!["a:b", "c"], header.get_all("x").collect::<Vec<_>>());
+        assert!(matches!(header.entries[0].1, Cow::Borrowed(_)));
+        assert!(matches!(header.entries[2].1, Cow::Owned(_)));
+
+        let owned = header.to_header();
+        assert_eq!(vec!["text/plain".to_owned()], owned["Content-Type"]);
+        assert_eq!(vec!["a:b".to_owned(), "c".to_owned()], owned["x"]);
+    }
+
+    #[test]
+    fn parse_incomplete_and_invalid() {
+        let lenient = ParserConfig::lenient();
+        assert_eq!(
+            None,
+            HeaderRef::parse(b"a:b\n", Version::V1_2, lenient).unwrap()
+        );
+        assert!(HeaderRef::parse(b"a\n\n", Version::V1_2, lenient).is_err());
+        assert!(HeaderRef::parse(b":b\n\n", Version::V1_2, lenient).is_err());
+
+        let strict = ParserConfig::strict();
+        assert!(HeaderRef::parse(b"a: b\n\n", Version::V1_2, strict).is_err());
+        assert!(HeaderRef::parse(b"a:b\\t\n\n", Version::V1_2, strict).is_err());
+    }
+}
@@ -1,6 +1,7 @@
+use crate::frame::ReadError;
 use std::cell::RefCell;
 use std::io;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Cursor, Read};
 use std::rc::Rc;
 
 pub struct LimitedReader<R: Read> {
@@ -12,6 +13,10 @@ impl<R: Read> LimitedReader<R> {
     pub fn new(reader: Rc<RefCell<R>>, limit: u64) -> Self {
         LimitedReader { reader, limit }
     }
+
+    pub fn remaining(&self) -> u64 {
+        self.limit
+    }
 }
 
 impl<R: Read> Read for LimitedReader<R> {
@@ -38,7 +43,10 @@ impl<R: Read> Read for LimitedReader<R> {
 pub struct DelimitedReader<R: Read> {
     inner: Rc<RefCell<R>>,
     delim: u8,
+    include_delim: bool,
     done: bool,
+    remaining: u64,
+    limit: Option<u64>,
 }
 
 impl<R: Read> DelimitedReader<R> {
@@ -46,9 +54,33 @@ impl<R: Read> DelimitedReader<R> {
         DelimitedReader {
             inner: reader,
             delim: delimiter,
+            include_delim: true,
             done: false,
+            remaining: u64::MAX,
+            limit: None,
         }
     }
+
+    /// Like `DelimitedReader::new`, but the delimiter is consumed without being returned.
+    pub fn excluding(reader: Rc<RefCell<R>>, delimiter: u8) -> Self {
+        DelimitedReader {
+            include_delim: false,
+            ..DelimitedReader::new(reader, delimiter)
+        }
+    }
+
+    /// Fails the read with `ReadError::BodyTooLarge` once more than `limit` bytes come before
+    /// the delimiter.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.remaining = limit;
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Whether the delimiter has been read, rather than the stream having ended before it.
+    pub fn found_delimiter(&self) -> bool {
+        self.done
+    }
 }
 
 impl<R: Read> Read for DelimitedReader<R> {
@@ -67,37 +99,209 @@ impl<R: Read> Read for DelimitedReader<R> {
             if bytes_read < 1 {
                 break;
             }
-            *x = local_buf[0];
-            total_bytes_read += bytes_read;
 
             if local_buf[0] == self.delim {
                 self.done = true;
+
+                if self.include_delim {
+                    *x = local_buf[0];
+                    total_bytes_read += bytes_read;
+                }
                 break;
             }
+
+            if let Some(limit) = self.limit {
+                if self.remaining == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        ReadError::BodyTooLarge { limit },
+                    ));
+                }
+                self.remaining -= 1;
+            }
+            *x = local_buf[0];
+            total_bytes_read += bytes_read;
         }
         Ok(total_bytes_read)
     }
 }
 
-pub struct BiReader<R1: Read, R2: Read> {
-    first: R1,
-    second: R2,
+/// Reads `length` bytes and then up to the delimiter that should follow them. Bytes in between
+/// are discarded, and in strict mode the read that finds them fails with
+/// `ReadError::ContentLengthMismatch`. If the stream ends first, the read fails with
+/// `ReadError::UnterminatedBody` in either mode.
+pub struct TerminatedReader<'a, R: Read> {
+    limited: LimitedReader<R>,
+    rest: DelimitedReader<R>,
+    length: u64,
+    strict: bool,
+    checked: bool,
+    on_discard: Option<Box<dyn FnMut(u64) + 'a>>,
+}
+
+impl<'a, R: Read> TerminatedReader<'a, R> {
+    pub fn new(reader: Rc<RefCell<R>>, length: u64, delimiter: u8, strict: bool) -> Self {
+        TerminatedReader {
+            limited: LimitedReader::new(reader.clone(), length),
+            rest: DelimitedReader::excluding(reader, delimiter),
+            length,
+            strict,
+            checked: false,
+            on_discard: None,
+        }
+    }
+
+    /// Calls `callback` with the number of bytes discarded in lenient mode.
+    pub fn on_discard<F: FnMut(u64) + 'a>(mut self, callback: F) -> Self {
+        self.on_discard = Some(Box::new(callback));
+        self
+    }
+}
+
+impl<'a, R: Read> Read for TerminatedReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let bytes_read = self.limited.read(buf)?;
+
+        if bytes_read > 0 || self.checked {
+            return Ok(bytes_read);
+        }
+        self.checked = true;
+        let received = self.length - self.limited.remaining();
+        let trailing = io::copy(&mut self.rest, &mut io::sink())?;
+
+        if received < self.length || !self.rest.found_delimiter() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                ReadError::UnterminatedBody {
+                    content_length: self.length,
+                    received,
+                },
+            ));
+        }
+
+        if trailing == 0 {
+            return Ok(0);
+        }
+
+        if self.strict {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ReadError::ContentLengthMismatch {
+                    content_length: self.length,
+                    trailing,
+                },
+            ));
+        }
+
+        if let Some(callback) = &mut self.on_discard {
+            callback(trailing);
+        }
+        Ok(0)
+    }
+}
+
+/// A reader whose buffered input can be looked at without reading more from its source.
+pub trait Buffered: BufRead {
+    fn buffered(&self) -> &[u8];
+}
+
+impl<R: Read> Buffered for BufReader<R> {
+    fn buffered(&self) -> &[u8] {
+        self.buffer()
+    }
+}
+
+impl<T: AsRef<[u8]>> Buffered for Cursor<T> {
+    fn buffered(&self) -> &[u8] {
+        let input = self.get_ref().as_ref();
+        &input[(self.position() as usize).min(input.len())..]
+    }
+}
+
+impl<B: Buffered + ?Sized> Buffered for &mut B {
+    fn buffered(&self) -> &[u8] {
+        (**self).buffered()
+    }
+}
+
+/// Reads a body from `inner`, then consumes the EOLs already buffered after it, so that the
+/// next frame starts at its command. EOLs yet to arrive are not waited for.
+pub struct PaddedReader<'a, R: Buffered> {
+    inner: Box<dyn Read + 'a>,
+    reader: Rc<RefCell<R>>,
+    done: bool,
+}
+
+impl<'a, R: Buffered> PaddedReader<'a, R> {
+    pub fn new(inner: Box<dyn Read + 'a>, reader: Rc<RefCell<R>>) -> Self {
+        PaddedReader {
+            inner,
+            reader,
+            done: false,
+        }
+    }
+}
+
+impl<'a, R: Buffered> Read for PaddedReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+
+        if bytes_read == 0 && !buf.is_empty() && !self.done {
+            self.done = true;
+            let mut reader = self.reader.borrow_mut();
+            let eols = reader
+                .buffered()
+                .iter()
+                .take_while(|b| **b == b'\n' || **b == b'\r')
+                .count();
+            reader.consume(eols);
+        }
+        Ok(bytes_read)
+    }
+}
+
+pub struct SharedReader<R: Read> {
+    inner: Rc<RefCell<R>>,
 }
 
-impl<R1: Read, R2: Read> BiReader<R1, R2> {
-    pub fn new(first: R1, second: R2) -> Self {
-        BiReader { first, second }
+impl<R: Read> SharedReader<R> {
+    pub fn new(reader: Rc<RefCell<R>>) -> Self {
+        SharedReader { inner: reader }
     }
 }
 
-impl<R1: Read, R2: Read> Read for BiReader<R1, R2> {
+impl<R: Read> Read for SharedReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let first_bytes_read = self.first.read(buf)?;
+        self.inner.borrow_mut().read(buf)
+    }
+}
+
+pub struct CountingReader<R: Read> {
+    inner: R,
+    count: u64,
+}
 
-        if first_bytes_read > 0 {
-            return Ok(first_bytes_read);
+impl<R: Read> CountingReader<R> {
+    pub fn new(reader: R, count: u64) -> Self {
+        CountingReader {
+            inner: reader,
+            count,
         }
-        self.second.read(buf)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        self.count += bytes_read as u64;
+        Ok(bytes_read)
     }
 }
 
@@ -108,6 +312,30 @@ mod test {
     use std::io::Cursor;
     use std::str;
 
+    #[test]
+    fn terminated_reader() {
+        let input = b"abcjunk\0next";
+        let cell = Rc::new(RefCell::new(Cursor::new(&input[..])));
+
+        let mut reader = TerminatedReader::new(cell.clone(), 3, b'\0', false);
+        let mut buffer: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"abc".to_vec(), buffer);
+        assert_eq!(8, cell.borrow().position());
+
+        cell.borrow_mut().set_position(0);
+        let mut reader = TerminatedReader::new(cell.clone(), 3, b'\0', true);
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            ReadError::from(error),
+            ReadError::ContentLengthMismatch {
+                content_length: 3,
+                trailing: 4
+            }
+        ));
+        assert_eq!(8, cell.borrow().position());
+    }
+
     #[test]
     fn delimited_reader_middle() {
         let input = b"this is; a test";
@@ -148,18 +376,64 @@ mod test {
     }
 
     #[test]
-    fn bi_reader_read() {
-        let input = b"this is a test; that already ended";
+    fn delimited_reader_excluding() {
+        let input = b"this is; a test";
         let cell = Rc::new(RefCell::new(Cursor::new(input)));
 
-        let limited_reader = LimitedReader::new(cell.clone(), 3);
-        let delimited_reader = DelimitedReader::new(cell.clone(), b';');
-        let mut bi_reader = BiReader::new(limited_reader, delimited_reader);
-
+        let mut dreader = DelimitedReader::excluding(cell.clone(), b';');
         let mut buffer: Vec<u8> = Vec::new();
-        Read::read_to_end(&mut bi_reader, &mut buffer).unwrap();
+        Read::read_to_end(&mut dreader, &mut buffer).unwrap();
         let output = str::from_utf8(&buffer).unwrap();
-        let target = "this is a test;";
-        assert_eq!(target, output)
+        let target = "this is";
+        assert_eq!(target, output);
+        assert_eq!(8, cell.borrow().position());
+    }
+
+    #[test]
+    fn terminated_reader_eof() {
+        for (input, received) in [(&b"ab"[..], 2), (&b"abc"[..], 3), (&b"abcjunk"[..], 3)].iter() {
+            let cell = Rc::new(RefCell::new(Cursor::new(*input)));
+            let mut reader = TerminatedReader::new(cell, 3, b'\0', false);
+            let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+            assert!(matches!(
+                ReadError::from(error),
+                ReadError::UnterminatedBody { content_length: 3, received: r } if r == *received
+            ));
+        }
+    }
+
+    #[test]
+    fn padded_reader() {
+        let input = b"abc\0\r\n\nnext";
+        let cell = Rc::new(RefCell::new(Cursor::new(&input[..])));
+
+        let body = Box::new(DelimitedReader::excluding(cell.clone(), b'\0'));
+        let mut reader = PaddedReader::new(body, cell.clone());
+        let mut buffer: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"abc".to_vec(), buffer);
+        assert_eq!(7, cell.borrow().position());
+    }
+
+    #[test]
+    fn shared_reader_read() {
+        let input = b"this is a test";
+        let cell = Rc::new(RefCell::new(Cursor::new(input)));
+
+        let mut shared_reader = SharedReader::new(cell.clone());
+        let mut buffer = [0; 4];
+        shared_reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"this", &buffer);
+        assert_eq!(4, cell.borrow().position());
+    }
+
+    #[test]
+    fn counting_reader_read() {
+        let input = b"this is a test";
+        let mut counting_reader = CountingReader::new(Cursor::new(input), 10);
+        let mut buffer = [0; 4];
+        counting_reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(14, counting_reader.count());
     }
 }
@@ -1,20 +1,45 @@
+mod builder;
+mod config;
+#[cfg(feature = "differential")]
+pub mod differential;
 mod error;
+mod events;
+mod header_ref;
+pub mod headers;
 mod io;
+mod owned;
+#[cfg(feature = "profiling")]
+mod profile;
+mod split;
 mod string;
-
-use crate::frame::io::{BiReader, LimitedReader};
-use error::ReadError;
+mod sync;
+mod validate;
+mod version;
+
+pub use builder::{allows_body, required_headers, required_headers_for, BuildError, FrameBuilder};
+pub use config::{Limits, ParserConfig};
+pub use error::{LimitKind, ReadError};
+pub use events::{ParserEvent, ParserEvents};
+pub use header_ref::HeaderRef;
+pub use owned::OwnedFrame;
+#[cfg(feature = "profiling")]
+pub use profile::ParseTimings;
+pub use split::{FrameSplitter, SplitFrame};
+pub use sync::SyncFrameReader;
+pub use validate::ValidationError;
+pub use version::Version;
+
+use crate::frame::headers::CONTENT_LENGTH;
+use crate::frame::io::{Buffered, CountingReader, PaddedReader, SharedReader, TerminatedReader};
 use io::DelimitedReader;
-use std::borrow::BorrowMut;
 use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
-use std::error::Error;
 use std::fmt;
-use std::fmt::{Display, Formatter};
 use std::io as stdio;
 use std::io::{BufRead, BufReader, BufWriter};
 use std::io::{Read, Write};
-use std::ops::{Deref, DerefMut};
+use std::mem;
+use std::ops::{DerefMut, Index};
 use std::rc::Rc;
 use std::str;
 use std::str::FromStr;
@@ -22,22 +47,10 @@ use std::str::FromStr;
 type LockFlag = isize;
 const UNUSED: LockFlag = 0;
 
-const MAX_COMMAND_SIZE: u64 = 1024;
-const MAX_HEADER_SIZE: u64 = 1024 * 1000;
+const SMALL_FRAME_SIZE: usize = 4096;
 const NULL: u8 = b'\0';
 const EOL: u8 = b'\n';
 
-#[derive(Debug, Clone)]
-pub struct LatchError;
-
-impl Display for LatchError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "latch already locked")
-    }
-}
-
-impl Error for LatchError {}
-
 struct Guard<'a> {
     value: &'a Cell<LockFlag>,
 }
@@ -72,19 +85,16 @@ impl Gate {
         }
     }
 
-    fn latch(&self) -> Guard<'_> {
-        Guard::new(&self.latch).expect("gate is latched")
+    fn latch(&self) -> Result<Guard<'_>, ReadError> {
+        Guard::new(&self.latch).ok_or(ReadError::PreviousBodyUnfinished)
     }
 
-    fn try_latch(&self) -> Result<Guard<'_>, LatchError> {
-        match Guard::new(&self.latch) {
-            Some(g) => Ok(g),
-            None => Err(LatchError),
-        }
+    fn is_latched(&self) -> bool {
+        self.latch.get() != UNUSED
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Command {
     Connect,
     Stomp,
@@ -103,11 +113,11 @@ pub enum Command {
     Error,
 }
 
-impl fmt::Display for Command {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Command {
+    pub fn as_str(&self) -> &'static str {
         use self::Command::*;
 
-        let value = match self {
+        match self {
             Connect => "CONNECT",
             Stomp => "STOMP",
             Connected => "CONNECTED",
@@ -123,9 +133,37 @@ impl fmt::Display for Command {
             Message => "MESSAGE",
             Receipt => "RECEIPT",
             Error => "ERROR",
-        };
+        }
+    }
 
-        write!(f, "{}", value)
+    /// Matches a command name without UTF-8 validation.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Command> {
+        use self::Command::*;
+
+        match bytes {
+            b"CONNECT" => Some(Connect),
+            b"STOMP" => Some(Stomp),
+            b"CONNECTED" => Some(Connected),
+            b"SEND" => Some(Send),
+            b"SUBSCRIBE" => Some(Subscribe),
+            b"UNSUBSCRIBE" => Some(Unsubscribe),
+            b"ACK" => Some(Ack),
+            b"NACK" => Some(Nack),
+            b"BEGIN" => Some(Begin),
+            b"COMMIT" => Some(Commit),
+            b"ABORT" => Some(Abort),
+            b"DISCONNECT" => Some(Disconnect),
+            b"MESSAGE" => Some(Message),
+            b"RECEIPT" => Some(Receipt),
+            b"ERROR" => Some(Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
     }
 }
 
@@ -151,84 +189,335 @@ impl FromStr for Command {
             "MESSAGE" => Ok(Message),
             "RECEIPT" => Ok(Receipt),
             "ERROR" => Ok(Error),
-            _ => Err("invalid command".into()),
+            _ => Err(ReadError::InvalidCommand { got: s.to_owned() }),
         }
     }
 }
 
-#[derive(Default, PartialEq, Debug)]
-pub struct Header(BTreeMap<String, Vec<String>>);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AckMode {
+    #[default]
+    Auto,
+    Client,
+    ClientIndividual,
+}
 
-impl Deref for Header {
-    type Target = BTreeMap<String, Vec<String>>;
+impl AckMode {
+    pub fn as_str(&self) -> &'static str {
+        use self::AckMode::*;
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+        match self {
+            Auto => "auto",
+            Client => "client",
+            ClientIndividual => "client-individual",
+        }
     }
 }
 
-impl DerefMut for Header {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.borrow_mut()
+impl fmt::Display for AckMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for AckMode {
+    type Err = ReadError;
+
+    fn from_str(s: &str) -> Result<AckMode, ReadError> {
+        use self::AckMode::*;
+
+        match s {
+            "auto" => Ok(Auto),
+            "client" => Ok(Client),
+            "client-individual" => Ok(ClientIndividual),
+            _ => Err(ReadError::InvalidAckMode { got: s.to_owned() }),
+        }
+    }
+}
+
+/// Header entries in the order their names first appeared, each with its values in the order
+/// they were added. The STOMP specification has the first value of a repeated header count,
+/// which is what `Header::first` returns.
+#[derive(Default, PartialEq, Clone)]
+pub struct Header {
+    entries: Vec<(String, Vec<String>)>,
+    index: BTreeMap<String, usize>,
+}
+
+impl fmt::Debug for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl Index<&str> for Header {
+    type Output = Vec<String>;
+
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key).expect("no header with this name")
+    }
+}
+
+impl<'a> IntoIterator for &'a Header {
+    type Item = (&'a String, &'a Vec<String>);
+    type IntoIter = HeaderIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct HeaderIter<'a>(std::slice::Iter<'a, (String, Vec<String>)>);
+
+impl<'a> Iterator for HeaderIter<'a> {
+    type Item = (&'a String, &'a Vec<String>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k, v))
     }
 }
 
 impl Header {
     pub fn new() -> Self {
-        Header(BTreeMap::new())
+        Header::default()
     }
 
+    /// Adds a value after any the header already has.
     pub fn push<T: Into<String>>(&mut self, key: T, value: String) {
-        self.entry(key.into())
-            .or_insert_with(|| Vec::with_capacity(1))
-            .push(value)
+        let key = key.into();
+
+        match self.index.get(&key) {
+            Some(i) => self.entries[*i].1.push(value),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, vec![value]));
+            }
+        }
+    }
+
+    /// The value that counts when a header is repeated.
+    pub fn first(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|v| v.first()).map(String::as_str)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.index.get(key).map(|i| &self.entries[*i].1)
+    }
+
+    /// Like `Header::get`, but matches names regardless of ASCII case, returning the values of
+    /// the first header that matches.
+    pub fn get_ignore_case(&self, key: &str) -> Option<&Vec<String>> {
+        match self.get(key) {
+            Some(values) => Some(values),
+            None => self
+                .entries
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+        }
+    }
+
+    pub fn first_ignore_case(&self, key: &str) -> Option<&str> {
+        self.get_ignore_case(key)
+            .and_then(|v| v.first())
+            .map(String::as_str)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Vec<String>> {
+        let i = *self.index.get(key)?;
+        Some(&mut self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Replaces the values of a header, which keeps its position, or adds it at the end.
+    pub fn insert(&mut self, key: String, values: Vec<String>) -> Option<Vec<String>> {
+        match self.index.get(&key) {
+            Some(i) => Some(mem::replace(&mut self.entries[*i].1, values)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, values));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<String>> {
+        let i = self.index.remove(key)?;
+        let (_, values) = self.entries.remove(i);
+
+        for position in self.index.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(values)
+    }
+
+    /// Like `Header::remove`, but removes every header whose name matches regardless of ASCII
+    /// case, returning their values in order.
+    pub fn remove_ignore_case(&mut self, key: &str) -> Vec<String> {
+        let names: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(k, _)| k.clone())
+            .collect();
+        names
+            .iter()
+            .filter_map(|name| self.remove(name))
+            .flatten()
+            .collect()
+    }
+
+    pub fn iter(&self) -> HeaderIter<'_> {
+        HeaderIter(self.entries.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 
     pub fn write_to<W: Write>(&self, mut w: W) -> stdio::Result<u64> {
-        let mut bytes_written: u64 = 0;
+        let mut buffer: Vec<u8> = Vec::with_capacity(256);
+        self.encode_into(&mut buffer, Version::default(), false);
+        w.write_all(&buffer)?;
+        Ok(buffer.len() as u64)
+    }
 
-        for (k, v) in self.0.iter() {
-            let field_str = format!("{}: {}\n", string::encode(k), string::encode(&v.join(",")));
-            let size = w.write(field_str.as_bytes())?;
-            bytes_written += size as u64;
+    /// Writes a line per value, so that a repeated header is read back the same way. With `pad`,
+    /// a space follows each colon; STOMP reads it as part of the value.
+    fn encode_into(&self, buffer: &mut Vec<u8>, version: Version, pad: bool) {
+        let separator: &[u8] = if pad { b": " } else { b":" };
+
+        for (k, v) in self.iter() {
+            for value in v {
+                string::encode_into(k, version, buffer);
+                buffer.extend_from_slice(separator);
+                string::encode_into(value, version, buffer);
+                buffer.push(EOL);
+            }
         }
-        Ok(bytes_written)
     }
 
-    fn read_from<R: Read>(reader: &mut BufReader<R>) -> Result<Self, ReadError> {
-        let mut limited_reader = reader.take(MAX_HEADER_SIZE);
+    /// Reads header lines up to the blank line that ends them, using `buffer` for each line so
+    /// that its allocation is kept between frames.
+    fn read_from<R: Read>(
+        reader: &mut BufReader<R>,
+        buffer: &mut Vec<u8>,
+        version: Version,
+        config: ParserConfig,
+        limits: Limits,
+        events: Option<&dyn ParserEvents>,
+    ) -> Result<Self, ReadError> {
+        let mut limited_reader = reader.take(limits.max_header);
         let mut header = Self::new();
+        let mut count = 0;
 
         loop {
-            let mut buffer: Vec<u8> = Vec::new();
-            let bytes_read = limited_reader.read_until(EOL, &mut buffer)?;
+            buffer.clear();
+            let bytes_read = read_until_eol_or_null(&mut limited_reader, buffer)?;
+
+            if limited_reader.limit() == 0 && !matches!(buffer.last(), Some(&EOL) | Some(&NULL)) {
+                return Err(ReadError::LimitExceeded {
+                    kind: LimitKind::Header,
+                    limit: limits.max_header,
+                });
+            }
 
             if bytes_read < 1 {
                 break;
             }
-            let line = str::from_utf8(&buffer)?;
-            let clean_line = line.trim_end_matches('\r').trim_end_matches('\n');
-            let parts: Vec<&str> = clean_line.split(':').collect();
-
-            if parts.len() < 2 {
-                return Err(format!(
-                    "invalid number of header field parts. Expected 2, got {}",
-                    parts.len()
+
+            if buffer.last() == Some(&NULL) {
+                skip_eols(limited_reader.get_mut())?;
+                return Err(ReadError::NullInHeader);
+            }
+            let line = str::from_utf8(buffer)?;
+            let mut clean_line = line.strip_suffix('\n').unwrap_or(line);
+
+            if version.allows_crlf() {
+                clean_line = clean_line.strip_suffix('\r').unwrap_or(clean_line);
+            }
+
+            if clean_line.is_empty() {
+                break;
+            }
+            count += 1;
+
+            if count > limits.max_headers_count {
+                return Err(ReadError::LimitExceeded {
+                    kind: LimitKind::HeadersCount,
+                    limit: limits.max_headers_count as u64,
+                });
+            }
+            let (name, value) = clean_line
+                .split_once(':')
+                .ok_or(ReadError::MalformedHeaderLine { line_no: count })?;
+            let untrimmed = name.trim() != name || value.trim_start() != value;
+
+            if config.is_strict() && untrimmed {
+                return Err(ReadError::UntrimmedWhitespace {
+                    header: name.trim().to_owned(),
+                });
+            }
+            let (name, value) = (name.trim(), value.trim_start());
+            let (field_name, field_value) = if config.is_strict() {
+                (
+                    string::decode_strict(name, version)?,
+                    string::decode_strict(value, version)?,
                 )
-                .into());
+            } else {
+                (
+                    decode_reporting(name, version, events),
+                    decode_reporting(value, version, events),
+                )
+            };
+
+            if config.is_strict()
+                && field_name.eq_ignore_ascii_case(CONTENT_LENGTH)
+                && header.get_ignore_case(CONTENT_LENGTH).is_some()
+            {
+                return Err(ReadError::RepeatedContentLength);
             }
-            let field_name = string::decode(parts[0]);
-            let field_value = string::decode(parts[1]);
 
-            let clean_field_name = field_name.trim().to_lowercase();
-            let clean_field_value = field_value
-                .trim_start()
-                .trim_end_matches('\n')
-                .trim_end_matches('\r')
-                .to_owned();
+            let clean_field_name = if config.lowercases_names() {
+                field_name.to_lowercase()
+            } else {
+                field_name.into_owned()
+            };
+            let clean_field_value = field_value.into_owned();
 
             if clean_field_name.is_empty() {
-                return Err("empty header field name".into());
+                return Err(ReadError::EmptyHeaderName);
+            }
+
+            if untrimmed {
+                events::notify(
+                    events,
+                    ParserEvent::UntrimmedWhitespace {
+                        header: &clean_field_name,
+                    },
+                );
+            }
+
+            if header.contains_key(&clean_field_name) {
+                events::notify(
+                    events,
+                    ParserEvent::RepeatedHeader {
+                        header: &clean_field_name,
+                    },
+                );
             }
             header.push(clean_field_name, clean_field_value);
         }
@@ -236,13 +525,139 @@ impl Header {
     }
 }
 
+/// Parses a `content-length` value, checking it against `Limits::max_body`.
+fn content_length(value: &str, limits: Limits) -> Result<u64, ReadError> {
+    let n = value
+        .parse::<u64>()
+        .map_err(|source| ReadError::InvalidContentLength {
+            value: value.to_owned(),
+            source,
+        })?;
+
+    if n > limits.max_body {
+        return Err(ReadError::LimitExceeded {
+            kind: LimitKind::Body,
+            limit: limits.max_body,
+        });
+    }
+    Ok(n)
+}
+
+/// Decodes leniently, reporting an escape sequence that strict decoding would reject.
+fn decode_reporting<'a>(
+    input: &'a str,
+    version: Version,
+    events: Option<&dyn ParserEvents>,
+) -> std::borrow::Cow<'a, str> {
+    match string::decode_strict(input, version) {
+        Ok(output) => output,
+        Err(_) => {
+            events::notify(events, ParserEvent::InvalidEscape(input));
+            string::decode(input, version)
+        }
+    }
+}
+
+/// Like `BufRead::read_until` with EOL, but also stops after a NULL.
+fn read_until_eol_or_null<R: BufRead>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+) -> stdio::Result<usize> {
+    let mut total = 0;
+
+    loop {
+        let available = reader.fill_buf()?;
+
+        if available.is_empty() {
+            return Ok(total);
+        }
+
+        match memchr::memchr2(EOL, NULL, available) {
+            Some(i) => {
+                buffer.extend_from_slice(&available[..=i]);
+                reader.consume(i + 1);
+                return Ok(total + i + 1);
+            }
+            None => {
+                let n = available.len();
+                buffer.extend_from_slice(available);
+                reader.consume(n);
+                total += n;
+            }
+        }
+    }
+}
+
+/// Consumes EOLs up to the next frame, returning false at the end of the input stream.
+fn skip_eols<R: Read>(reader: &mut BufReader<R>) -> stdio::Result<bool> {
+    loop {
+        let buffer = reader.fill_buf()?;
+
+        if buffer.is_empty() {
+            return Ok(false);
+        }
+        let eols = buffer
+            .iter()
+            .take_while(|b| **b == EOL || **b == b'\r')
+            .count();
+
+        if eols == 0 {
+            return Ok(true);
+        }
+        reader.consume(eols);
+    }
+}
+
 pub struct Body<'a> {
     reader: Box<dyn Read + 'a>,
+    source: Rc<RefCell<Box<dyn Read + 'a>>>,
+    _guard: Option<Guard<'a>>,
 }
 
 impl<'a> Body<'a> {
+    pub fn new<T: Read + 'a>(reader: T) -> Self {
+        Body::with_guard(Box::new(reader), None)
+    }
+
+    pub fn empty() -> Self {
+        Body::new(stdio::empty())
+    }
+
+    fn with_guard(source: Box<dyn Read + 'a>, guard: Option<Guard<'a>>) -> Self {
+        let source = Rc::new(RefCell::new(source));
+        let reader = Box::new(SharedReader::new(source.clone()));
+
+        Body {
+            reader,
+            source,
+            _guard: guard,
+        }
+    }
+
+    /// Replaces the body reader with a wrapper around it, such as a decompressor. The wrapper
+    /// cannot read past the end of the body, and closing the body still consumes whatever the
+    /// wrapper left unread.
+    pub fn map_reader<F, T>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Box<dyn Read + 'a>) -> T,
+        T: Read + 'a,
+    {
+        let reader = mem::replace(&mut self.reader, Box::new(stdio::empty()));
+        self.reader = Box::new(f(reader));
+        self
+    }
+
+    /// Reads and discards the rest of the body, leaving the input stream past its NULL and the
+    /// EOLs already received after it. Dropping the body does the same, ignoring errors.
     pub fn close(&mut self) -> stdio::Result<()> {
-        stdio::copy(&mut *self.reader, &mut stdio::sink()).map(|_| ())
+        let result = stdio::copy(&mut *self.reader, &mut stdio::sink());
+        self.drain()?;
+        result.map(|_| ())
+    }
+
+    fn drain(&mut self) -> stdio::Result<()> {
+        let mut source = RefCell::borrow_mut(&self.source);
+        stdio::copy(&mut *source, &mut stdio::sink()).map(|_| ())
     }
 }
 
@@ -252,42 +667,103 @@ impl<'a> Read for Body<'a> {
     }
 }
 
-struct BodyBuilder<R: Read> {
+impl<'a> Drop for Body<'a> {
+    fn drop(&mut self) {
+        let _ = self.drain();
+    }
+}
+
+struct BodyBuilder<'a, R: Buffered> {
     reference: Rc<RefCell<R>>,
     content_length: Option<u64>,
+    config: ParserConfig,
+    limits: Limits,
+    events: Option<&'a dyn ParserEvents>,
+    guard: Option<Guard<'a>>,
 }
 
-impl<'a, R: Read + 'a> BodyBuilder<R> {
+impl<'a, R: Buffered + 'a> BodyBuilder<'a, R> {
     fn new(reference: Rc<RefCell<R>>) -> Self {
         BodyBuilder {
             reference,
             content_length: None,
+            config: ParserConfig::default(),
+            limits: Limits::default(),
+            events: None,
+            guard: None,
         }
     }
 
+    fn config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn events(mut self, events: Option<&'a dyn ParserEvents>) -> Self {
+        self.events = events;
+        self
+    }
+
     fn content_length(mut self, length: u64) -> Self {
         self.content_length = Some(length);
         self
     }
 
-    fn build(self) -> Body<'a> {
-        let reader: Box<dyn Read> = if let Some(n) = self.content_length {
-            let limited_reader = LimitedReader::new(self.reference.clone(), n);
-            let delimited_reader = DelimitedReader::new(self.reference, NULL);
-            Box::new(BiReader::new(limited_reader, delimited_reader))
-        } else {
-            Box::new(DelimitedReader::new(self.reference, NULL))
-        };
+    fn guard(mut self, guard: Guard<'a>) -> Self {
+        self.guard = Some(guard);
+        self
+    }
 
-        Body { reader }
+    fn build(self) -> Body<'a> {
+        let reader = body_reader(
+            self.reference,
+            self.content_length,
+            self.config,
+            self.limits,
+            self.events,
+        );
+        Body::with_guard(reader, self.guard)
     }
 }
 
+/// Reads a body up to its NULL terminator. With a `content-length`, bytes between the declared
+/// length and the NULL are discarded, or in strict mode fail the read. Without one, the body
+/// fails the read once it exceeds `Limits::max_body`. EOLs already buffered after the NULL are
+/// consumed with it.
+fn body_reader<'a, R: Buffered + 'a>(
+    reference: Rc<RefCell<R>>,
+    content_length: Option<u64>,
+    config: ParserConfig,
+    limits: Limits,
+    events: Option<&'a dyn ParserEvents>,
+) -> Box<dyn Read + 'a> {
+    let body: Box<dyn Read + 'a> = if let Some(n) = content_length {
+        let reader = TerminatedReader::new(reference.clone(), n, NULL, config.is_strict());
+
+        match events {
+            Some(events) => Box::new(reader.on_discard(move |discarded| {
+                events.on_event(&ParserEvent::TrailingBytes {
+                    content_length: n,
+                    discarded,
+                })
+            })),
+            None => Box::new(reader),
+        }
+    } else {
+        Box::new(DelimitedReader::excluding(reference.clone(), NULL).limit(limits.max_body))
+    };
+    Box::new(PaddedReader::new(body, reference))
+}
+
 pub struct Frame<'a> {
     pub command: Command,
     pub header: Header,
     pub body: Body<'a>,
-    _guard: Option<Guard<'a>>,
 }
 
 impl<'a> Frame<'a> {
@@ -296,113 +772,562 @@ impl<'a> Frame<'a> {
             command,
             header,
             body,
-            _guard: None,
         }
     }
 
-    fn with_guard(command: Command, header: Header, body: Body<'a>, guard: Guard<'a>) -> Self {
-        Frame {
-            command,
-            header,
-            body,
-            _guard: Some(guard),
-        }
+    /// Reassembles a frame from parts previously taken apart with `Frame::into_parts`.
+    pub fn from_parts(parts: (Command, Header, Body<'a>)) -> Self {
+        let (command, header, body) = parts;
+        Frame::new(command, header, body)
     }
 
+    /// Takes the frame apart without reading the body. The body keeps its place on the input
+    /// stream, so the next frame cannot be read until the body has been read, closed, or dropped.
+    pub fn into_parts(self) -> (Command, Header, Body<'a>) {
+        (self.command, self.header, self.body)
+    }
+
+    /// Writes the frame. Frames of up to 4 KiB are encoded into a single buffer and handed to the
+    /// writer in one call; larger bodies are streamed after the head.
     pub fn write_to<W: Write>(&mut self, w: W) -> stdio::Result<u64> {
+        self.write_with(w, Version::default(), false)
+    }
+
+    fn write_with<W: Write>(
+        &mut self,
+        mut w: W,
+        version: Version,
+        pad: bool,
+    ) -> stdio::Result<u64> {
+        let mut buffer: Vec<u8> = Vec::with_capacity(SMALL_FRAME_SIZE);
+        buffer.extend_from_slice(self.command.as_str().as_bytes());
+        buffer.push(EOL);
+        self.header.encode_into(&mut buffer, version, pad);
+        buffer.push(EOL);
+
+        let room = SMALL_FRAME_SIZE.saturating_sub(buffer.len() + 1) as u64;
+        let body_bytes_read = (&mut self.body).take(room + 1).read_to_end(&mut buffer)?;
+
+        if body_bytes_read as u64 <= room {
+            buffer.push(NULL);
+            w.write_all(&buffer)?;
+            return w.flush().and(Ok(buffer.len() as u64));
+        }
         let mut bw = BufWriter::new(w);
-        let mut bytes_written: u64 = 0;
-        bytes_written += bw.write(self.command.to_string().as_bytes())? as u64;
-        bytes_written += bw.write(&[EOL])? as u64;
-        bytes_written += self.header.write_to(&mut bw)?;
-        bytes_written += bw.write(&[EOL])? as u64;
+        let mut bytes_written: u64 = buffer.len() as u64;
+        bw.write_all(&buffer)?;
         bytes_written += stdio::copy(&mut self.body, &mut bw)?;
-        bytes_written += bw.write(&[NULL])? as u64;
+        bw.write_all(&[NULL])?;
+        bytes_written += 1;
 
         bw.flush().and(Ok(bytes_written))
     }
 
-    fn read_command<R: Read>(r: &mut BufReader<R>) -> Result<Command, ReadError> {
-        let mut command_reader = r.take(MAX_COMMAND_SIZE);
+    /// Reads the command line. A line that is already buffered and names a known command is
+    /// matched on its bytes without allocating; anything else goes through the string path,
+    /// which reports what was wrong with it.
+    fn read_command<R: Read>(r: &mut BufReader<R>, limit: u64) -> Result<Command, ReadError> {
+        let buffer = r.fill_buf()?;
+
+        if let Some(end) = memchr::memchr(EOL, buffer) {
+            if (end as u64) < limit {
+                if let Some(command) = Command::from_bytes(buffer[..end].trim_ascii()) {
+                    r.consume(end + 1);
+                    return Ok(command);
+                }
+            }
+        }
+        let mut command_reader = r.take(limit);
         let mut command_buffer: Vec<u8> = Vec::new();
         let cmd_bytes_read = command_reader.read_until(EOL, &mut command_buffer)?;
 
         if cmd_bytes_read < 1 {
-            return Err("empty command".into());
+            return Err(ReadError::EmptyCommand);
+        }
+
+        if command_reader.limit() == 0 && command_buffer.last() != Some(&EOL) {
+            return Err(ReadError::LimitExceeded {
+                kind: LimitKind::Command,
+                limit,
+            });
         }
         let raw_string_command = str::from_utf8(&command_buffer)?;
         let clean_string_command = raw_string_command.trim();
 
         if clean_string_command.is_empty() {
-            return Err("empty command".into());
+            return Err(ReadError::EmptyCommand);
         }
         Command::from_str(clean_string_command)
     }
 }
 
-impl<'a> Drop for Frame<'a> {
-    fn drop(&mut self) {
-        self.body.close().unwrap();
+/// How `FrameWriter` sets the `content-length` header of the frames it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentLengthMode {
+    /// Sets `content-length` from the body when the frame has none, reading the body into memory
+    /// first. A header already present is trusted, so a large body of known length is streamed.
+    /// This is the default.
+    Missing,
+    /// Reads every body into memory and replaces any `content-length` header with its length.
+    Always,
+    /// Writes frames without adding the header. A body without one is read into memory and
+    /// refused if it contains a NULL byte, which would end the frame early.
+    Never,
+}
+
+pub struct FrameWriter<W: Write> {
+    writer: W,
+    version: Version,
+    pad_values: bool,
+    content_length: ContentLengthMode,
+}
+
+impl<W: Write> FrameWriter<W> {
+    pub fn new(writer: W) -> FrameWriter<W> {
+        FrameWriter {
+            writer,
+            version: Version::default(),
+            pad_values: false,
+            content_length: ContentLengthMode::Missing,
+        }
+    }
+
+    /// The protocol version that header escaping follows, 1.2 unless changed with
+    /// `FrameWriter::set_version`.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    /// Whether header lines are written as `name: value` rather than `name:value`, off unless
+    /// changed with `FrameWriter::set_pad_values`.
+    pub fn pads_values(&self) -> bool {
+        self.pad_values
+    }
+
+    /// Writes a space after the colon of each header line, for peers that expect the padded
+    /// form. The specification counts the space as part of the value, so strict readers, this
+    /// crate's included, see a different value or reject the frame.
+    pub fn set_pad_values(&mut self, pad: bool) {
+        self.pad_values = pad;
+    }
+
+    pub fn content_length(&self) -> ContentLengthMode {
+        self.content_length
+    }
+
+    pub fn set_content_length(&mut self, mode: ContentLengthMode) {
+        self.content_length = mode;
+    }
+
+    /// Writes the frame and flushes the writer, setting its `content-length` header as
+    /// `FrameWriter::set_content_length` chose. A body refused under `ContentLengthMode::Never`
+    /// fails with `io::ErrorKind::InvalidInput` before anything is written.
+    pub fn write_frame(&mut self, frame: &mut Frame<'_>) -> stdio::Result<u64> {
+        let present = frame.header.get_ignore_case(CONTENT_LENGTH).is_some();
+        let measure = match self.content_length {
+            ContentLengthMode::Missing => !present,
+            ContentLengthMode::Always => true,
+            ContentLengthMode::Never => false,
+        };
+
+        if measure || !present {
+            let mut body: Vec<u8> = Vec::new();
+            frame.body.read_to_end(&mut body)?;
+
+            if measure {
+                frame.header.remove_ignore_case(CONTENT_LENGTH);
+                frame
+                    .header
+                    .insert(CONTENT_LENGTH.to_owned(), vec![body.len().to_string()]);
+            } else if memchr::memchr(NULL, &body).is_some() {
+                return Err(stdio::Error::new(
+                    stdio::ErrorKind::InvalidInput,
+                    "body contains a NULL byte but the frame has no content-length header",
+                ));
+            }
+            frame.body = Body::new(stdio::Cursor::new(body));
+        }
+        frame.write_with(&mut self.writer, self.version, self.pad_values)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReaderState {
+    /// The next call to `read_frame` starts reading a command.
+    Ready,
+    /// A frame body is still outstanding on the input stream.
+    Body,
+}
+
 pub struct FrameReader<R: Read> {
-    reader: Rc<RefCell<BufReader<R>>>,
+    reader: Rc<RefCell<BufReader<CountingReader<R>>>>,
     gate: Gate,
+    line: RefCell<Vec<u8>>,
+    version: Cell<Version>,
+    config: ParserConfig,
+    limits: Limits,
+    events: Option<Box<dyn ParserEvents>>,
+    #[cfg(feature = "profiling")]
+    profile: profile::Profile,
 }
 
 impl<R: Read> FrameReader<R> {
     pub fn new(reader: R) -> FrameReader<R> {
+        FrameReader::with_position(reader, 0)
+    }
+
+    /// Creates a reader over a stream that has already been read up to `position`, such as a
+    /// capture file seeked to a position saved from an earlier `FrameReader::position`.
+    pub fn with_position(reader: R, position: u64) -> FrameReader<R> {
+        let counting_reader = CountingReader::new(reader, position);
+
         FrameReader {
-            reader: Rc::new(RefCell::new(BufReader::new(reader))),
+            reader: Rc::new(RefCell::new(BufReader::new(counting_reader))),
             gate: Gate::new(),
+            line: RefCell::new(Vec::new()),
+            version: Cell::new(Version::default()),
+            config: ParserConfig::default(),
+            limits: Limits::default(),
+            events: None,
+            #[cfg(feature = "profiling")]
+            profile: profile::Profile::default(),
+        }
+    }
+
+    pub fn with_config(reader: R, config: ParserConfig) -> FrameReader<R> {
+        FrameReader {
+            config,
+            ..FrameReader::new(reader)
         }
     }
 
-    pub fn read_frame(&'static self) -> Result<Frame, ReadError> {
-        let guard = self.gate.try_latch()?;
-        let mut reader = self.reader.try_borrow_mut()?;
-        let command = Frame::read_command(reader.deref_mut())?;
-        let header = Header::read_from(reader.deref_mut())?;
+    pub fn config(&self) -> ParserConfig {
+        self.config
+    }
 
-        let clen = header
-            .get("content-length")
-            .map(|v| v.first())
-            .unwrap_or(None);
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
 
-        let mut body = BodyBuilder::new(self.reader.clone());
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Reports the input that lenient parsing repairs or discards to `events`.
+    pub fn set_events<E: ParserEvents + 'static>(&mut self, events: E) {
+        self.events = Some(Box::new(events));
+    }
+
+    /// Time spent parsing the frames read so far.
+    #[cfg(feature = "profiling")]
+    pub fn timings(&self) -> ParseTimings {
+        self.profile.timings()
+    }
+
+    /// The protocol version that header decoding follows, 1.2 unless changed with
+    /// `FrameReader::set_version`.
+    pub fn version(&self) -> Version {
+        self.version.get()
+    }
+
+    /// Changes how the headers of the following frames are decoded, typically once CONNECTED
+    /// has settled the version.
+    pub fn set_version(&self, version: Version) {
+        self.version.set(version);
+    }
+
+    /// The number of bytes consumed from the input stream. While in `ReaderState::Ready`, this is
+    /// the offset of the next frame.
+    pub fn position(&self) -> u64 {
+        let reader = RefCell::borrow(&self.reader);
+        reader.get_ref().count() - reader.buffer().len() as u64
+    }
+
+    pub fn state(&self) -> ReaderState {
+        if self.gate.is_latched() {
+            ReaderState::Body
+        } else {
+            ReaderState::Ready
+        }
+    }
+
+    /// Reads the next frame, skipping the heart-beat EOLs before it.
+    pub fn read_frame(&self) -> Result<Frame<'_>, ReadError> {
+        let guard = self.gate.latch()?;
+        let mut reader = self
+            .reader
+            .try_borrow_mut()
+            .map_err(|_| ReadError::PreviousBodyUnfinished)?;
+        let mut line = RefCell::borrow_mut(&self.line);
+        let (command, header) = loop {
+            skip_eols(reader.deref_mut())?;
+
+            #[cfg(not(feature = "profiling"))]
+            let (command, header) = (
+                Frame::read_command(reader.deref_mut(), self.limits.max_command)?,
+                Header::read_from(
+                    reader.deref_mut(),
+                    &mut line,
+                    self.version.get(),
+                    self.config,
+                    self.limits,
+                    self.events.as_deref(),
+                ),
+            );
+            #[cfg(feature = "profiling")]
+            let (command, header) = (
+                self.profile
+                    .command(|| Frame::read_command(reader.deref_mut(), self.limits.max_command))?,
+                self.profile.header(|| {
+                    Header::read_from(
+                        reader.deref_mut(),
+                        &mut line,
+                        self.version.get(),
+                        self.config,
+                        self.limits,
+                        self.events.as_deref(),
+                    )
+                }),
+            );
+
+            match header {
+                Err(ReadError::NullInHeader) if !self.config.is_strict() => {
+                    events::notify(self.events.as_deref(), ParserEvent::TruncatedFrame)
+                }
+                header => break (command, header?),
+            }
+        };
+        drop(line);
+
+        let clen = header.first_ignore_case(CONTENT_LENGTH);
+
+        let mut body = BodyBuilder::new(self.reader.clone())
+            .config(self.config)
+            .limits(self.limits)
+            .events(self.events.as_deref())
+            .guard(guard);
 
         body = if let Some(n) = clen {
-            body.content_length(n.parse::<u64>()?)
+            body.content_length(content_length(n, self.limits)?)
         } else {
             body
         };
 
-        let frame = Frame::with_guard(command, header, body.build(), guard);
+        let body = body.build();
+        #[cfg(feature = "profiling")]
+        let body = body.map_reader(|r| self.profile.body(r));
+
+        let frame = Frame::new(command, header, body);
 
         Ok(frame)
     }
+
+    /// Reads the next frame, or reports a heart-beat if EOLs come before it, so that the caller
+    /// can note the peer is alive. EOLs that arrive together count as one heart-beat.
+    /// `FrameReader::read_frame` skips them instead.
+    pub fn read_event(&self) -> Result<FrameEvent<'_>, ReadError> {
+        if self.gate.is_latched() {
+            return Err(ReadError::PreviousBodyUnfinished);
+        }
+        {
+            let mut reader = self
+                .reader
+                .try_borrow_mut()
+                .map_err(|_| ReadError::PreviousBodyUnfinished)?;
+            let eols = reader
+                .fill_buf()?
+                .iter()
+                .take_while(|b| **b == EOL || **b == b'\r')
+                .count();
+
+            if eols > 0 {
+                reader.consume(eols);
+                return Ok(FrameEvent::Heartbeat);
+            }
+        }
+        self.read_frame().map(FrameEvent::Frame)
+    }
+
+    /// Returns an iterator that reads frames into memory until the end of the input stream. EOLs
+    /// between frames, including heart-beats, are skipped. The iterator stops after the first
+    /// error.
+    pub fn frames(&self) -> Frames<'_, R> {
+        Frames {
+            reader: self,
+            done: false,
+        }
+    }
+
+    /// Consumes EOLs up to the next frame, returning false at the end of the input stream. This
+    /// waits for the first byte of the frame without consuming it, so a read timeout here leaves
+    /// the stream at a frame boundary.
+    pub fn skip_eols(&self) -> Result<bool, ReadError> {
+        if self.gate.is_latched() {
+            return Err(ReadError::PreviousBodyUnfinished);
+        }
+        let mut reader = RefCell::borrow_mut(&self.reader);
+        Ok(skip_eols(&mut reader)?)
+    }
+}
+
+/// What `FrameReader::read_event` read from the input stream.
+pub enum FrameEvent<'a> {
+    Frame(Frame<'a>),
+    Heartbeat,
+}
+
+pub struct Frames<'a, R: Read> {
+    reader: &'a FrameReader<R>,
+    done: bool,
+}
+
+impl<'a, R: Read> Iterator for Frames<'a, R> {
+    type Item = Result<OwnedFrame, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = match self.reader.skip_eols() {
+            Ok(false) => None,
+            Ok(true) => Some(
+                self.reader
+                    .read_frame()
+                    .and_then(|frame| frame.into_owned().map_err(ReadError::from)),
+            ),
+            Err(e) => Some(Err(e)),
+        };
+        self.done = !matches!(result, Some(Ok(_)));
+        result
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::error::Error;
     use std::io::Cursor;
 
     #[test]
-    #[should_panic]
     fn gate() {
         let gate = Gate::new();
-        let _guard = gate.latch();
-        gate.latch();
+        let _guard = gate.latch().unwrap();
+        let result = gate.latch();
+        assert!(matches!(result, Err(ReadError::PreviousBodyUnfinished)));
     }
 
     #[test]
     fn gate_proper() {
         let gate = Gate::new();
-        let guard = gate.latch();
+        let guard = gate.latch().unwrap();
         drop(guard);
-        gate.latch();
+        gate.latch().unwrap();
+    }
+
+    #[test]
+    fn read_frame_previous_body_unfinished() {
+        let input = b"SEND\n\nfirst\0SEND\n\nsecond\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let frame = frame_reader.read_frame().unwrap();
+        let result = frame_reader.read_frame();
+        assert!(matches!(result, Err(ReadError::PreviousBodyUnfinished)));
+
+        drop(frame);
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(Command::Send, frame.command);
+    }
+
+    #[test]
+    fn back_to_back_frames() {
+        let input = b"SEND\n\nfirst\0\nSEND\ncontent-length:6\n\nsecond\0\r\n\r\n\
+            SEND\n\nthird\0\n\nSEND\ncontent-length:6\n\nfourth\0\n";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+
+        let mut frame = frame_reader.read_frame().unwrap();
+        let mut body = String::new();
+        frame.body.read_to_string(&mut body).unwrap();
+        assert_eq!("first", body);
+        drop(frame);
+
+        let mut frame = frame_reader.read_frame().unwrap();
+        let mut buffer = [0; 3];
+        frame.body.read_exact(&mut buffer).unwrap();
+        frame.body.close().unwrap();
+        drop(frame);
+
+        let frame = frame_reader.read_frame().unwrap();
+        drop(frame);
+
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(b"fourth".to_vec(), frame.into_owned().unwrap().body);
+        assert_eq!(input.len() as u64, frame_reader.position());
+
+        let frame_reader = SyncFrameReader::new(Cursor::new(&input[..]));
+        for body in ["first", "second", "third", "fourth"].iter() {
+            assert_eq!(
+                body.as_bytes(),
+                &frame_reader.read_frame().unwrap().body[..]
+            );
+        }
+        assert_eq!(input.len() as u64, frame_reader.position());
+    }
+
+    #[test]
+    fn heartbeats() {
+        let input = b"\n\r\nSEND\n\nfirst\0\n\n\nSEND\n\nsecond\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(b"first".to_vec(), frame.into_owned().unwrap().body);
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(b"second".to_vec(), frame.into_owned().unwrap().body);
+
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        assert!(matches!(
+            frame_reader.read_event(),
+            Ok(FrameEvent::Heartbeat)
+        ));
+        let frame = match frame_reader.read_event().unwrap() {
+            FrameEvent::Frame(frame) => frame,
+            FrameEvent::Heartbeat => panic!("expected a frame"),
+        };
+        assert!(matches!(
+            frame_reader.read_event(),
+            Err(ReadError::PreviousBodyUnfinished)
+        ));
+        drop(frame);
+        assert!(matches!(
+            frame_reader.read_event(),
+            Ok(FrameEvent::Frame(_))
+        ));
+
+        let frame_reader = SyncFrameReader::new(Cursor::new(&input[..]));
+        assert_eq!(b"first".to_vec(), frame_reader.read_frame().unwrap().body);
+        assert_eq!(b"second".to_vec(), frame_reader.read_frame().unwrap().body);
+    }
+
+    #[test]
+    fn ack_mode_round_trip() {
+        for mode in &[AckMode::Auto, AckMode::Client, AckMode::ClientIndividual] {
+            assert_eq!(*mode, AckMode::from_str(&mode.to_string()).unwrap());
+        }
+        assert_eq!(AckMode::Auto, AckMode::default());
+        assert!(AckMode::from_str("Client").is_err());
     }
 
     #[test]
@@ -410,7 +1335,16 @@ mod test {
         let input = b"Content-Type: application/json\r\nContent-Length: 30\r\nName: Joshua\r\n";
         let reader = Cursor::new(&input[..]);
         let mut buf_reader = BufReader::new(reader);
-        let header = Header::read_from(&mut buf_reader).unwrap();
+        let mut line = b"stale".to_vec();
+        let header = Header::read_from(
+            &mut buf_reader,
+            &mut line,
+            Version::V1_2,
+            ParserConfig::lenient().lowercase_names(true),
+            Limits::default(),
+            None,
+        )
+        .unwrap();
 
         let mut target = Header::new();
         target.push("content-type", "application/json".to_owned());
@@ -419,9 +1353,102 @@ mod test {
         assert_eq!(target, header);
     }
 
+    #[test]
+    fn read_header_preserves_case() {
+        let input = b"SEND\ndestination:/queue/a\nX-Trace-Id:t-1\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = frame_reader.read_frame().unwrap();
+
+        assert_eq!(Some("t-1"), frame.header.first("X-Trace-Id"));
+        assert_eq!(None, frame.header.first("x-trace-id"));
+        assert_eq!(Some("t-1"), frame.header.first_ignore_case("x-trace-id"));
+        assert_eq!(
+            Some(&vec!["/queue/a".to_owned()]),
+            frame.header.get_ignore_case("Destination")
+        );
+
+        let mut buffer: Vec<u8> = Vec::new();
+        frame.write_to(&mut buffer).unwrap();
+        assert!(buffer.starts_with(b"SEND\ndestination:/queue/a\nX-Trace-Id:t-1\n"));
+    }
+
+    #[test]
+    fn read_header_value_with_colons() {
+        let input = b"destination:/queue/a:b\nselector:a\\cb:c\n\n";
+        let mut buf_reader = BufReader::new(Cursor::new(&input[..]));
+        let header = Header::read_from(
+            &mut buf_reader,
+            &mut Vec::new(),
+            Version::V1_2,
+            ParserConfig::lenient(),
+            Limits::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(vec!["/queue/a:b".to_owned()], header["destination"]);
+        assert_eq!(vec!["a:b:c".to_owned()], header["selector"]);
+    }
+
+    #[test]
+    fn read_header_escaped_whitespace() {
+        let input = b"a:\\nb\r\r\nc: \\r\r\n\r\n";
+
+        for config in [ParserConfig::lenient(), ParserConfig::strict()].iter() {
+            let mut buf_reader = BufReader::new(Cursor::new(&input[..]));
+            let header = Header::read_from(
+                &mut buf_reader,
+                &mut Vec::new(),
+                Version::V1_2,
+                *config,
+                Limits::default(),
+                None,
+            );
+
+            if config.is_strict() {
+                assert!(matches!(header, Err(ReadError::UntrimmedWhitespace { .. })));
+            } else {
+                let header = header.unwrap();
+                assert_eq!(Some("\nb\r"), header.first("a"));
+                assert_eq!(Some("\r"), header.first("c"));
+            }
+        }
+    }
+
+    #[test]
+    fn header_order() {
+        let input = b"SEND\nz:1\na:2\nz:3\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut header = frame_reader.read_frame().unwrap().header;
+
+        assert_eq!(vec!["z", "a"], header.keys().collect::<Vec<_>>());
+        assert_eq!(Some("1"), header.first("z"));
+        assert_eq!(vec!["1".to_owned(), "3".to_owned()], header["z"]);
+
+        let mut buffer: Vec<u8> = Vec::new();
+        header.write_to(&mut buffer).unwrap();
+        assert_eq!(b"z:1\nz:3\na:2\n".to_vec(), buffer);
+
+        assert_eq!(
+            Some(vec!["1".to_owned(), "3".to_owned()]),
+            header.remove("z")
+        );
+        header.insert("b".to_owned(), vec!["4".to_owned()]);
+        header.insert("a".to_owned(), vec!["5".to_owned()]);
+        assert_eq!(
+            vec![("a", "5"), ("b", "4")],
+            header
+                .iter()
+                .map(|(k, v)| (k.as_str(), v[0].as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("4"), header.first("b"));
+        assert_eq!(None, header.first("z"));
+    }
+
     #[test]
     fn write_header() {
-        let target = "Content-Length: 30\nContent-Type: application/json\n";
+        let target = "Content-Type:application/json\nContent-Length:30\n";
 
         let mut header = Header::new();
         header.push("Content-Type", "application/json".to_owned());
@@ -435,7 +1462,7 @@ mod test {
 
     #[test]
     fn write_header_encode_colon() {
-        let target = "Content-Length: 30\nContent-Type: vnd\\capplication/json\n";
+        let target = "Content-Type:vnd\\capplication/json\nContent-Length:30\n";
 
         let mut header = Header::new();
         header.push("Content-Type", "vnd:application/json".to_owned());
@@ -449,19 +1476,19 @@ mod test {
 
     #[test]
     fn write_frame() {
-        let target = "CONNECT\nContent-Length: 30\nContent-Type: application/json\n\n\0";
-        let input = stdio::empty();
+        let target = "CONNECT\nContent-Type:application/json\nContent-Length:0\n\n\0";
+        let input = Cursor::new(b"\0");
         let ref_input = Rc::new(RefCell::new(input));
-        let mut body = BodyBuilder::new(ref_input);
-        body = body.content_length(30);
-
         let gate = Gate::new();
-        let guard = gate.latch();
+        let guard = gate.latch().unwrap();
+        let mut body = BodyBuilder::new(ref_input).guard(guard);
+        body = body.content_length(0);
+
         let mut header = Header::new();
         header.push("Content-Type", "application/json".to_owned());
-        header.push("Content-Length", "30".to_owned());
+        header.push("Content-Length", "0".to_owned());
 
-        let mut frame = Frame::with_guard(Command::Connect, header, body.build(), guard);
+        let mut frame = Frame::new(Command::Connect, header, body.build());
         let mut buffer: Vec<u8> = Vec::new();
         frame.write_to(&mut buffer).unwrap();
         let data = str::from_utf8(&buffer).unwrap();
@@ -470,25 +1497,661 @@ mod test {
 
     #[test]
     fn write_frame_with_body() {
-        let target = "CONNECT\nContent-Length: 30\nContent-Type: application/json\n\n{\"name\":\"Joshua\"}\0";
-        let input = Cursor::new(b"{\"name\":\"Joshua\"}");
+        let target =
+            "CONNECT\nContent-Type:application/json\nContent-Length:17\n\n{\"name\":\"Joshua\"}\0";
+        let input = Cursor::new(b"{\"name\":\"Joshua\"}\0");
         let ref_input = Rc::new(RefCell::new(input));
-        let mut body = BodyBuilder::new(ref_input);
-        body = body.content_length(30);
-
         let gate = Gate::new();
-        let guard = gate.latch();
+        let guard = gate.latch().unwrap();
+        let mut body = BodyBuilder::new(ref_input).guard(guard);
+        body = body.content_length(17);
+
         let mut header = Header::new();
         header.push("Content-Type", "application/json".to_owned());
-        header.push("Content-Length", "30".to_owned());
+        header.push("Content-Length", "17".to_owned());
 
-        let mut frame = Frame::with_guard(Command::Connect, header, body.build(), guard);
+        let mut frame = Frame::new(Command::Connect, header, body.build());
         let mut buffer: Vec<u8> = Vec::new();
         frame.write_to(&mut buffer).unwrap();
         let data = str::from_utf8(&buffer).unwrap();
         assert_eq!(target, data)
     }
 
+    struct CountingWriter {
+        writes: usize,
+        data: Vec<u8>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> stdio::Result<usize> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> stdio::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_small_frame_single_write() {
+        let input = b"SEND\ndestination:/queue/a\n\nhello\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = frame_reader.read_frame().unwrap();
+
+        let mut writer = CountingWriter {
+            writes: 0,
+            data: Vec::new(),
+        };
+        let bytes_written = frame.write_to(&mut writer).unwrap();
+        assert_eq!(1, writer.writes);
+        assert_eq!(writer.data.len() as u64, bytes_written);
+        assert!(writer
+            .data
+            .starts_with(b"SEND\ndestination:/queue/a\n\nhello"));
+    }
+
+    #[test]
+    fn write_large_frame() {
+        let body = vec![b'a'; SMALL_FRAME_SIZE * 2];
+        let mut input = b"SEND\n\n".to_vec();
+        input.extend_from_slice(&body);
+        input.push(NULL);
+        let frame_reader = FrameReader::new(Cursor::new(input));
+        let mut frame = frame_reader.read_frame().unwrap();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let bytes_written = frame.write_to(&mut buffer).unwrap();
+        assert_eq!(buffer.len() as u64, bytes_written);
+        assert!(buffer.starts_with(b"SEND\n\n"));
+        assert_eq!(&body[..], &buffer[6..6 + body.len()]);
+        assert_eq!(Some(&NULL), buffer.last());
+    }
+
+    #[test]
+    fn frame_writer_inserts_content_length() {
+        let mut frame = Frame::new(Command::Send, Header::new(), Body::new(&b"a\0b"[..]));
+        let mut frame_writer = FrameWriter::new(Vec::new());
+        frame_writer.write_frame(&mut frame).unwrap();
+
+        let data = frame_writer.into_inner();
+        assert_eq!(b"SEND\ncontent-length:3\n\na\0b\0".to_vec(), data);
+    }
+
+    #[test]
+    fn frame_writer_keeps_content_length() {
+        let mut header = Header::new();
+        header.push("content-length", "5".to_owned());
+        let mut frame = Frame::new(Command::Send, header, Body::new(&b"hello"[..]));
+        let mut frame_writer = FrameWriter::new(Vec::new());
+        frame_writer.write_frame(&mut frame).unwrap();
+
+        let mut frame = Frame::new(Command::Send, Header::new(), Body::empty());
+        frame_writer.write_frame(&mut frame).unwrap();
+
+        let data = frame_writer.into_inner();
+        let target = b"SEND\ncontent-length:5\n\nhello\0SEND\ncontent-length:0\n\n\0";
+        assert_eq!(target.to_vec(), data);
+    }
+
+    #[test]
+    fn frame_writer_content_length_modes() {
+        let frame = |length: Option<&str>, body: &'static [u8]| {
+            let mut header = Header::new();
+
+            if let Some(length) = length {
+                header.push("content-length", length.to_owned());
+            }
+            Frame::new(Command::Send, header, Body::new(body))
+        };
+        let mut frame_writer = FrameWriter::new(Vec::new());
+        assert_eq!(ContentLengthMode::Missing, frame_writer.content_length());
+
+        frame_writer.set_content_length(ContentLengthMode::Always);
+        frame_writer
+            .write_frame(&mut frame(Some("9"), b"hello"))
+            .unwrap();
+        frame_writer.write_frame(&mut frame(None, b"a\0b")).unwrap();
+
+        frame_writer.set_content_length(ContentLengthMode::Never);
+        frame_writer.write_frame(&mut frame(None, b"hi")).unwrap();
+        frame_writer
+            .write_frame(&mut frame(Some("3"), b"a\0b"))
+            .unwrap();
+        let error = frame_writer
+            .write_frame(&mut frame(None, b"a\0b"))
+            .unwrap_err();
+        assert_eq!(stdio::ErrorKind::InvalidInput, error.kind());
+
+        let data = frame_writer.into_inner();
+        let target = b"SEND\ncontent-length:5\n\nhello\0SEND\ncontent-length:3\n\na\0b\0\
+            SEND\n\nhi\0SEND\ncontent-length:3\n\na\0b\0";
+        assert_eq!(target.to_vec(), data);
+    }
+
+    #[test]
+    fn content_length_ignores_case() {
+        let input = b"SEND\nContent-Length:3\n\na\0b\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(b"a\0b".to_vec(), frame.into_owned().unwrap().body);
+
+        let frame_reader = SyncFrameReader::new(Cursor::new(&input[..]));
+        assert_eq!(b"a\0b".to_vec(), frame_reader.read_frame().unwrap().body);
+
+        let mut frame_writer = FrameWriter::new(Vec::new());
+        let mut header = Header::new();
+        header.push("Content-Length", "3".to_owned());
+        let mut frame = Frame::new(Command::Send, header.clone(), Body::new(&b"a\0b"[..]));
+        frame_writer.write_frame(&mut frame).unwrap();
+
+        frame_writer.set_content_length(ContentLengthMode::Always);
+        let mut frame = Frame::new(Command::Send, header, Body::new(&b"hi"[..]));
+        frame_writer.write_frame(&mut frame).unwrap();
+
+        let target = b"SEND\nContent-Length:3\n\na\0b\0SEND\ncontent-length:2\n\nhi\0";
+        assert_eq!(target.to_vec(), frame_writer.into_inner());
+    }
+
+    #[test]
+    fn reader_position_and_state() {
+        let input = b"SEND\n\nhello\0MESSAGE\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        assert_eq!(ReaderState::Ready, frame_reader.state());
+        assert_eq!(0, frame_reader.position());
+
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(ReaderState::Body, frame_reader.state());
+        assert_eq!(6, frame_reader.position());
+
+        drop(frame);
+        assert_eq!(ReaderState::Ready, frame_reader.state());
+        assert_eq!(12, frame_reader.position());
+    }
+
+    #[test]
+    fn reader_resume_from_position() {
+        let input = b"SEND\n\nhello\0MESSAGE\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        drop(frame_reader.read_frame().unwrap());
+        let position = frame_reader.position();
+
+        let mut cursor = Cursor::new(&input[..]);
+        cursor.set_position(position);
+        let frame_reader = FrameReader::with_position(cursor, position);
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(Command::Message, frame.command);
+        drop(frame);
+        assert_eq!(input.len() as u64, frame_reader.position());
+    }
+
+    #[test]
+    fn frames() {
+        let input = b"\nSEND\n\na\0\n\r\n\nMESSAGE\n\nb\0\n";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let frames: Vec<OwnedFrame> = frame_reader.frames().map(|f| f.unwrap()).collect();
+
+        assert_eq!(2, frames.len());
+        assert_eq!(Command::Send, frames[0].command);
+        assert_eq!(b"a".to_vec(), frames[0].body);
+        assert_eq!(Command::Message, frames[1].command);
+        assert_eq!(b"b".to_vec(), frames[1].body);
+    }
+
+    #[test]
+    fn frames_stop_after_error() {
+        let input = b"BOGUS\n\n\0SEND\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frames = frame_reader.frames();
+
+        assert!(frames.next().unwrap().is_err());
+        assert!(frames.next().is_none());
+    }
+
+    #[test]
+    fn reader_version() {
+        let input = b"MESSAGE\r\na:b\\c\\r\r\n\r\n\0MESSAGE\na:b\\c\\r\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(vec!["b:\r".to_owned()], frame.header["a"]);
+        drop(frame);
+
+        frame_reader.set_version(Version::V1_0);
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(vec!["b\\c\\r".to_owned()], frame.header["a"]);
+    }
+
+    #[test]
+    fn writer_version() {
+        let mut header = Header::new();
+        header.push("a", "b:c".to_owned());
+        let mut frame = Frame::new(Command::Send, header, Body::empty());
+
+        let mut writer = FrameWriter::new(Vec::new());
+        writer.set_version(Version::V1_0);
+        writer.write_frame(&mut frame).unwrap();
+        assert_eq!(
+            b"SEND\na:b:c\ncontent-length:0\n\n\0".to_vec(),
+            writer.into_inner()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut header = Header::new();
+        header.push("destination", "/queue/a".to_owned());
+        header.push("Reply-To", "a:b\\c\nd ".to_owned());
+        header.push("x", "1".to_owned());
+        header.push("x", "".to_owned());
+        header.push("content-length", "5".to_owned());
+        let frames = vec![
+            OwnedFrame::new(Command::Send, header, b"he\0lo".to_vec()),
+            OwnedFrame::new(Command::Ack, Header::new(), Vec::new()),
+        ];
+
+        let mut writer = FrameWriter::new(Vec::new());
+
+        for frame in &frames {
+            writer.write_frame(&mut frame.as_frame()).unwrap();
+        }
+        let data = writer.into_inner();
+        let reader = FrameReader::with_config(&data[..], ParserConfig::strict());
+
+        for frame in &frames {
+            let mut read = frame.clone();
+            read.header
+                .insert(CONTENT_LENGTH.to_owned(), vec![read.body.len().to_string()]);
+            assert_eq!(read, reader.read_frame().unwrap().into_owned().unwrap());
+        }
+    }
+
+    #[test]
+    fn writer_pad_values() {
+        let mut header = Header::new();
+        header.push("a", "b".to_owned());
+        let mut writer = FrameWriter::new(Vec::new());
+        assert!(!writer.pads_values());
+        writer.set_pad_values(true);
+        writer
+            .write_frame(&mut Frame::new(Command::Send, header, Body::empty()))
+            .unwrap();
+        let data = writer.into_inner();
+        assert_eq!(b"SEND\na: b\ncontent-length: 0\n\n\0".to_vec(), data);
+
+        let reader = FrameReader::new(&data[..]);
+        assert_eq!(Some("b"), reader.read_frame().unwrap().header.first("a"));
+        assert!(FrameReader::with_config(&data[..], ParserConfig::strict())
+            .read_frame()
+            .is_err());
+    }
+
+    #[test]
+    fn strict_parsing() {
+        let inputs: [&[u8]; 4] = [
+            b"SEND\na:b\\t\n\n\0",
+            b"SEND\n a:b\n\n\0",
+            b"SEND\na: b\n\n\0",
+            b"SEND\ncontent-length:0\ncontent-length:0\n\n\0",
+        ];
+
+        for input in inputs.iter() {
+            let lenient = FrameReader::new(Cursor::new(*input));
+            assert!(lenient.read_frame().is_ok());
+
+            let strict = FrameReader::with_config(Cursor::new(*input), ParserConfig::strict());
+            assert!(strict.config().is_strict());
+            assert!(strict.read_frame().is_err());
+        }
+
+        let input = b"SEND\na:b\\c\ncontent-length:0\n\n\0";
+        let strict = FrameReader::with_config(Cursor::new(&input[..]), ParserConfig::strict());
+        let frame = strict.read_frame().unwrap();
+        assert_eq!(vec!["b:".to_owned()], frame.header["a"]);
+    }
+
+    #[test]
+    fn null_in_header() {
+        let input = b"SEND\na:b\0\nMESSAGE\n\nhello\0";
+
+        let lenient = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = lenient.read_frame().unwrap();
+        assert_eq!(Command::Message, frame.command);
+        let mut body = Vec::new();
+        frame.body.read_to_end(&mut body).unwrap();
+        assert_eq!(b"hello".to_vec(), body);
+
+        let strict = FrameReader::with_config(Cursor::new(&input[..]), ParserConfig::strict());
+        let result = strict.read_frame();
+        assert!(matches!(result, Err(ReadError::NullInHeader)));
+        assert_eq!(Command::Message, strict.read_frame().unwrap().command);
+
+        let lenient = SyncFrameReader::new(Cursor::new(&input[..]));
+        assert_eq!(Command::Message, lenient.read_frame().unwrap().command);
+    }
+
+    #[test]
+    fn parser_events() {
+        let input = b"SEND\na:b\\t\n a :c\ncontent-length:1\n\nxjunk\0SEND\nb:\0\nMESSAGE\n\n\0";
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let recorder = seen.clone();
+
+        let mut frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        frame_reader.set_events(move |event: &ParserEvent<'_>| {
+            RefCell::borrow_mut(&recorder).push(format!("{:?}", event));
+        });
+        drop(frame_reader.read_frame().unwrap());
+        assert_eq!(Command::Message, frame_reader.read_frame().unwrap().command);
+
+        assert_eq!(
+            vec![
+                "InvalidEscape(\"b\\\\t\")",
+                "UntrimmedWhitespace { header: \"a\" }",
+                "RepeatedHeader { header: \"a\" }",
+                "TrailingBytes { content_length: 1, discarded: 4 }",
+                "TruncatedFrame",
+            ],
+            *RefCell::borrow(&seen)
+        );
+    }
+
+    #[test]
+    fn binary_body() {
+        let body: Vec<u8> = (0..=255).chain(0..=255).collect();
+        let mut input = format!("SEND\ncontent-length:{}\n\n", body.len()).into_bytes();
+        input.extend_from_slice(&body);
+        input.extend_from_slice(b"\0\nMESSAGE\n\n\0");
+
+        for config in [ParserConfig::lenient(), ParserConfig::strict()].iter() {
+            let reader = FrameReader::with_config(Cursor::new(&input[..]), *config);
+            let frame = reader.read_frame().unwrap().into_owned().unwrap();
+            assert_eq!(body, frame.body);
+            assert_eq!(Command::Message, reader.read_frame().unwrap().command);
+
+            let reader = SyncFrameReader::new(Cursor::new(&input[..])).with_config(*config);
+            assert_eq!(body, reader.read_frame().unwrap().body);
+            assert_eq!(Command::Message, reader.read_frame().unwrap().command);
+        }
+    }
+
+    #[test]
+    fn content_length_trailing_bytes() {
+        let input = b"SEND\ncontent-length:2\n\nhello\0MESSAGE\n\n\0";
+
+        let lenient = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = lenient.read_frame().unwrap();
+        let mut body = Vec::new();
+        frame.body.read_to_end(&mut body).unwrap();
+        assert_eq!(b"he".to_vec(), body);
+        drop(frame);
+        assert_eq!(Command::Message, lenient.read_frame().unwrap().command);
+
+        let strict = FrameReader::with_config(Cursor::new(&input[..]), ParserConfig::strict());
+        let mut frame = strict.read_frame().unwrap();
+        let error = frame.body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            ReadError::from(error),
+            ReadError::ContentLengthMismatch {
+                content_length: 2,
+                trailing: 3
+            }
+        ));
+        drop(frame);
+        assert_eq!(Command::Message, strict.read_frame().unwrap().command);
+
+        let strict =
+            SyncFrameReader::new(Cursor::new(&input[..])).with_config(ParserConfig::strict());
+        let result = strict.read_frame();
+        assert!(matches!(
+            result,
+            Err(ReadError::ContentLengthMismatch { .. })
+        ));
+        assert_eq!(Command::Message, strict.read_frame().unwrap().command);
+    }
+
+    #[test]
+    fn content_length_unterminated() {
+        let input = b"SEND\ncontent-length:5\n\nhel";
+        let reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = reader.read_frame().unwrap();
+        let error = frame.body.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            ReadError::from(error),
+            ReadError::UnterminatedBody {
+                content_length: 5,
+                received: 3
+            }
+        ));
+
+        let strict = SyncFrameReader::new(Cursor::new(&b"SEND\ncontent-length:3\n\nhel"[..]))
+            .with_config(ParserConfig::strict());
+        assert!(matches!(
+            strict.read_frame(),
+            Err(ReadError::UnterminatedBody { received: 3, .. })
+        ));
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn timings() {
+        let input = b"SEND\na:b\n\nhello\0SEND\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        assert_eq!(ParseTimings::default(), frame_reader.timings());
+
+        let mut frame = frame_reader.read_frame().unwrap();
+        frame.body.read_to_end(&mut Vec::new()).unwrap();
+        drop(frame);
+        drop(frame_reader.read_frame().unwrap());
+
+        let timings = frame_reader.timings();
+        assert_eq!(2, timings.frames);
+        assert!(timings.header > std::time::Duration::from_secs(0));
+        assert!(timings.body > std::time::Duration::from_secs(0));
+    }
+
+    #[test]
+    fn command_from_bytes() {
+        let commands = [
+            Command::Connect,
+            Command::Stomp,
+            Command::Connected,
+            Command::Send,
+            Command::Subscribe,
+            Command::Unsubscribe,
+            Command::Ack,
+            Command::Nack,
+            Command::Begin,
+            Command::Commit,
+            Command::Abort,
+            Command::Disconnect,
+            Command::Message,
+            Command::Receipt,
+            Command::Error,
+        ];
+
+        for command in commands.iter() {
+            assert_eq!(
+                Some(*command),
+                Command::from_bytes(command.as_str().as_bytes())
+            );
+            assert_eq!(*command, Command::from_str(command.as_str()).unwrap());
+        }
+        assert_eq!(None, Command::from_bytes(b"send"));
+    }
+
+    #[test]
+    fn read_command() {
+        let mut reader = BufReader::new(Cursor::new(&b"SEND\r\nrest"[..]));
+        assert_eq!(
+            Command::Send,
+            Frame::read_command(&mut reader, 1024).unwrap()
+        );
+        assert_eq!(b"rest", reader.fill_buf().unwrap());
+
+        let mut reader = BufReader::with_capacity(2, Cursor::new(&b"MESSAGE\n"[..]));
+        assert_eq!(
+            Command::Message,
+            Frame::read_command(&mut reader, 1024).unwrap()
+        );
+
+        let mut reader = BufReader::new(Cursor::new(&b"BOGUS\n"[..]));
+        assert!(matches!(
+            Frame::read_command(&mut reader, 1024),
+            Err(ReadError::InvalidCommand { got }) if got == "BOGUS"
+        ));
+    }
+
+    #[test]
+    fn read_error_kinds() {
+        let read = |input: &[u8], config| {
+            let frame_reader = FrameReader::with_config(Cursor::new(input.to_vec()), config);
+            let result = frame_reader.read_frame().map(|_| ());
+            result.unwrap_err()
+        };
+        let lenient = ParserConfig::lenient();
+        let strict = ParserConfig::strict();
+
+        assert!(matches!(read(b"\n", lenient), ReadError::EmptyCommand));
+        assert!(matches!(
+            read(b"SEND\na:1\nb\n\n\0", lenient),
+            ReadError::MalformedHeaderLine { line_no: 2 }
+        ));
+        assert!(matches!(
+            read(b"SEND\n:1\n\n\0", lenient),
+            ReadError::EmptyHeaderName
+        ));
+        assert!(matches!(
+            read(b"SEND\na:\\t\n\n\0", strict),
+            ReadError::InvalidEscapeSequence { sequence } if sequence == "\\t"
+        ));
+        assert!(matches!(
+            read(b"SEND\na: 1\n\n\0", strict),
+            ReadError::UntrimmedWhitespace { header } if header == "a"
+        ));
+        assert!(matches!(
+            read(b"SEND\ncontent-length:1\ncontent-length:1\n\na\0", strict),
+            ReadError::RepeatedContentLength
+        ));
+
+        let e = read(b"SEND\ncontent-length:x\n\n\0", lenient);
+        assert!(matches!(&e, ReadError::InvalidContentLength { value, .. } if value == "x"));
+        assert!(Error::source(&e).is_some());
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_command: 8,
+            max_header: 32,
+            max_headers_count: 2,
+            max_body: 4,
+        };
+        let exceeded = |input: &[u8]| {
+            let mut frame_reader = FrameReader::new(Cursor::new(input.to_vec()));
+            frame_reader.set_limits(limits);
+
+            match frame_reader.read_frame().map(|_| ()) {
+                Err(ReadError::LimitExceeded { kind, limit }) => Some((kind, limit)),
+                _ => None,
+            }
+        };
+
+        assert_eq!(Some((LimitKind::Command, 8)), exceeded(b"SUBSCRIBE\n\n\0"));
+        assert_eq!(
+            Some((LimitKind::Header, 32)),
+            exceeded(b"SEND\ndestination:/queue/a-longer-name\n\n\0")
+        );
+        assert_eq!(
+            Some((LimitKind::Header, 32)),
+            exceeded(b"SEND\naaaaaaaaaa:1\nbbbbbbbbbb:2\ncccc:3\n\n\0")
+        );
+        assert_eq!(
+            Some((LimitKind::HeadersCount, 2)),
+            exceeded(b"SEND\na:1\nb:2\nc:3\n\n\0")
+        );
+        assert_eq!(
+            Some((LimitKind::Body, 4)),
+            exceeded(b"SEND\ncontent-length:5\n\nhello\0")
+        );
+        assert_eq!(None, exceeded(b"SEND\na:1\ncontent-length:4\n\nabcd\0"));
+    }
+
+    #[test]
+    fn body_too_large() {
+        let input = b"SEND\n\nabcd\0SEND\n\nhello\0";
+        let mut frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        frame_reader.set_limits(Limits {
+            max_body: 4,
+            ..Limits::default()
+        });
+
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(b"abcd".to_vec(), frame.into_owned().unwrap().body);
+
+        let frame = frame_reader.read_frame().unwrap();
+        let result = frame.into_owned().map_err(ReadError::from);
+        assert!(matches!(result, Err(ReadError::BodyTooLarge { limit: 4 })));
+    }
+
+    #[test]
+    fn frame_into_parts() {
+        let input = b"SEND\ndestination:/queue/a\n\nhello\0SEND\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let frame = frame_reader.read_frame().unwrap();
+        let (command, mut header, body) = frame.into_parts();
+
+        assert_eq!(Command::Send, command);
+        let result = frame_reader.read_frame();
+        assert!(matches!(result, Err(ReadError::PreviousBodyUnfinished)));
+
+        header.push("x-forwarded", "true".to_owned());
+        let mut frame = Frame::from_parts((command, header, body));
+        let mut buffer: Vec<u8> = Vec::new();
+        frame.write_to(&mut buffer).unwrap();
+        let data = str::from_utf8(&buffer).unwrap();
+        assert!(data.starts_with("SEND\ndestination:/queue/a\nx-forwarded:true\n\nhello"));
+
+        drop(frame);
+        frame_reader.read_frame().unwrap();
+    }
+
+    #[test]
+    fn read_frame_body_excludes_null() {
+        let input = b"SEND\n\nhello\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = frame_reader.read_frame().unwrap();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        Read::read_to_end(&mut frame.body, &mut buffer).unwrap();
+        assert_eq!(b"hello".to_vec(), buffer);
+    }
+
+    #[test]
+    fn body_map_reader() {
+        let input = b"SEND\n\nhello\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = frame_reader.read_frame().unwrap();
+        frame.body = frame.body.map_reader(|r| r.take(4));
+
+        let mut buffer: Vec<u8> = Vec::new();
+        Read::read_to_end(&mut frame.body, &mut buffer).unwrap();
+        assert_eq!(b"hell".to_vec(), buffer);
+    }
+
+    #[test]
+    fn body_map_reader_close_consumes_remainder() {
+        let input = b"SEND\n\nhello\0MESSAGE\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let mut frame = frame_reader.read_frame().unwrap();
+        frame.body = frame.body.map_reader(|r| r.take(1));
+        frame.body.close().unwrap();
+        drop(frame);
+
+        let frame = frame_reader.read_frame().unwrap();
+        assert_eq!(Command::Message, frame.command);
+    }
+
     /*
 
     #[test]
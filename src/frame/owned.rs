@@ -0,0 +1,68 @@
+use crate::frame::{Body, Command, Frame, Header};
+use std::io;
+use std::io::Read;
+
+/// A frame with its body read into memory. Unlike `Frame`, it does not borrow the reader it
+/// came from, so it can be stored or sent to another thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedFrame {
+    pub command: Command,
+    pub header: Header,
+    pub body: Vec<u8>,
+}
+
+impl OwnedFrame {
+    pub fn new(command: Command, header: Header, body: Vec<u8>) -> Self {
+        OwnedFrame {
+            command,
+            header,
+            body,
+        }
+    }
+
+    /// Borrows the frame as a `Frame`, for example to write it with `Frame::write_to`.
+    pub fn as_frame(&self) -> Frame<'_> {
+        Frame::new(self.command, self.header.clone(), Body::new(&self.body[..]))
+    }
+}
+
+impl<'a> Frame<'a> {
+    /// Reads the rest of the body into memory, releasing the reader for the next frame.
+    pub fn into_owned(self) -> io::Result<OwnedFrame> {
+        let (command, header, mut body) = self.into_parts();
+        let mut buffer: Vec<u8> = Vec::new();
+        body.read_to_end(&mut buffer)?;
+        Ok(OwnedFrame::new(command, header, buffer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::FrameReader;
+    use std::io::Cursor;
+    use std::thread;
+
+    #[test]
+    fn into_owned() {
+        let input = b"SEND\ndestination:/queue/a\n\nhello\0MESSAGE\n\n\0";
+        let frame_reader = FrameReader::new(Cursor::new(&input[..]));
+        let owned = frame_reader.read_frame().unwrap().into_owned().unwrap();
+
+        let next = frame_reader.read_frame().unwrap();
+        assert_eq!(Command::Message, next.command);
+
+        let owned = thread::spawn(move || owned).join().unwrap();
+        assert_eq!(Command::Send, owned.command);
+        assert_eq!(vec!["/queue/a".to_owned()], owned.header["destination"]);
+        assert_eq!(b"hello".to_vec(), owned.body);
+    }
+
+    #[test]
+    fn as_frame() {
+        let owned = OwnedFrame::new(Command::Send, Header::new(), b"hello".to_vec());
+        let mut buffer: Vec<u8> = Vec::new();
+        owned.as_frame().write_to(&mut buffer).unwrap();
+        assert_eq!(b"SEND\n\nhello\0".to_vec(), buffer);
+    }
+}
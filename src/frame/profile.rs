@@ -0,0 +1,68 @@
+use std::cell::Cell;
+use std::io;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Time spent in each phase of parsing, summed over the frames read by a `FrameReader`. Body
+/// time covers reads through `Frame::body`, not the draining of unread bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseTimings {
+    pub frames: u64,
+    pub command: Duration,
+    pub header: Duration,
+    pub body: Duration,
+}
+
+#[derive(Default)]
+pub(super) struct Profile {
+    frames: Cell<u64>,
+    command: Cell<Duration>,
+    header: Cell<Duration>,
+    body: Cell<Duration>,
+}
+
+impl Profile {
+    pub(super) fn timings(&self) -> ParseTimings {
+        ParseTimings {
+            frames: self.frames.get(),
+            command: self.command.get(),
+            header: self.header.get(),
+            body: self.body.get(),
+        }
+    }
+
+    pub(super) fn command<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.frames.set(self.frames.get() + 1);
+        time(&self.command, f)
+    }
+
+    pub(super) fn header<T>(&self, f: impl FnOnce() -> T) -> T {
+        time(&self.header, f)
+    }
+
+    pub(super) fn body<R: Read>(&self, reader: R) -> TimedReader<'_, R> {
+        TimedReader {
+            inner: reader,
+            total: &self.body,
+        }
+    }
+}
+
+fn time<T>(total: &Cell<Duration>, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    total.set(total.get() + started.elapsed());
+    result
+}
+
+pub(super) struct TimedReader<'a, R: Read> {
+    inner: R,
+    total: &'a Cell<Duration>,
+}
+
+impl<'a, R: Read> Read for TimedReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        time(self.total, || inner.read(buf))
+    }
+}
@@ -0,0 +1,206 @@
+use crate::frame::headers::CONTENT_LENGTH;
+use crate::frame::{Command, EOL, NULL};
+use std::io;
+use std::io::{BufRead, Read};
+use std::str;
+use std::str::FromStr;
+
+/// A complete frame found by `FrameSplitter`, with its offset in the input stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitFrame {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Splits a byte stream of concatenated frames, such as a payload extracted from a packet
+/// capture, into complete frames. Bytes that cannot start a frame are skipped until a line
+/// holding a known command is found, and frames cut off by the end of the stream are dropped.
+pub struct FrameSplitter<R: BufRead> {
+    reader: R,
+    position: u64,
+    pending: Option<(u64, Vec<u8>)>,
+}
+
+impl<R: BufRead> FrameSplitter<R> {
+    pub fn new(reader: R) -> Self {
+        FrameSplitter {
+            reader,
+            position: 0,
+            pending: None,
+        }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        let offset = self.position;
+        let mut line: Vec<u8> = Vec::new();
+        let bytes_read = self.reader.read_until(EOL, &mut line)?;
+        self.position += bytes_read as u64;
+
+        if bytes_read < 1 {
+            return Ok(None);
+        }
+        Ok(Some((offset, line)))
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<SplitFrame>> {
+        'scan: loop {
+            let (offset, line) = match self.next_line()? {
+                Some(l) => l,
+                None => return Ok(None),
+            };
+            let start = memchr::memrchr(NULL, &line).map(|i| i + 1).unwrap_or(0);
+
+            if !is_command(&line[start..]) {
+                continue;
+            }
+            let mut bytes = line[start..].to_vec();
+            let mut content_length: Option<u64> = None;
+
+            loop {
+                let (line_offset, line) = match self.next_line()? {
+                    Some(l) => l,
+                    None => return Ok(None),
+                };
+
+                if trim_eol(&line).is_empty() {
+                    bytes.extend_from_slice(&line);
+                    break;
+                }
+
+                if line.contains(&NULL) || !line.contains(&b':') {
+                    self.pending = Some((line_offset, line));
+                    continue 'scan;
+                }
+
+                if content_length.is_none() {
+                    content_length = parse_content_length(&line);
+                }
+                bytes.extend_from_slice(&line);
+            }
+
+            match content_length {
+                Some(n) => {
+                    let body_start = bytes.len();
+                    let body_offset = self.position;
+                    let bytes_read = (&mut self.reader).take(n + 1).read_to_end(&mut bytes)?;
+                    self.position += bytes_read as u64;
+
+                    if (bytes_read as u64) < n + 1 {
+                        return Ok(None);
+                    }
+
+                    if bytes.last() != Some(&NULL) {
+                        self.pending = Some((body_offset, bytes.split_off(body_start)));
+                        continue;
+                    }
+                }
+                None => {
+                    let bytes_read = self.reader.read_until(NULL, &mut bytes)?;
+                    self.position += bytes_read as u64;
+
+                    if bytes.last() != Some(&NULL) {
+                        return Ok(None);
+                    }
+                }
+            }
+            return Ok(Some(SplitFrame {
+                offset: offset + start as u64,
+                bytes,
+            }));
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for FrameSplitter<R> {
+    type Item = io::Result<SplitFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(&[EOL]).unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn is_command(line: &[u8]) -> bool {
+    str::from_utf8(trim_eol(line))
+        .map(|s| Command::from_str(s).is_ok())
+        .unwrap_or(false)
+}
+
+fn parse_content_length(line: &[u8]) -> Option<u64> {
+    let line = str::from_utf8(trim_eol(line)).ok()?;
+    let mut parts = line.splitn(2, ':');
+    let name = parts.next()?;
+
+    if !name.trim().eq_ignore_ascii_case(CONTENT_LENGTH) {
+        return None;
+    }
+    parts.next()?.trim().parse::<u64>().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn split(input: &[u8]) -> Vec<SplitFrame> {
+        FrameSplitter::new(Cursor::new(input))
+            .collect::<io::Result<Vec<SplitFrame>>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn split_frames() {
+        let input = b"SEND\ndestination:/queue/a\n\nhello\0\n\nMESSAGE\n\n\0";
+        let frames = split(input);
+        assert_eq!(2, frames.len());
+        assert_eq!(0, frames[0].offset);
+        assert_eq!(
+            b"SEND\ndestination:/queue/a\n\nhello\0".to_vec(),
+            frames[0].bytes
+        );
+        assert_eq!(35, frames[1].offset);
+        assert_eq!(b"MESSAGE\n\n\0".to_vec(), frames[1].bytes);
+    }
+
+    #[test]
+    fn split_leading_garbage() {
+        let input = b"}\"tail of a body\0MESSAGE\n\nbody\0";
+        let frames = split(input);
+        assert_eq!(1, frames.len());
+        assert_eq!(17, frames[0].offset);
+        assert_eq!(b"MESSAGE\n\nbody\0".to_vec(), frames[0].bytes);
+    }
+
+    #[test]
+    fn split_garbage_header() {
+        let input = b"SEND\nnot a header\nSEND\n\nbody\0";
+        let frames = split(input);
+        assert_eq!(1, frames.len());
+        assert_eq!(18, frames[0].offset);
+    }
+
+    #[test]
+    fn split_content_length_with_null() {
+        let input = b"SEND\ncontent-length:3\n\na\0b\0MESSAGE\n\n\0";
+        let frames = split(input);
+        assert_eq!(2, frames.len());
+        assert_eq!(
+            b"SEND\ncontent-length:3\n\na\0b\0".to_vec(),
+            frames[0].bytes
+        );
+    }
+
+    #[test]
+    fn split_truncated() {
+        let input = b"SEND\n\nbody\0MESSAGE\n\nunfinished";
+        let frames = split(input);
+        assert_eq!(1, frames.len());
+    }
+}
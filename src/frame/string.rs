@@ -1,50 +1,89 @@
-const NULL: char = '\0';
+use crate::frame::error::ReadError;
+use crate::frame::Version;
+use std::borrow::Cow;
+
 const BACKSLASH: char = '\\';
-const NEWLINE: char = '\n';
-const CARRIAGE_RETURN: char = '\r';
-const COLON: char = ':';
 
-pub fn encode(input: &str) -> String {
-    let mut output = String::with_capacity(input.len());
+pub fn encode_into(input: &str, version: Version, output: &mut Vec<u8>) {
+    if !version.escapes_headers() {
+        output.extend_from_slice(input.as_bytes());
+        return;
+    }
 
-    for c in input.chars() {
-        match c {
-            BACKSLASH => output.push_str("\\\\"),
-            CARRIAGE_RETURN => output.push_str("\\r"),
-            NEWLINE => output.push_str("\\n"),
-            COLON => output.push_str("\\c"),
+    for b in input.bytes() {
+        match b {
+            b'\\' => output.extend_from_slice(b"\\\\"),
+            b'\r' if version.allows_crlf() => output.extend_from_slice(b"\\r"),
+            b'\n' => output.extend_from_slice(b"\\n"),
+            b':' => output.extend_from_slice(b"\\c"),
             a => output.push(a),
         }
     }
-    output
 }
 
-pub fn decode(input: &str) -> String {
+/// Decodes escape sequences leniently: an unknown escape yields the escaped character and a
+/// trailing backslash is dropped. Input without escapes is returned without allocating.
+pub fn decode(input: &str, version: Version) -> Cow<'_, str> {
+    match unescape(input, version, false) {
+        Ok(output) => output,
+        Err(_) => unreachable!("lenient decoding does not fail"),
+    }
+}
+
+/// Decodes escape sequences, rejecting any sequence the protocol version does not define.
+pub fn decode_strict(input: &str, version: Version) -> Result<Cow<'_, str>, ReadError> {
+    unescape(input, version, true)
+}
+
+fn unescape(input: &str, version: Version, strict: bool) -> Result<Cow<'_, str>, ReadError> {
+    if !version.escapes_headers() {
+        return Ok(Cow::Borrowed(input));
+    }
+    let start = match input.find(BACKSLASH) {
+        Some(i) => i,
+        None => return Ok(Cow::Borrowed(input)),
+    };
     let mut output = String::with_capacity(input.len());
-    let mut last_char = NULL;
-
-    for c in input.chars() {
-        match c {
-            'c' if last_char == BACKSLASH => output.push(':'),
-            'n' if last_char == BACKSLASH => output.push('\n'),
-            'r' if last_char == BACKSLASH => output.push('\r'),
-            BACKSLASH if last_char == BACKSLASH => output.push('\\'),
-            BACKSLASH => (),
-            a => output.push(a),
+    output.push_str(&input[..start]);
+    let mut chars = input[start..].chars();
+
+    while let Some(c) = chars.next() {
+        if c != BACKSLASH {
+            output.push(c);
+            continue;
         }
-        last_char = if last_char == BACKSLASH && c == BACKSLASH {
-            NULL
-        } else {
-            c
+        match chars.next() {
+            Some('c') => output.push(':'),
+            Some('n') => output.push('\n'),
+            Some('r') if version.allows_crlf() => output.push('\r'),
+            Some(BACKSLASH) => output.push(BACKSLASH),
+            Some(a) if strict => {
+                return Err(ReadError::InvalidEscapeSequence {
+                    sequence: format!("\\{}", a),
+                })
+            }
+            Some(a) => output.push(a),
+            None if strict => {
+                return Err(ReadError::InvalidEscapeSequence {
+                    sequence: BACKSLASH.to_string(),
+                })
+            }
+            None => (),
         }
     }
-    output
+    Ok(Cow::Owned(output))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn encode(input: &str) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        encode_into(input, Version::V1_2, &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
     #[test]
     fn encode_backslash() {
         let input = "Hello\\World";
@@ -77,34 +116,78 @@ mod test {
     fn decode_backslash() {
         let input = "Hello\\\\World";
         let target = "Hello\\World";
-        assert_eq!(target, decode(input))
+        assert_eq!(target, decode(input, Version::V1_2))
     }
 
     #[test]
     fn decode_newline() {
         let input = "Hello\\nWorld";
         let target = "Hello\nWorld";
-        assert_eq!(target, decode(input))
+        assert_eq!(target, decode(input, Version::V1_2))
     }
 
     #[test]
     fn decode_backslash_newline() {
         let input = "Hello\\\\\\nWorld";
         let target = "Hello\\\nWorld";
-        assert_eq!(target, decode(input))
+        assert_eq!(target, decode(input, Version::V1_2))
     }
 
     #[test]
     fn decode_colon() {
         let input = "Hello\\cWorld";
         let target = "Hello:World";
-        assert_eq!(target, decode(input))
+        assert_eq!(target, decode(input, Version::V1_2))
     }
 
     #[test]
     fn decode_carriage_return() {
         let input = "Hello\\rWorld";
         let target = "Hello\rWorld";
-        assert_eq!(target, decode(input))
+        assert_eq!(target, decode(input, Version::V1_2))
+    }
+
+    #[test]
+    fn decode_borrows_without_escapes() {
+        let input = "Hello World";
+        assert!(matches!(
+            decode(input, Version::V1_2),
+            Cow::Borrowed("Hello World")
+        ))
+    }
+
+    #[test]
+    fn decode_lenient_invalid_escape() {
+        let input = "Hello\\tWorld\\";
+        let target = "HellotWorld";
+        assert_eq!(target, decode(input, Version::V1_2))
+    }
+
+    #[test]
+    fn decode_strict_valid() {
+        let input = "Hello\\c\\\\World";
+        let target = "Hello:\\World";
+        assert_eq!(target, decode_strict(input, Version::V1_2).unwrap())
+    }
+
+    #[test]
+    fn decode_strict_invalid_escape() {
+        assert!(decode_strict("Hello\\tWorld", Version::V1_2).is_err());
+        assert!(decode_strict("Hello World\\", Version::V1_2).is_err());
+    }
+
+    #[test]
+    fn encode_decode_by_version() {
+        let mut output: Vec<u8> = Vec::new();
+        encode_into("a:b\r", Version::V1_0, &mut output);
+        assert_eq!(b"a:b\r".to_vec(), output);
+
+        output.clear();
+        encode_into("a:b\r", Version::V1_1, &mut output);
+        assert_eq!(b"a\\cb\r".to_vec(), output);
+
+        assert_eq!("a\\cb", decode("a\\cb", Version::V1_0));
+        assert_eq!("ar", decode("a\\r", Version::V1_1));
+        assert!(decode_strict("a\\r", Version::V1_1).is_err());
     }
 }
@@ -0,0 +1,186 @@
+use crate::frame::events;
+use crate::frame::io::CountingReader;
+use crate::frame::{
+    body_reader, content_length, skip_eols, Frame, Header, Limits, OwnedFrame, ParserConfig,
+    ParserEvent, ParserEvents, ReadError, Version, CONTENT_LENGTH,
+};
+use std::cell::RefCell;
+use std::io::{BufReader, Read};
+use std::rc::Rc;
+use std::sync::Mutex;
+
+/// A frame reader that can be moved to another thread or shared between threads behind an
+/// `Arc`. Where `FrameReader` streams each body from the input, this reader reads the whole
+/// frame while holding a lock and returns it as an `OwnedFrame`, so concurrent callers each
+/// receive complete frames.
+pub struct SyncFrameReader<R: Read> {
+    reader: Mutex<BufReader<CountingReader<R>>>,
+    /// Scratch space for header lines, only locked while `reader` is.
+    line: Mutex<Vec<u8>>,
+    version: Version,
+    config: ParserConfig,
+    limits: Limits,
+    events: Option<Box<dyn ParserEvents + Send + Sync>>,
+}
+
+impl<R: Read> SyncFrameReader<R> {
+    pub fn new(reader: R) -> SyncFrameReader<R> {
+        SyncFrameReader::with_position(reader, 0)
+    }
+
+    /// Creates a reader over a stream that has already been read up to `position`.
+    pub fn with_position(reader: R, position: u64) -> SyncFrameReader<R> {
+        let counting_reader = CountingReader::new(reader, position);
+
+        SyncFrameReader {
+            reader: Mutex::new(BufReader::new(counting_reader)),
+            line: Mutex::new(Vec::new()),
+            version: Version::default(),
+            config: ParserConfig::default(),
+            limits: Limits::default(),
+            events: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Reports the input that lenient parsing repairs or discards to `events`.
+    pub fn with_events<E: ParserEvents + Send + Sync + 'static>(mut self, events: E) -> Self {
+        self.events = Some(Box::new(events));
+        self
+    }
+
+    /// Sets the protocol version that header decoding follows, 1.2 by default.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The number of bytes consumed from the input stream, which is the offset of the next frame.
+    pub fn position(&self) -> u64 {
+        let reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        reader.get_ref().count() - reader.buffer().len() as u64
+    }
+
+    /// Reads the next frame, skipping the heart-beat EOLs before it.
+    pub fn read_frame(&self) -> Result<OwnedFrame, ReadError> {
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        let mut line = self.line.lock().unwrap_or_else(|e| e.into_inner());
+        let events = self.events.as_deref().map(|e| e as &dyn ParserEvents);
+
+        let (command, header) = loop {
+            skip_eols(&mut *reader)?;
+            let command = Frame::read_command(&mut *reader, self.limits.max_command)?;
+            let header = Header::read_from(
+                &mut *reader,
+                &mut line,
+                self.version,
+                self.config,
+                self.limits,
+                events,
+            );
+
+            match header {
+                Err(ReadError::NullInHeader) if !self.config.is_strict() => {
+                    events::notify(events, ParserEvent::TruncatedFrame)
+                }
+                header => break (command, header?),
+            }
+        };
+
+        let clen = match header.first_ignore_case(CONTENT_LENGTH) {
+            Some(n) => Some(content_length(n, self.limits)?),
+            None => None,
+        };
+
+        let mut body: Vec<u8> = Vec::new();
+        body_reader(
+            Rc::new(RefCell::new(&mut *reader)),
+            clen,
+            self.config,
+            self.limits,
+            events,
+        )
+        .read_to_end(&mut body)?;
+
+        Ok(OwnedFrame::new(command, header, body))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Command, LimitKind};
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn read_frame() {
+        let input = b"SEND\ncontent-length:5\n\nhello\0MESSAGE\n\nworld\0";
+        let frame_reader = SyncFrameReader::new(Cursor::new(input.to_vec()));
+
+        let first = frame_reader.read_frame().unwrap();
+        assert_eq!(Command::Send, first.command);
+        assert_eq!(b"hello".to_vec(), first.body);
+        assert_eq!(29, frame_reader.position());
+
+        let second = frame_reader.read_frame().unwrap();
+        assert_eq!(Command::Message, second.command);
+        assert_eq!(b"world".to_vec(), second.body);
+    }
+
+    #[test]
+    fn limits() {
+        let input = b"SEND\ncontent-length:5\n\nhello\0";
+        let frame_reader = SyncFrameReader::new(Cursor::new(input.to_vec())).with_limits(Limits {
+            max_body: 4,
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            frame_reader.read_frame(),
+            Err(ReadError::LimitExceeded {
+                kind: LimitKind::Body,
+                limit: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let input = b"SEND\n\na\0SEND\n\nb\0SEND\n\nc\0SEND\n\nd\0".to_vec();
+        let frame_reader = Arc::new(SyncFrameReader::new(Cursor::new(input)));
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let frame_reader = frame_reader.clone();
+                thread::spawn(move || {
+                    let mut bodies = Vec::new();
+                    for _ in 0..2 {
+                        bodies.push(frame_reader.read_frame().unwrap().body);
+                    }
+                    bodies
+                })
+            })
+            .collect();
+
+        let mut bodies: Vec<Vec<u8>> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        bodies.sort();
+        assert_eq!(
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()],
+            bodies
+        );
+    }
+}
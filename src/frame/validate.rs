@@ -0,0 +1,171 @@
+use crate::frame::{Command, Frame, Version};
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A header that would not read back as it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    EmptyName,
+    /// The name or value contains a NULL octet.
+    ContainsNull {
+        name: String,
+    },
+    /// The name or value has whitespace that the reader trims.
+    Trimmed {
+        name: String,
+    },
+    /// The name or value has characters that cannot be escaped: any that need escaping under
+    /// STOMP 1.0 and in CONNECT and CONNECTED frames, whose headers are not unescaped, and
+    /// carriage returns under 1.1.
+    NeedsEscaping {
+        name: String,
+    },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::ValidationError::*;
+
+        match self {
+            EmptyName => write!(f, "empty header name"),
+            ContainsNull { name } => write!(f, "header {} contains a NULL octet", name),
+            Trimmed { name } => write!(f, "header {} has leading or trailing whitespace", name),
+            NeedsEscaping { name } => write!(
+                f,
+                "header {} contains characters that cannot be escaped in this frame",
+                name
+            ),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+fn needs_escaping(s: &str) -> bool {
+    s.chars().any(|c| matches!(c, '\\' | '\r' | '\n' | ':'))
+}
+
+impl<'a> Frame<'a> {
+    /// Checks that every header will be read back by a peer exactly as it is written under
+    /// STOMP 1.2.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_for(Version::V1_2)
+    }
+
+    /// Like `Frame::validate`, under the escaping rules of `version`.
+    pub fn validate_for(&self, version: Version) -> Result<(), ValidationError> {
+        let escaped = version.escapes_headers()
+            && !matches!(self.command, Command::Connect | Command::Connected);
+
+        for (name, values) in self.header.iter() {
+            let error_name = || name.clone();
+
+            if name.trim().is_empty() {
+                return Err(ValidationError::EmptyName);
+            }
+
+            if name.trim() != name {
+                return Err(ValidationError::Trimmed { name: error_name() });
+            }
+
+            for s in values.iter().chain(Some(name)) {
+                if s.contains('\0') {
+                    return Err(ValidationError::ContainsNull { name: error_name() });
+                }
+
+                if (!escaped && needs_escaping(s)) || (!version.allows_crlf() && s.contains('\r')) {
+                    return Err(ValidationError::NeedsEscaping { name: error_name() });
+                }
+            }
+
+            for value in values {
+                if value.trim_start() != value || value.ends_with(&['\r', '\n'][..]) {
+                    return Err(ValidationError::Trimmed { name: error_name() });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Body, Header};
+
+    fn frame(command: Command, name: &str, values: &[&str]) -> Frame<'static> {
+        let mut header = Header::new();
+
+        for value in values {
+            header.push(name, (*value).to_owned());
+        }
+        Frame::new(command, header, Body::empty())
+    }
+
+    #[test]
+    fn validate_ok() {
+        assert_eq!(
+            Ok(()),
+            frame(Command::Send, "destination", &["/queue/a:b"]).validate()
+        );
+        assert_eq!(
+            Ok(()),
+            frame(Command::Send, "x-value", &["a\nb\\c"]).validate()
+        );
+        assert_eq!(Ok(()), frame(Command::Connect, "host", &["/"]).validate());
+        assert_eq!(Ok(()), frame(Command::Send, "X-Trace", &["a"]).validate());
+        assert_eq!(Ok(()), frame(Command::Send, "x", &["a", "b"]).validate());
+    }
+
+    #[test]
+    fn validate_errors() {
+        let name = || "x".to_owned();
+        assert_eq!(
+            Err(ValidationError::EmptyName),
+            frame(Command::Send, " ", &["a"]).validate()
+        );
+        assert_eq!(
+            Err(ValidationError::Trimmed { name: name() }),
+            frame(Command::Send, "x", &[" a"]).validate()
+        );
+        assert_eq!(
+            Err(ValidationError::Trimmed { name: name() }),
+            frame(Command::Send, "x", &["a\r"]).validate()
+        );
+        assert_eq!(
+            Err(ValidationError::ContainsNull { name: name() }),
+            frame(Command::Send, "x", &["a\0"]).validate()
+        );
+        assert_eq!(
+            Err(ValidationError::NeedsEscaping { name: name() }),
+            frame(Command::Connect, "x", &["a:b"]).validate()
+        );
+    }
+
+    #[test]
+    fn validate_for_version() {
+        let name = || "x".to_owned();
+        let send = frame(Command::Send, "x", &["a:b\nc"]);
+        assert_eq!(Ok(()), send.validate_for(Version::V1_1));
+        assert_eq!(
+            Err(ValidationError::NeedsEscaping { name: name() }),
+            send.validate_for(Version::V1_0)
+        );
+        assert_eq!(
+            Err(ValidationError::NeedsEscaping { name: name() }),
+            frame(Command::Send, "x", &["/a:b"]).validate_for(Version::V1_0)
+        );
+
+        let send = frame(Command::Send, "x", &["a\rb"]);
+        assert_eq!(Ok(()), send.validate_for(Version::V1_2));
+        assert_eq!(
+            Err(ValidationError::NeedsEscaping { name: name() }),
+            send.validate_for(Version::V1_1)
+        );
+        assert_eq!(
+            Ok(()),
+            frame(Command::Send, "x", &["a b"]).validate_for(Version::V1_0)
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::frame::ReadError;
+use std::fmt;
+use std::str::FromStr;
+
+/// A STOMP protocol version. Versions differ in how frames are encoded: 1.0 does not escape
+/// header values, 1.1 escapes them, and 1.2 also escapes carriage returns and allows lines to
+/// end with CRLF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Version {
+    V1_0,
+    V1_1,
+    #[default]
+    V1_2,
+}
+
+impl Version {
+    pub const ALL: [Version; 3] = [Version::V1_0, Version::V1_1, Version::V1_2];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::V1_0 => "1.0",
+            Version::V1_1 => "1.1",
+            Version::V1_2 => "1.2",
+        }
+    }
+
+    /// Whether header names and values are escaped.
+    pub fn escapes_headers(&self) -> bool {
+        *self >= Version::V1_1
+    }
+
+    /// Whether carriage returns are escaped, and lines may end with CRLF.
+    pub fn allows_crlf(&self) -> bool {
+        *self >= Version::V1_2
+    }
+
+    /// The highest of `supported` listed in an `accept-version` value. A peer that sends no
+    /// `accept-version` header speaks 1.0, so `None` is treated as "1.0".
+    pub fn negotiate(accept_version: Option<&str>, supported: &[Version]) -> Option<Version> {
+        let offered = accept_version.unwrap_or("1.0");
+
+        supported
+            .iter()
+            .filter(|v| offered.split(',').any(|o| o.trim() == v.as_str()))
+            .max()
+            .copied()
+    }
+
+    /// Formats `versions` as an `accept-version` value.
+    pub fn accept_version(versions: &[Version]) -> String {
+        let names: Vec<&str> = versions.iter().map(|v| v.as_str()).collect();
+        names.join(",")
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Version {
+    type Err = ReadError;
+
+    fn from_str(s: &str) -> Result<Version, ReadError> {
+        match s {
+            "1.0" => Ok(Version::V1_0),
+            "1.1" => Ok(Version::V1_1),
+            "1.2" => Ok(Version::V1_2),
+            _ => Err(ReadError::UnknownVersion { got: s.to_owned() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let all = Version::ALL;
+        assert_eq!(
+            Some(Version::V1_2),
+            Version::negotiate(Some("1.0,1.1,1.2"), &all)
+        );
+        assert_eq!(
+            Some(Version::V1_1),
+            Version::negotiate(Some("1.1, 1.0"), &all)
+        );
+        assert_eq!(Some(Version::V1_0), Version::negotiate(None, &all));
+        assert_eq!(None, Version::negotiate(Some("1.2"), &[Version::V1_0]));
+    }
+
+    #[test]
+    fn round_trip() {
+        for version in Version::ALL.iter() {
+            assert_eq!(*version, version.as_str().parse::<Version>().unwrap());
+        }
+        assert!("2.0".parse::<Version>().is_err());
+        assert_eq!(
+            "1.0,1.2",
+            Version::accept_version(&[Version::V1_0, Version::V1_2])
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use crate::frame::{Header, ReadError};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub use crate::frame::headers::HEART_BEAT;
+
+/// The number of late heart-beats in a row after which the peer counts as lagging.
+const LAGGING_AFTER: u32 = 3;
+
+/// The value of a `heart-beat` header: the smallest interval at which this side can send
+/// heart-beats, and the interval at which it would like to receive them. A zero interval means
+/// "none".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeartBeat {
+    pub send: Duration,
+    pub receive: Duration,
+}
+
+impl HeartBeat {
+    pub fn new(send: Duration, receive: Duration) -> Self {
+        HeartBeat { send, receive }
+    }
+
+    /// Reads the `heart-beat` header, treating a missing header as `0,0`.
+    pub fn from_header(header: &Header) -> Result<Self, ReadError> {
+        match header.get(HEART_BEAT).and_then(|v| v.first()) {
+            Some(v) => HeartBeat::from_str(v),
+            None => Ok(HeartBeat::default()),
+        }
+    }
+
+    pub fn write_header(&self, header: &mut Header) {
+        header.insert(HEART_BEAT.to_owned(), vec![self.to_string()]);
+    }
+
+    /// Computes the intervals agreed between this side and the `remote` side, per the STOMP 1.2
+    /// negotiation rules.
+    pub fn negotiate(&self, remote: &HeartBeat) -> Intervals {
+        Intervals {
+            send: negotiate(self.send, remote.receive),
+            receive: negotiate(self.receive, remote.send),
+        }
+    }
+}
+
+fn negotiate(ours: Duration, theirs: Duration) -> Option<Duration> {
+    if ours == Duration::from_millis(0) || theirs == Duration::from_millis(0) {
+        None
+    } else {
+        Some(ours.max(theirs))
+    }
+}
+
+impl Display for HeartBeat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.send.as_millis(), self.receive.as_millis())
+    }
+}
+
+impl FromStr for HeartBeat {
+    type Err = ReadError;
+
+    fn from_str(s: &str) -> Result<HeartBeat, ReadError> {
+        let parts: Vec<&str> = s.split(',').collect();
+
+        let invalid = || ReadError::InvalidHeartBeat { got: s.to_owned() };
+
+        if parts.len() != 2 {
+            return Err(invalid());
+        }
+        let send = parts[0].trim().parse::<u64>().map_err(|_| invalid())?;
+        let receive = parts[1].trim().parse::<u64>().map_err(|_| invalid())?;
+
+        Ok(HeartBeat::new(
+            Duration::from_millis(send),
+            Duration::from_millis(receive),
+        ))
+    }
+}
+
+/// Negotiated heart-beat intervals. `None` means heart-beats are disabled in that direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Intervals {
+    pub send: Option<Duration>,
+    pub receive: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatAction {
+    /// Nothing needs to happen yet.
+    Wait,
+    /// Nothing has been written for the send interval; an EOL should be sent.
+    Send,
+    /// Nothing has been read for the receive interval plus the grace period.
+    Dead,
+}
+
+/// Statistics on the heart-beats received from the peer, from `HeartbeatMonitor::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct KeepaliveStats {
+    /// The number of heart-beats received.
+    pub samples: u64,
+    /// The smoothed time between the last read before a heart-beat and the heart-beat.
+    pub mean_gap: Duration,
+    /// The smoothed deviation of the gap from `mean_gap`.
+    pub jitter: Duration,
+    /// How much later than the receive interval heart-beats arrive on average, in milliseconds.
+    /// A steady positive value points at a peer whose timer runs slow or a loaded broker; jitter
+    /// without skew points at the network.
+    pub skew_millis: i64,
+    /// Heart-beats that arrived more than a tenth of the receive interval late.
+    pub late: u64,
+    /// Late heart-beats in a row, up to the last one received.
+    pub consecutive_late: u32,
+}
+
+impl KeepaliveStats {
+    /// Whether the peer has missed its interval for several heart-beats in a row.
+    pub fn is_lagging(&self) -> bool {
+        self.consecutive_late >= LAGGING_AFTER
+    }
+}
+
+/// Tracks the last read and write on a connection against negotiated intervals.
+#[derive(Debug, Clone)]
+pub struct HeartbeatMonitor {
+    intervals: Intervals,
+    grace: Duration,
+    last_read: Instant,
+    last_write: Instant,
+    stats: KeepaliveStats,
+}
+
+impl HeartbeatMonitor {
+    /// The grace period defaults to the receive interval, so the connection is declared dead
+    /// after twice the receive interval without reading anything.
+    pub fn new(intervals: Intervals, now: Instant) -> Self {
+        HeartbeatMonitor {
+            intervals,
+            grace: intervals.receive.unwrap_or_default(),
+            last_read: now,
+            last_write: now,
+            stats: KeepaliveStats::default(),
+        }
+    }
+
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    pub fn intervals(&self) -> Intervals {
+        self.intervals
+    }
+
+    pub fn record_read(&mut self, now: Instant) {
+        self.last_read = now;
+    }
+
+    pub fn record_write(&mut self, now: Instant) {
+        self.last_write = now;
+    }
+
+    /// Records a heart-beat read from the peer. Like `record_read`, but the time since the
+    /// previous read is also sampled for `HeartbeatMonitor::stats`.
+    pub fn record_heartbeat(&mut self, now: Instant) {
+        let gap = now.saturating_duration_since(self.last_read);
+        self.last_read = now;
+
+        let interval = match self.intervals.receive {
+            Some(interval) => interval,
+            None => return,
+        };
+        let stats = &mut self.stats;
+
+        if stats.samples == 0 {
+            stats.mean_gap = gap;
+        } else {
+            let deviation = gap.abs_diff(stats.mean_gap);
+            stats.jitter = (stats.jitter * 3 + deviation) / 4;
+            stats.mean_gap = (stats.mean_gap * 7 + gap) / 8;
+        }
+        stats.samples += 1;
+        stats.skew_millis = stats.mean_gap.as_millis() as i64 - interval.as_millis() as i64;
+
+        if gap > interval + interval / 10 {
+            stats.late += 1;
+            stats.consecutive_late += 1;
+        } else {
+            stats.consecutive_late = 0;
+        }
+    }
+
+    pub fn stats(&self) -> KeepaliveStats {
+        self.stats
+    }
+
+    pub fn poll(&self, now: Instant) -> HeartbeatAction {
+        if let Some(receive) = self.intervals.receive {
+            if now.saturating_duration_since(self.last_read) > receive + self.grace {
+                return HeartbeatAction::Dead;
+            }
+        }
+
+        if let Some(send) = self.intervals.send {
+            if now.saturating_duration_since(self.last_write) >= send {
+                return HeartbeatAction::Send;
+            }
+        }
+        HeartbeatAction::Wait
+    }
+
+    /// The next instant at which `poll` may return something other than `Wait`.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let send = self.intervals.send.map(|i| self.last_write + i);
+        let receive = self
+            .intervals
+            .receive
+            .map(|i| self.last_read + i + self.grace);
+
+        match (send, receive) {
+            (Some(s), Some(r)) => Some(s.min(r)),
+            (s, r) => s.or(r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn parse() {
+        let heart_beat = HeartBeat::from_str("1000, 500").unwrap();
+        assert_eq!(HeartBeat::new(ms(1000), ms(500)), heart_beat);
+        assert_eq!("1000,500", heart_beat.to_string());
+        assert!(HeartBeat::from_str("1000").is_err());
+        assert!(HeartBeat::from_str("a,b").is_err());
+    }
+
+    #[test]
+    fn header() {
+        let mut header = Header::new();
+        assert_eq!(
+            HeartBeat::default(),
+            HeartBeat::from_header(&header).unwrap()
+        );
+
+        HeartBeat::new(ms(10), ms(20)).write_header(&mut header);
+        assert_eq!(vec!["10,20".to_owned()], header["heart-beat"]);
+    }
+
+    #[test]
+    fn negotiate_intervals() {
+        let client = HeartBeat::new(ms(1000), ms(5000));
+        let server = HeartBeat::new(ms(3000), ms(0));
+
+        let intervals = client.negotiate(&server);
+        assert_eq!(None, intervals.send);
+        assert_eq!(Some(ms(5000)), intervals.receive);
+
+        let intervals = server.negotiate(&client);
+        assert_eq!(Some(ms(5000)), intervals.send);
+        assert_eq!(None, intervals.receive);
+    }
+
+    #[test]
+    fn monitor() {
+        let start = Instant::now();
+        let intervals = Intervals {
+            send: Some(ms(100)),
+            receive: Some(ms(200)),
+        };
+        let mut monitor = HeartbeatMonitor::new(intervals, start);
+        assert_eq!(HeartbeatAction::Wait, monitor.poll(start + ms(50)));
+        assert_eq!(HeartbeatAction::Send, monitor.poll(start + ms(100)));
+        assert_eq!(Some(start + ms(100)), monitor.next_deadline());
+
+        monitor.record_write(start + ms(100));
+        assert_eq!(HeartbeatAction::Wait, monitor.poll(start + ms(150)));
+
+        monitor.record_write(start + ms(400));
+        assert_eq!(HeartbeatAction::Wait, monitor.poll(start + ms(400)));
+        assert_eq!(HeartbeatAction::Dead, monitor.poll(start + ms(401)));
+
+        monitor.record_read(start + ms(400));
+        assert_eq!(HeartbeatAction::Wait, monitor.poll(start + ms(401)));
+    }
+
+    #[test]
+    fn keepalive_stats() {
+        let start = Instant::now();
+        let intervals = Intervals {
+            send: None,
+            receive: Some(ms(100)),
+        };
+        let mut monitor = HeartbeatMonitor::new(intervals, start);
+
+        monitor.record_heartbeat(start + ms(100));
+        let stats = monitor.stats();
+        assert_eq!(1, stats.samples);
+        assert_eq!(ms(100), stats.mean_gap);
+        assert_eq!(0, stats.skew_millis);
+
+        let mut now = start + ms(100);
+        for _ in 0..3 {
+            now += ms(180);
+            monitor.record_heartbeat(now);
+        }
+        let stats = monitor.stats();
+        assert_eq!(3, stats.late);
+        assert!(stats.is_lagging());
+        assert!(stats.skew_millis > 0);
+        assert!(stats.jitter > ms(0));
+
+        now += ms(100);
+        monitor.record_heartbeat(now);
+        assert!(!monitor.stats().is_lagging());
+        assert_eq!(3, monitor.stats().late);
+    }
+
+    #[test]
+    fn monitor_disabled() {
+        let start = Instant::now();
+        let monitor = HeartbeatMonitor::new(Intervals::default(), start);
+        assert_eq!(HeartbeatAction::Wait, monitor.poll(start + ms(1_000_000)));
+        assert_eq!(None, monitor.next_deadline());
+    }
+}
@@ -1,4 +1,20 @@
+pub mod checksum;
+pub mod client;
+pub mod compression;
+pub mod content;
+pub mod destination;
 pub mod frame;
+pub mod heartbeat;
+pub mod message;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod quota;
+pub mod receipt;
+pub mod server;
+pub mod subscription;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transport;
 
 #[cfg(test)]
 mod tests {
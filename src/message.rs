@@ -0,0 +1,165 @@
+use crate::frame::headers::{ACK, ID, MESSAGE_ID, SUBSCRIPTION};
+use crate::frame::{Header, Version};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+const PRIORITY: &str = "priority";
+const PERSISTENT: &str = "persistent";
+
+/// Message priority from 0 (lowest) to 9 (highest), carried in the `priority` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Priority(u8);
+
+impl Priority {
+    pub const MIN: Priority = Priority(0);
+    pub const MAX: Priority = Priority(9);
+    pub const DEFAULT: Priority = Priority(4);
+
+    pub fn new(value: u8) -> Option<Self> {
+        if value <= Self::MAX.0 {
+            Some(Priority(value))
+        } else {
+            None
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn from_header(header: &Header) -> Option<Self> {
+        header
+            .get(PRIORITY)
+            .and_then(|v| v.first())
+            .and_then(|v| v.trim().parse::<u8>().ok())
+            .and_then(Priority::new)
+    }
+
+    pub fn write_header(&self, header: &mut Header) {
+        header.insert(PRIORITY.to_owned(), vec![self.0.to_string()]);
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Whether the broker should persist a message, carried in the `persistent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DeliveryMode {
+    Persistent,
+    #[default]
+    NonPersistent,
+}
+
+impl DeliveryMode {
+    pub fn from_header(header: &Header) -> Option<Self> {
+        match header.get(PERSISTENT).and_then(|v| v.first()) {
+            Some(v) if v.eq_ignore_ascii_case("true") => Some(DeliveryMode::Persistent),
+            Some(v) if v.eq_ignore_ascii_case("false") => Some(DeliveryMode::NonPersistent),
+            _ => None,
+        }
+    }
+
+    pub fn write_header(&self, header: &mut Header) {
+        let value = match self {
+            DeliveryMode::Persistent => "true",
+            DeliveryMode::NonPersistent => "false",
+        };
+        header.insert(PERSISTENT.to_owned(), vec![value.to_owned()]);
+    }
+}
+
+/// The header of an ACK or NACK frame for a received MESSAGE frame with header `message`. STOMP
+/// 1.2 identifies the message by its `ack` header, 1.1 by `message-id` and `subscription`, and
+/// 1.0 by `message-id` alone. Returns `None` if `message` lacks the headers `version` needs.
+pub fn ack_header(message: &Header, version: Version) -> Option<Header> {
+    let get = |key: &str| message.get(key).and_then(|v| v.first()).cloned();
+    let mut header = Header::new();
+
+    match version {
+        Version::V1_2 => header.push(ID, get(ACK)?),
+        Version::V1_1 => {
+            header.push(MESSAGE_ID, get(MESSAGE_ID)?);
+            header.push(SUBSCRIPTION, get(SUBSCRIPTION)?);
+        }
+        Version::V1_0 => header.push(MESSAGE_ID, get(MESSAGE_ID)?),
+    }
+    Some(header)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ack_header_by_version() {
+        let mut message = Header::new();
+        message.push("ack", "a-1".to_owned());
+        message.push("message-id", "m-1".to_owned());
+        message.push("subscription", "sub-0".to_owned());
+
+        let header = ack_header(&message, Version::V1_2).unwrap();
+        assert_eq!(vec!["a-1".to_owned()], header["id"]);
+        assert!(!header.contains_key("message-id"));
+
+        let header = ack_header(&message, Version::V1_1).unwrap();
+        assert_eq!(vec!["m-1".to_owned()], header["message-id"]);
+        assert_eq!(vec!["sub-0".to_owned()], header["subscription"]);
+        assert!(!header.contains_key("id"));
+
+        let header = ack_header(&message, Version::V1_0).unwrap();
+        assert!(!header.contains_key("subscription"));
+
+        message.remove("ack");
+        assert_eq!(None, ack_header(&message, Version::V1_2));
+    }
+
+    #[test]
+    fn priority_range() {
+        assert_eq!(Some(Priority::MAX), Priority::new(9));
+        assert_eq!(None, Priority::new(10));
+        assert_eq!(Priority::DEFAULT, Priority::default());
+    }
+
+    #[test]
+    fn priority_header() {
+        let mut header = Header::new();
+        assert_eq!(None, Priority::from_header(&header));
+
+        header.push("priority", "1".to_owned());
+        Priority::new(7).unwrap().write_header(&mut header);
+        assert_eq!(vec!["7".to_owned()], header["priority"]);
+        assert_eq!(Priority::new(7), Priority::from_header(&header));
+
+        header.insert("priority".to_owned(), vec!["12".to_owned()]);
+        assert_eq!(None, Priority::from_header(&header));
+    }
+
+    #[test]
+    fn delivery_mode_header() {
+        let mut header = Header::new();
+        assert_eq!(None, DeliveryMode::from_header(&header));
+
+        DeliveryMode::Persistent.write_header(&mut header);
+        assert_eq!(vec!["true".to_owned()], header["persistent"]);
+        assert_eq!(
+            Some(DeliveryMode::Persistent),
+            DeliveryMode::from_header(&header)
+        );
+
+        DeliveryMode::NonPersistent.write_header(&mut header);
+        assert_eq!(
+            Some(DeliveryMode::NonPersistent),
+            DeliveryMode::from_header(&header)
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::frame::headers::DESTINATION;
+use crate::frame::{Command, Header};
+use std::collections::BTreeMap;
+
+const BUCKETS: usize = 33;
+
+/// Counts body sizes in power-of-two buckets: bucket `i` holds sizes below `2^i` that did not fit
+/// in bucket `i - 1`, and the last bucket holds everything from 2 GiB up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: u64,
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        SizeHistogram {
+            buckets: [0; BUCKETS],
+            count: 0,
+            total: 0,
+        }
+    }
+}
+
+impl SizeHistogram {
+    pub fn new() -> Self {
+        SizeHistogram::default()
+    }
+
+    pub fn record(&mut self, size: u64) {
+        let bucket = (64 - size.leading_zeros() as usize).min(BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(size);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<u64> {
+        self.total.checked_div(self.count)
+    }
+
+    /// The non-empty buckets as `(upper bound, count)`, where the upper bound is exclusive.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| **n > 0)
+            .map(|(i, n)| (Self::upper_bound(i), *n))
+    }
+
+    /// The upper bound of the bucket holding the `p`th percentile, for `p` from 0 to 100.
+    pub fn percentile(&self, p: u8) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (self.count * u64::from(p.min(100))).div_ceil(100).max(1);
+        let mut seen = 0;
+
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+
+            if seen >= rank {
+                return Some(Self::upper_bound(i));
+            }
+        }
+        None
+    }
+
+    fn upper_bound(bucket: usize) -> u64 {
+        if bucket == BUCKETS - 1 {
+            u64::MAX
+        } else {
+            1 << bucket
+        }
+    }
+}
+
+/// Frame counts per destination for at most a fixed number of destinations. When a new
+/// destination would exceed the limit, the least recently seen one is dropped.
+#[derive(Debug, Clone)]
+pub struct DestinationCounts {
+    capacity: usize,
+    tick: u64,
+    entries: BTreeMap<String, (u64, u64)>,
+}
+
+impl DestinationCounts {
+    pub fn new(capacity: usize) -> Self {
+        DestinationCounts {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, destination: &str) {
+        self.tick += 1;
+
+        if let Some(entry) = self.entries.get_mut(destination) {
+            entry.0 += 1;
+            entry.1 = self.tick;
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, seen))| *seen)
+                .map(|(k, _)| k.clone());
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(destination.to_owned(), (1, self.tick));
+    }
+
+    pub fn get(&self, destination: &str) -> Option<u64> {
+        self.entries.get(destination).map(|(n, _)| *n)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The `n` destinations with the most frames, most first.
+    pub fn top(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut top: Vec<(&str, u64)> = self
+            .entries
+            .iter()
+            .map(|(k, (count, _))| (k.as_str(), *count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+}
+
+/// Body sizes and destinations of the SEND and MESSAGE frames passing through a connection.
+/// Frames recorded without a body size are only counted by destination.
+#[derive(Debug, Clone)]
+pub struct FrameMetrics {
+    pub sent: SizeHistogram,
+    pub received: SizeHistogram,
+    pub destinations: DestinationCounts,
+}
+
+impl Default for FrameMetrics {
+    fn default() -> Self {
+        FrameMetrics::new(100)
+    }
+}
+
+impl FrameMetrics {
+    /// Creates metrics that keep counts for up to `destinations` destinations.
+    pub fn new(destinations: usize) -> Self {
+        FrameMetrics {
+            sent: SizeHistogram::new(),
+            received: SizeHistogram::new(),
+            destinations: DestinationCounts::new(destinations),
+        }
+    }
+
+    pub fn record_sent(&mut self, command: Command, header: &Header, size: Option<u64>) {
+        if self.record(command, header) {
+            if let Some(size) = size {
+                self.sent.record(size);
+            }
+        }
+    }
+
+    pub fn record_received(&mut self, command: Command, header: &Header, size: Option<u64>) {
+        if self.record(command, header) {
+            if let Some(size) = size {
+                self.received.record(size);
+            }
+        }
+    }
+
+    fn record(&mut self, command: Command, header: &Header) -> bool {
+        if !matches!(command, Command::Send | Command::Message) {
+            return false;
+        }
+
+        if let Some(destination) = header.first(DESTINATION) {
+            self.destinations.record(destination);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = SizeHistogram::new();
+        assert_eq!(None, histogram.percentile(50));
+
+        for size in [0, 1, 3, 100, 100, 5000].iter() {
+            histogram.record(*size);
+        }
+        assert_eq!(6, histogram.count());
+        assert_eq!(Some(867), histogram.mean());
+        assert_eq!(
+            vec![(1, 1), (2, 1), (4, 1), (128, 2), (8192, 1)],
+            histogram.buckets().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(4), histogram.percentile(50));
+        assert_eq!(Some(128), histogram.percentile(80));
+        assert_eq!(Some(8192), histogram.percentile(100));
+        assert_eq!(Some(1), histogram.percentile(0));
+    }
+
+    #[test]
+    fn destination_counts_evict_least_recent() {
+        let mut counts = DestinationCounts::new(2);
+        counts.record("/queue/a");
+        counts.record("/queue/a");
+        counts.record("/queue/b");
+        counts.record("/queue/a");
+        counts.record("/queue/c");
+
+        assert_eq!(2, counts.len());
+        assert_eq!(None, counts.get("/queue/b"));
+        assert_eq!(vec![("/queue/a", 3), ("/queue/c", 1)], counts.top(5));
+        assert_eq!(vec![("/queue/a", 3)], counts.top(1));
+    }
+
+    #[test]
+    fn frame_metrics() {
+        let mut metrics = FrameMetrics::new(10);
+        let mut header = Header::new();
+        header.push("destination", "/queue/a".to_owned());
+
+        metrics.record_sent(Command::Send, &header, Some(5));
+        metrics.record_received(Command::Message, &header, Some(5));
+        metrics.record_received(Command::Message, &header, None);
+        metrics.record_sent(Command::Subscribe, &header, Some(5));
+
+        assert_eq!(1, metrics.sent.count());
+        assert_eq!(1, metrics.received.count());
+        assert_eq!(Some(3), metrics.destinations.get("/queue/a"));
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const SECOND: Duration = Duration::from_secs(1);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Limits on what one principal may do, each unlimited when `None`. Rates are counted in fixed
+/// windows that start with the principal's first frame in them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quota {
+    pub max_connections: Option<u32>,
+    pub sends_per_second: Option<u32>,
+    /// SEND body bytes, as given by their `content-length` headers. A `server::Session` refuses
+    /// a SEND without one while this is set.
+    pub bytes_per_day: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    Connections { limit: u32 },
+    SendRate { limit: u32 },
+    Bytes { limit: u64 },
+    Destinations { limit: u32 },
+    QueuedBytes { limit: u64 },
+}
+
+impl Display for QuotaExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::QuotaExceeded::*;
+
+        match self {
+            Connections { limit } => write!(f, "more than {} connections", limit),
+            SendRate { limit } => write!(f, "more than {} sends per second", limit),
+            Bytes { limit } => write!(f, "more than {} bytes per day", limit),
+            Destinations { limit } => write!(f, "more than {} destinations", limit),
+            QueuedBytes { limit } => write!(f, "more than {} queued bytes", limit),
+        }
+    }
+}
+
+impl Error for QuotaExceeded {}
+
+/// Counters for one principal, for metrics and auditing. All but `connections` are totals since
+/// the principal was first seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    /// Connections currently open.
+    pub connections: u32,
+    pub sends: u64,
+    pub bytes: u64,
+    /// Connections and sends refused for exceeding the quota.
+    pub rejected: u64,
+}
+
+struct Window {
+    start: Instant,
+    used: u64,
+}
+
+/// Adds `amount` to `window`, starting a new window if `length` has passed, unless that would
+/// take it past `limit`.
+fn take(
+    window: &mut Option<Window>,
+    amount: u64,
+    limit: u64,
+    length: Duration,
+    now: Instant,
+) -> bool {
+    let window = match window {
+        Some(w) if now.saturating_duration_since(w.start) < length => w,
+        _ => window.insert(Window {
+            start: now,
+            used: 0,
+        }),
+    };
+
+    if window.used.saturating_add(amount) > limit {
+        return false;
+    }
+    window.used += amount;
+    true
+}
+
+#[derive(Default)]
+struct Account {
+    usage: Usage,
+    sends: Option<Window>,
+    bytes: Option<Window>,
+}
+
+#[derive(Default)]
+struct State {
+    quotas: BTreeMap<String, Quota>,
+    accounts: BTreeMap<String, Account>,
+}
+
+/// Enforces a `Quota` per principal across all of a broker's connections, and keeps `Usage`
+/// counters for each. A `server::Session` given the tracker with `Session::quotas` charges it
+/// for the `login` of its CONNECT frame; others can call `QuotaTracker::connect` and
+/// `QuotaTracker::send` directly.
+pub struct QuotaTracker {
+    default: Quota,
+    state: Mutex<State>,
+}
+
+impl QuotaTracker {
+    /// Creates a tracker that applies `default` to principals without a quota of their own.
+    pub fn new(default: Quota) -> Self {
+        QuotaTracker {
+            default,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn set_quota<T: Into<String>>(&self, principal: T, quota: Quota) {
+        self.lock().quotas.insert(principal.into(), quota);
+    }
+
+    pub fn quota(&self, principal: &str) -> Quota {
+        self.lock()
+            .quotas
+            .get(principal)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Counts a new connection for `principal`, unless it already has as many as allowed.
+    pub fn connect(&self, principal: &str) -> Result<(), QuotaExceeded> {
+        let quota = self.quota(principal);
+        let mut state = self.lock();
+        let account = state.accounts.entry(principal.to_owned()).or_default();
+
+        if let Some(limit) = quota.max_connections {
+            if account.usage.connections >= limit {
+                account.usage.rejected += 1;
+                return Err(QuotaExceeded::Connections { limit });
+            }
+        }
+        account.usage.connections += 1;
+        Ok(())
+    }
+
+    /// Counts a connection counted by `QuotaTracker::connect` as closed.
+    pub fn disconnect(&self, principal: &str) {
+        if let Some(account) = self.lock().accounts.get_mut(principal) {
+            account.usage.connections = account.usage.connections.saturating_sub(1);
+        }
+    }
+
+    /// Counts a SEND of `bytes` body bytes, unless it would exceed the principal's rate or
+    /// daily byte limit. A refused SEND is not counted against either.
+    pub fn send(&self, principal: &str, bytes: u64, now: Instant) -> Result<(), QuotaExceeded> {
+        let quota = self.quota(principal);
+        let mut state = self.lock();
+        let account = state.accounts.entry(principal.to_owned()).or_default();
+
+        if let Some(limit) = quota.sends_per_second {
+            if !take(&mut account.sends, 1, u64::from(limit), SECOND, now) {
+                account.usage.rejected += 1;
+                return Err(QuotaExceeded::SendRate { limit });
+            }
+        }
+
+        if let Some(limit) = quota.bytes_per_day {
+            if !take(&mut account.bytes, bytes, limit, DAY, now) {
+                if let Some(sends) = &mut account.sends {
+                    sends.used -= 1;
+                }
+                account.usage.rejected += 1;
+                return Err(QuotaExceeded::Bytes { limit });
+            }
+        }
+        account.usage.sends += 1;
+        account.usage.bytes += bytes;
+        Ok(())
+    }
+
+    pub fn usage(&self, principal: &str) -> Usage {
+        self.lock()
+            .accounts
+            .get(principal)
+            .map(|a| a.usage)
+            .unwrap_or_default()
+    }
+
+    /// The usage of every principal seen so far, by principal.
+    pub fn report(&self) -> Vec<(String, Usage)> {
+        self.lock()
+            .accounts
+            .iter()
+            .map(|(k, a)| (k.clone(), a.usage))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Limits on the resources of one virtual host, shared by all of its connections, each
+/// unlimited when `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VhostQuota {
+    pub max_connections: Option<u32>,
+    /// Distinct destinations with at least one subscription.
+    pub max_destinations: Option<u32>,
+    /// SEND body bytes, as given by their `content-length` headers, that the broker has accepted
+    /// and not yet reported as delivered or dropped with `VhostTracker::dequeue`. A
+    /// `server::Session` refuses a SEND without one while this is set.
+    pub max_queued_bytes: Option<u64>,
+}
+
+/// Counters for one virtual host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VhostUsage {
+    pub connections: u32,
+    pub destinations: u32,
+    pub queued_bytes: u64,
+    /// Connections, subscriptions and sends refused for exceeding the quota.
+    pub rejected: u64,
+}
+
+#[derive(Default)]
+struct Vhost {
+    usage: VhostUsage,
+    /// The number of subscriptions to each destination.
+    destinations: BTreeMap<String, u32>,
+}
+
+#[derive(Default)]
+struct VhostState {
+    quotas: BTreeMap<String, VhostQuota>,
+    vhosts: BTreeMap<String, Vhost>,
+}
+
+/// Enforces a `VhostQuota` per virtual host across all of a broker's connections. A
+/// `server::Session` given the tracker with `Session::vhost_quotas` charges it for the `host`
+/// of its CONNECT frame; the broker reports delivered messages with `VhostTracker::dequeue`.
+pub struct VhostTracker {
+    default: VhostQuota,
+    state: Mutex<VhostState>,
+}
+
+impl VhostTracker {
+    /// Creates a tracker that applies `default` to virtual hosts without a quota of their own.
+    pub fn new(default: VhostQuota) -> Self {
+        VhostTracker {
+            default,
+            state: Mutex::new(VhostState::default()),
+        }
+    }
+
+    pub fn set_quota<T: Into<String>>(&self, vhost: T, quota: VhostQuota) {
+        self.lock().quotas.insert(vhost.into(), quota);
+    }
+
+    pub fn quota(&self, vhost: &str) -> VhostQuota {
+        self.lock()
+            .quotas
+            .get(vhost)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Counts a new connection to `vhost`, unless it already has as many as allowed.
+    pub fn connect(&self, vhost: &str) -> Result<(), QuotaExceeded> {
+        let quota = self.quota(vhost);
+        let mut state = self.lock();
+        let usage = &mut state.vhosts.entry(vhost.to_owned()).or_default().usage;
+
+        if let Some(limit) = quota.max_connections {
+            if usage.connections >= limit {
+                usage.rejected += 1;
+                return Err(QuotaExceeded::Connections { limit });
+            }
+        }
+        usage.connections += 1;
+        Ok(())
+    }
+
+    /// Counts a connection counted by `VhostTracker::connect` as closed.
+    pub fn disconnect(&self, vhost: &str) {
+        if let Some(entry) = self.lock().vhosts.get_mut(vhost) {
+            entry.usage.connections = entry.usage.connections.saturating_sub(1);
+        }
+    }
+
+    /// Counts a subscription to `destination`, unless it is a destination the virtual host has
+    /// no subscriptions to and it already has as many destinations as allowed.
+    pub fn subscribe(&self, vhost: &str, destination: &str) -> Result<(), QuotaExceeded> {
+        let quota = self.quota(vhost);
+        let mut state = self.lock();
+        let entry = state.vhosts.entry(vhost.to_owned()).or_default();
+
+        if let Some(n) = entry.destinations.get_mut(destination) {
+            *n += 1;
+            return Ok(());
+        }
+
+        if let Some(limit) = quota.max_destinations {
+            if entry.usage.destinations >= limit {
+                entry.usage.rejected += 1;
+                return Err(QuotaExceeded::Destinations { limit });
+            }
+        }
+        entry.destinations.insert(destination.to_owned(), 1);
+        entry.usage.destinations += 1;
+        Ok(())
+    }
+
+    /// Counts a subscription counted by `VhostTracker::subscribe` as removed, releasing the
+    /// destination with its last one.
+    pub fn unsubscribe(&self, vhost: &str, destination: &str) {
+        let mut state = self.lock();
+        let entry = match state.vhosts.get_mut(vhost) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        if let Some(n) = entry.destinations.get_mut(destination) {
+            *n -= 1;
+
+            if *n == 0 {
+                entry.destinations.remove(destination);
+                entry.usage.destinations -= 1;
+            }
+        }
+    }
+
+    /// Counts `bytes` more queued on `vhost`, unless that would take it past its limit.
+    pub fn enqueue(&self, vhost: &str, bytes: u64) -> Result<(), QuotaExceeded> {
+        let quota = self.quota(vhost);
+        let mut state = self.lock();
+        let usage = &mut state.vhosts.entry(vhost.to_owned()).or_default().usage;
+
+        if let Some(limit) = quota.max_queued_bytes {
+            if usage.queued_bytes.saturating_add(bytes) > limit {
+                usage.rejected += 1;
+                return Err(QuotaExceeded::QueuedBytes { limit });
+            }
+        }
+        usage.queued_bytes += bytes;
+        Ok(())
+    }
+
+    /// Counts `bytes` queued on `vhost` as delivered or dropped.
+    pub fn dequeue(&self, vhost: &str, bytes: u64) {
+        if let Some(entry) = self.lock().vhosts.get_mut(vhost) {
+            entry.usage.queued_bytes = entry.usage.queued_bytes.saturating_sub(bytes);
+        }
+    }
+
+    pub fn usage(&self, vhost: &str) -> VhostUsage {
+        self.lock()
+            .vhosts
+            .get(vhost)
+            .map(|v| v.usage)
+            .unwrap_or_default()
+    }
+
+    /// The usage of every virtual host seen so far, by virtual host.
+    pub fn report(&self) -> Vec<(String, VhostUsage)> {
+        self.lock()
+            .vhosts
+            .iter()
+            .map(|(k, v)| (k.clone(), v.usage))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VhostState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn connections() {
+        let tracker = QuotaTracker::new(Quota {
+            max_connections: Some(1),
+            ..Quota::default()
+        });
+        tracker.set_quota("ops", Quota::default());
+
+        tracker.connect("team-a").unwrap();
+        assert_eq!(
+            Err(QuotaExceeded::Connections { limit: 1 }),
+            tracker.connect("team-a")
+        );
+        tracker.connect("ops").unwrap();
+        tracker.connect("ops").unwrap();
+
+        tracker.disconnect("team-a");
+        tracker.connect("team-a").unwrap();
+        assert_eq!(
+            Usage {
+                connections: 1,
+                rejected: 1,
+                ..Usage::default()
+            },
+            tracker.usage("team-a")
+        );
+        assert_eq!(2, tracker.usage("ops").connections);
+    }
+
+    #[test]
+    fn sends() {
+        let tracker = QuotaTracker::new(Quota {
+            sends_per_second: Some(2),
+            bytes_per_day: Some(100),
+            ..Quota::default()
+        });
+        let now = Instant::now();
+
+        tracker.send("a", 10, now).unwrap();
+        tracker.send("a", 10, now).unwrap();
+        assert!(matches!(
+            tracker.send("a", 10, now),
+            Err(QuotaExceeded::SendRate { limit: 2 })
+        ));
+
+        let later = now + SECOND;
+        assert!(matches!(
+            tracker.send("a", 90, later),
+            Err(QuotaExceeded::Bytes { limit: 100 })
+        ));
+        tracker.send("a", 80, later).unwrap();
+        tracker.send("a", 1, later).unwrap_err();
+        tracker.send("a", 10, later + DAY).unwrap();
+
+        assert_eq!(
+            Usage {
+                connections: 0,
+                sends: 4,
+                bytes: 110,
+                rejected: 3,
+            },
+            tracker.usage("a")
+        );
+        assert_eq!(vec![("a".to_owned(), tracker.usage("a"))], tracker.report());
+    }
+
+    #[test]
+    fn vhosts() {
+        let tracker = VhostTracker::new(VhostQuota {
+            max_connections: Some(1),
+            max_destinations: Some(2),
+            max_queued_bytes: Some(10),
+        });
+        tracker.set_quota("ops", VhostQuota::default());
+
+        tracker.connect("a").unwrap();
+        assert_eq!(
+            Err(QuotaExceeded::Connections { limit: 1 }),
+            tracker.connect("a")
+        );
+        tracker.connect("ops").unwrap();
+        tracker.connect("ops").unwrap();
+        tracker.disconnect("a");
+        tracker.connect("a").unwrap();
+
+        tracker.subscribe("a", "/queue/1").unwrap();
+        tracker.subscribe("a", "/queue/1").unwrap();
+        tracker.subscribe("a", "/queue/2").unwrap();
+        assert_eq!(
+            Err(QuotaExceeded::Destinations { limit: 2 }),
+            tracker.subscribe("a", "/queue/3")
+        );
+        tracker.unsubscribe("a", "/queue/1");
+        tracker.subscribe("a", "/queue/3").unwrap_err();
+        tracker.unsubscribe("a", "/queue/1");
+        tracker.subscribe("a", "/queue/3").unwrap();
+
+        tracker.enqueue("a", 6).unwrap();
+        assert_eq!(
+            Err(QuotaExceeded::QueuedBytes { limit: 10 }),
+            tracker.enqueue("a", 6)
+        );
+        tracker.dequeue("a", 6);
+        tracker.enqueue("a", 10).unwrap();
+
+        assert_eq!(
+            VhostUsage {
+                connections: 1,
+                destinations: 2,
+                queued_bytes: 10,
+                rejected: 4,
+            },
+            tracker.usage("a")
+        );
+        assert_eq!(2, tracker.usage("ops").connections);
+        assert_eq!(2, tracker.report().len());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::frame::{Command, Frame, Header};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+pub use crate::frame::headers::{RECEIPT, RECEIPT_ID};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiptError {
+    /// The receipt was not assigned by this tracker, or has already been waited for.
+    Unknown(String),
+    Timeout(String),
+}
+
+impl Display for ReceiptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiptError::Unknown(id) => write!(f, "unknown receipt {}", id),
+            ReceiptError::Timeout(id) => write!(f, "timed out waiting for receipt {}", id),
+        }
+    }
+}
+
+impl Error for ReceiptError {}
+
+type Callback = Box<dyn FnOnce(&str) + Send>;
+
+enum Entry {
+    Pending(Option<Callback>),
+    Confirmed,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    entries: BTreeMap<String, Entry>,
+}
+
+/// Assigns `receipt` headers to outgoing frames and matches the RECEIPT frames that confirm
+/// them. The tracker can be shared between the thread that reads frames, which passes them to
+/// `ReceiptTracker::handle`, and threads that wait for confirmations.
+pub struct ReceiptTracker {
+    prefix: String,
+    state: Mutex<State>,
+    confirmed: Condvar,
+}
+
+impl Default for ReceiptTracker {
+    fn default() -> Self {
+        ReceiptTracker::with_prefix("receipt-")
+    }
+}
+
+impl ReceiptTracker {
+    pub fn new() -> Self {
+        ReceiptTracker::default()
+    }
+
+    /// Creates a tracker whose receipt ids start with `prefix`, to keep them apart from ids
+    /// assigned elsewhere on the same connection.
+    pub fn with_prefix<T: Into<String>>(prefix: T) -> Self {
+        ReceiptTracker {
+            prefix: prefix.into(),
+            state: Mutex::new(State::default()),
+            confirmed: Condvar::new(),
+        }
+    }
+
+    /// Sets a new receipt id on `header` and returns it. The receipt is tracked until
+    /// `ReceiptTracker::wait_for` has seen it confirmed.
+    pub fn assign(&self, header: &mut Header) -> String {
+        self.insert(header, None)
+    }
+
+    /// Like `ReceiptTracker::assign`, but instead of being waited for, the receipt calls
+    /// `callback` with its id when it is confirmed, and is then forgotten.
+    pub fn assign_with<F>(&self, header: &mut Header, callback: F) -> String
+    where
+        F: FnOnce(&str) + Send + 'static,
+    {
+        self.insert(header, Some(Box::new(callback)))
+    }
+
+    /// Confirms the receipt a RECEIPT frame refers to, returning whether it was one assigned by
+    /// this tracker.
+    pub fn handle(&self, frame: &Frame<'_>) -> bool {
+        if frame.command != Command::Receipt {
+            return false;
+        }
+
+        match frame.header.get(RECEIPT_ID).and_then(|v| v.first()) {
+            Some(id) => self.confirm(id),
+            None => false,
+        }
+    }
+
+    /// Marks a receipt confirmed, returning whether it was one assigned by this tracker.
+    pub fn confirm(&self, id: &str) -> bool {
+        let mut state = self.lock();
+        let callback = match state.entries.get_mut(id) {
+            Some(Entry::Pending(callback)) => callback.take(),
+            _ => return false,
+        };
+
+        if callback.is_some() {
+            state.entries.remove(id);
+        } else {
+            state.entries.insert(id.to_owned(), Entry::Confirmed);
+        }
+        drop(state);
+        self.confirmed.notify_all();
+
+        if let Some(callback) = callback {
+            callback(id);
+        }
+        true
+    }
+
+    /// Blocks until the receipt is confirmed or `timeout` passes. A confirmed receipt is
+    /// forgotten once this returns.
+    pub fn wait_for(&self, id: &str, timeout: Duration) -> Result<(), ReceiptError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+
+        loop {
+            match state.entries.get(id) {
+                Some(Entry::Confirmed) => {
+                    state.entries.remove(id);
+                    return Ok(());
+                }
+                Some(Entry::Pending(None)) => (),
+                _ => return Err(ReceiptError::Unknown(id.to_owned())),
+            }
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Err(ReceiptError::Timeout(id.to_owned()));
+            }
+            state = self
+                .confirmed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Whether the receipt has been assigned but not yet confirmed.
+    pub fn is_pending(&self, id: &str) -> bool {
+        matches!(self.lock().entries.get(id), Some(Entry::Pending(_)))
+    }
+
+    /// The number of receipts that have not been confirmed yet.
+    pub fn pending(&self) -> usize {
+        self.lock()
+            .entries
+            .values()
+            .filter(|e| matches!(e, Entry::Pending(_)))
+            .count()
+    }
+
+    fn insert(&self, header: &mut Header, callback: Option<Callback>) -> String {
+        let mut state = self.lock();
+        let id = format!("{}{}", self.prefix, state.next_id);
+        state.next_id += 1;
+        state.entries.insert(id.clone(), Entry::Pending(callback));
+        header.insert(RECEIPT.to_owned(), vec![id.clone()]);
+        id
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::Body;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn receipt(id: &str) -> Frame<'static> {
+        let mut header = Header::new();
+        header.push(RECEIPT_ID, id.to_owned());
+        Frame::new(Command::Receipt, header, Body::empty())
+    }
+
+    #[test]
+    fn assign() {
+        let tracker = ReceiptTracker::with_prefix("r-");
+        let mut header = Header::new();
+        let id = tracker.assign(&mut header);
+        assert_eq!("r-0", id);
+        assert_eq!(vec!["r-0".to_owned()], header[RECEIPT]);
+        assert_eq!("r-1", tracker.assign(&mut Header::new()));
+        assert_eq!(2, tracker.pending());
+        assert!(tracker.is_pending("r-0"));
+    }
+
+    #[test]
+    fn wait_for() {
+        let tracker = Arc::new(ReceiptTracker::new());
+        let id = tracker.assign(&mut Header::new());
+
+        let reader = tracker.clone();
+        let confirmed = id.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            assert!(!reader.handle(&receipt("other")));
+            assert!(reader.handle(&receipt(&confirmed)));
+        });
+
+        tracker.wait_for(&id, Duration::from_secs(5)).unwrap();
+        assert_eq!(0, tracker.pending());
+        assert_eq!(
+            Err(ReceiptError::Unknown(id.clone())),
+            tracker.wait_for(&id, Duration::from_secs(0))
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn wait_for_timeout() {
+        let tracker = ReceiptTracker::new();
+        let id = tracker.assign(&mut Header::new());
+        assert_eq!(
+            Err(ReceiptError::Timeout(id.clone())),
+            tracker.wait_for(&id, Duration::from_millis(10))
+        );
+        assert!(tracker.is_pending(&id));
+    }
+
+    #[test]
+    fn callback() {
+        let tracker = ReceiptTracker::new();
+        let (sender, receiver) = mpsc::channel();
+        let id = tracker.assign_with(&mut Header::new(), move |id| {
+            sender.send(id.to_owned()).unwrap();
+        });
+
+        assert!(tracker.confirm(&id));
+        assert_eq!(id, receiver.try_recv().unwrap());
+        assert!(!tracker.confirm(&id));
+        assert_eq!(0, tracker.pending());
+    }
+}
@@ -0,0 +1,991 @@
+use crate::compression;
+use crate::compression::COMPRESSION;
+use crate::destination::Destination;
+use crate::frame::headers::{
+    ACCEPT_VERSION, ACK, CONTENT_LENGTH, DESTINATION, HOST, ID, LOGIN, MESSAGE, RECEIPT,
+    RECEIPT_ID, SERVER, SESSION, TRANSACTION, VERSION,
+};
+use crate::frame::{required_headers_for, AckMode, Command, Frame, Header, OwnedFrame, Version};
+use crate::heartbeat::HeartBeat;
+#[cfg(feature = "metrics")]
+use crate::metrics::FrameMetrics;
+use crate::quota::{QuotaExceeded, QuotaTracker, VhostTracker};
+use crate::subscription::{SubscriptionId, SubscriptionRegistry};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// No CONNECT or STOMP frame has been received yet.
+    Connecting,
+    Connected,
+    /// The client disconnected or the session sent an ERROR. The connection should be closed.
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    NotConnected(Command),
+    AlreadyConnected,
+    Closed(Command),
+    UnexpectedCommand(Command),
+    UnsupportedVersion(String),
+    MissingHeader {
+        command: Command,
+        header: &'static str,
+    },
+    InvalidHeader {
+        header: &'static str,
+        value: String,
+    },
+    DuplicateSubscription(SubscriptionId),
+    UnknownSubscription(SubscriptionId),
+    DuplicateTransaction(String),
+    UnknownTransaction(String),
+    QuotaExceeded(QuotaExceeded),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::Violation::*;
+
+        match self {
+            NotConnected(command) => write!(f, "{} frame received before CONNECT", command),
+            AlreadyConnected => write!(f, "already connected"),
+            Closed(command) => write!(f, "{} frame received after the session closed", command),
+            UnexpectedCommand(command) => write!(f, "{} frame cannot be sent by a client", command),
+            UnsupportedVersion(versions) => write!(f, "unsupported protocol version {}", versions),
+            MissingHeader { command, header } => {
+                write!(f, "{} frame requires a {} header", command, header)
+            }
+            InvalidHeader { header, value } => write!(f, "invalid {} header: {}", header, value),
+            DuplicateSubscription(id) => write!(f, "subscription {} already exists", id),
+            UnknownSubscription(id) => write!(f, "unknown subscription {}", id),
+            DuplicateTransaction(tx) => write!(f, "transaction {} already started", tx),
+            UnknownTransaction(tx) => write!(f, "unknown transaction {}", tx),
+            QuotaExceeded(e) => write!(f, "quota exceeded: {}", e),
+        }
+    }
+}
+
+impl Error for Violation {}
+
+/// A frame that broke the protocol, with the ERROR frame to send before closing the connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub violation: Violation,
+    pub error: OwnedFrame,
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.violation)
+    }
+}
+
+impl Error for Rejection {}
+
+/// The server side of one STOMP connection. The session checks each frame received from the
+/// client against the protocol and tracks connection state, subscriptions and transactions; it
+/// does no I/O and leaves routing messages to the broker.
+pub struct Session {
+    id: String,
+    server: Option<String>,
+    heart_beat: HeartBeat,
+    state: SessionState,
+    versions: Vec<Version>,
+    version: Option<Version>,
+    codecs: Vec<&'static str>,
+    compression: Option<&'static str>,
+    subscriptions: SubscriptionRegistry,
+    transactions: BTreeSet<String>,
+    quotas: Option<Arc<QuotaTracker>>,
+    principal: Option<String>,
+    vhost_quotas: Option<Arc<VhostTracker>>,
+    vhost: Option<String>,
+    #[cfg(feature = "metrics")]
+    metrics: FrameMetrics,
+}
+
+impl Session {
+    pub fn new<T: Into<String>>(id: T) -> Self {
+        Session {
+            id: id.into(),
+            server: None,
+            heart_beat: HeartBeat::default(),
+            state: SessionState::Connecting,
+            versions: Version::ALL.to_vec(),
+            version: None,
+            codecs: Vec::new(),
+            compression: None,
+            subscriptions: SubscriptionRegistry::new(),
+            transactions: BTreeSet::new(),
+            quotas: None,
+            principal: None,
+            vhost_quotas: None,
+            vhost: None,
+            #[cfg(feature = "metrics")]
+            metrics: FrameMetrics::default(),
+        }
+    }
+
+    /// Sets the `server` header sent in CONNECTED.
+    pub fn server<T: Into<String>>(mut self, server: T) -> Self {
+        self.server = Some(server.into());
+        self
+    }
+
+    /// Sets the heart-beat intervals offered in CONNECTED.
+    pub fn heart_beat(mut self, heart_beat: HeartBeat) -> Self {
+        self.heart_beat = heart_beat;
+        self
+    }
+
+    /// Sets the protocol versions the session accepts, all of them by default.
+    pub fn versions(mut self, versions: &[Version]) -> Self {
+        self.versions = versions.to_vec();
+        self
+    }
+
+    /// Sets the compression codecs the broker can use, in order of preference. The first one the
+    /// client offers is named in CONNECTED; see `compression::Codec`.
+    pub fn compression(mut self, codecs: &[&'static str]) -> Self {
+        self.codecs = codecs.to_vec();
+        self
+    }
+
+    /// Charges the session's CONNECT and SEND frames to the quota of the principal named by the
+    /// `login` header of CONNECT, or of the empty principal without one. The session takes no
+    /// part in authenticating the principal. Frames over the quota are rejected with
+    /// `Violation::QuotaExceeded`, and the connection is counted until the session closes or is
+    /// dropped.
+    pub fn quotas(mut self, quotas: Arc<QuotaTracker>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Charges the session's connection, subscriptions and SEND frames to the quota of the
+    /// virtual host named by the `host` header of CONNECT, or of the empty host without one.
+    /// Frames over the quota are rejected with `Violation::QuotaExceeded`. The connection and
+    /// its subscriptions are counted until the session closes or is dropped; queued bytes until
+    /// the broker reports them with `VhostTracker::dequeue`.
+    pub fn vhost_quotas(mut self, quotas: Arc<VhostTracker>) -> Self {
+        self.vhost_quotas = Some(quotas);
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The principal the session's connection is counted for by its `QuotaTracker`.
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// The virtual host the session's connection is counted for by its `VhostTracker`.
+    pub fn vhost(&self) -> Option<&str> {
+        self.vhost.as_deref()
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// The protocol version negotiated during CONNECT.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// The compression codec negotiated during CONNECT.
+    pub fn negotiated_compression(&self) -> Option<&'static str> {
+        self.compression
+    }
+
+    pub fn subscriptions(&self) -> &SubscriptionRegistry {
+        &self.subscriptions
+    }
+
+    pub fn in_transaction(&self, transaction: &str) -> bool {
+        self.transactions.contains(transaction)
+    }
+
+    /// Body sizes and destinations of the frames handled so far. A session does not read bodies,
+    /// so sizes are taken from `content-length`, and frames without one are only counted by
+    /// destination.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> &FrameMetrics {
+        &self.metrics
+    }
+
+    /// Applies a frame received from the client and returns the frame to send in response, if
+    /// any: CONNECTED for CONNECT, and RECEIPT for frames that request one. A frame that breaks
+    /// the protocol closes the session and is rejected with an ERROR frame.
+    pub fn handle(&mut self, frame: &Frame<'_>) -> Result<Option<OwnedFrame>, Rejection> {
+        #[cfg(feature = "metrics")]
+        self.metrics.record_received(
+            frame.command,
+            &frame.header,
+            frame
+                .header
+                .first_ignore_case(CONTENT_LENGTH)
+                .and_then(|v| v.parse().ok()),
+        );
+
+        match self.apply(frame.command, &frame.header) {
+            Ok(response) => Ok(response),
+            Err(violation) => {
+                self.close();
+                let error = error_frame(&violation, frame.header.first(RECEIPT));
+                Err(Rejection { violation, error })
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        command: Command,
+        header: &Header,
+    ) -> Result<Option<OwnedFrame>, Violation> {
+        use self::Command::*;
+
+        match (self.state, command) {
+            (_, Connected) | (_, Message) | (_, Receipt) | (_, Error) => {
+                return Err(Violation::UnexpectedCommand(command))
+            }
+            (SessionState::Closed, _) => return Err(Violation::Closed(command)),
+            (SessionState::Connecting, Connect) | (SessionState::Connecting, Stomp) => {
+                return self.connect(header).map(Some)
+            }
+            (SessionState::Connecting, _) => return Err(Violation::NotConnected(command)),
+            (SessionState::Connected, Connect) | (SessionState::Connected, Stomp) => {
+                return Err(Violation::AlreadyConnected)
+            }
+            (SessionState::Connected, _) => (),
+        }
+
+        for name in required_headers_for(command, self.version.unwrap_or_default()) {
+            if header.first(name).is_none() {
+                return Err(Violation::MissingHeader {
+                    command,
+                    header: name,
+                });
+            }
+        }
+        let transaction = header.first(TRANSACTION).unwrap_or_default();
+
+        match command {
+            Send | Ack | Nack if !transaction.is_empty() && !self.in_transaction(transaction) => {
+                return Err(Violation::UnknownTransaction(transaction.to_owned()))
+            }
+            Send => self.charge_send(header)?,
+            Subscribe => self.subscribe(header)?,
+            Unsubscribe => {
+                let id = subscription_id(header).ok_or(Violation::MissingHeader {
+                    command,
+                    header: ID,
+                })?;
+
+                let entry = self
+                    .subscriptions
+                    .remove(&id)
+                    .ok_or(Violation::UnknownSubscription(id))?;
+
+                if let (Some(quotas), Some(vhost)) = (&self.vhost_quotas, &self.vhost) {
+                    quotas.unsubscribe(vhost, entry.destination.as_str());
+                }
+            }
+            Begin => self.begin(transaction)?,
+            Commit | Abort => self.end(transaction)?,
+            Disconnect => self.close(),
+            _ => (),
+        }
+
+        Ok(header.first(RECEIPT).map(|receipt| {
+            let mut response = Header::new();
+            response.push(RECEIPT_ID, receipt.to_owned());
+            OwnedFrame::new(Receipt, response, Vec::new())
+        }))
+    }
+
+    fn connect(&mut self, header: &Header) -> Result<OwnedFrame, Violation> {
+        let offered = header.first(ACCEPT_VERSION);
+        let version = Version::negotiate(offered, &self.versions)
+            .ok_or_else(|| Violation::UnsupportedVersion(offered.unwrap_or("1.0").to_owned()))?;
+
+        if version != Version::V1_0 && header.first(HOST).is_none() {
+            return Err(Violation::MissingHeader {
+                command: Command::Connect,
+                header: HOST,
+            });
+        }
+        if let Some(quotas) = &self.vhost_quotas {
+            let host = header.first(HOST).unwrap_or_default();
+            quotas.connect(host).map_err(Violation::QuotaExceeded)?;
+            self.vhost = Some(host.to_owned());
+        }
+        if let Some(quotas) = &self.quotas {
+            let principal = header.first(LOGIN).unwrap_or_default();
+            quotas
+                .connect(principal)
+                .map_err(Violation::QuotaExceeded)?;
+            self.principal = Some(principal.to_owned());
+        }
+        self.state = SessionState::Connected;
+        self.version = Some(version);
+
+        let mut response = Header::new();
+        response.push(VERSION, version.to_string());
+        response.push(SESSION, self.id.clone());
+
+        if let Some(server) = &self.server {
+            response.push(SERVER, server.clone());
+        }
+        self.heart_beat.write_header(&mut response);
+        self.compression = compression::negotiate(header, &self.codecs);
+
+        if let Some(codec) = self.compression {
+            response.push(COMPRESSION, codec.to_owned());
+        }
+        Ok(OwnedFrame::new(Command::Connected, response, Vec::new()))
+    }
+
+    /// Charges a SEND to the virtual host's and the principal's quotas. Under a byte quota the
+    /// body is measured by its `content-length`, which the reader holds the body to, so a SEND
+    /// must carry one.
+    fn charge_send(&self, header: &Header) -> Result<(), Violation> {
+        let principal = match (&self.quotas, &self.principal) {
+            (Some(quotas), Some(principal)) => Some((quotas, principal)),
+            _ => None,
+        };
+        let vhost = match (&self.vhost_quotas, &self.vhost) {
+            (Some(quotas), Some(vhost)) => Some((quotas, vhost)),
+            _ => None,
+        };
+
+        if principal.is_none() && vhost.is_none() {
+            return Ok(());
+        }
+        let measured = principal.is_some_and(|(q, p)| q.quota(p).bytes_per_day.is_some())
+            || vhost.is_some_and(|(q, v)| q.quota(v).max_queued_bytes.is_some());
+        let bytes = match header.first_ignore_case(CONTENT_LENGTH) {
+            Some(value) => value.parse().map_err(|_| Violation::InvalidHeader {
+                header: CONTENT_LENGTH,
+                value: value.to_owned(),
+            })?,
+            None if measured => {
+                return Err(Violation::MissingHeader {
+                    command: Command::Send,
+                    header: CONTENT_LENGTH,
+                })
+            }
+            None => 0,
+        };
+
+        if let Some((quotas, vhost)) = vhost {
+            quotas
+                .enqueue(vhost, bytes)
+                .map_err(Violation::QuotaExceeded)?;
+        }
+
+        if let Some((quotas, principal)) = principal {
+            if let Err(e) = quotas.send(principal, bytes, Instant::now()) {
+                if let Some((quotas, vhost)) = vhost {
+                    quotas.dequeue(vhost, bytes);
+                }
+                return Err(Violation::QuotaExceeded(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.state = SessionState::Closed;
+
+        if let (Some(quotas), Some(principal)) = (&self.quotas, self.principal.take()) {
+            quotas.disconnect(&principal);
+        }
+        if let (Some(quotas), Some(vhost)) = (&self.vhost_quotas, self.vhost.take()) {
+            for (_, entry) in self.subscriptions.iter() {
+                quotas.unsubscribe(&vhost, entry.destination.as_str());
+            }
+            quotas.disconnect(&vhost);
+        }
+    }
+
+    fn begin(&mut self, transaction: &str) -> Result<(), Violation> {
+        if !self.transactions.insert(transaction.to_owned()) {
+            return Err(Violation::DuplicateTransaction(transaction.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, transaction: &str) -> Result<(), Violation> {
+        if !self.transactions.remove(transaction) {
+            return Err(Violation::UnknownTransaction(transaction.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn subscribe(&mut self, header: &Header) -> Result<(), Violation> {
+        let value = header.first(DESTINATION).unwrap_or_default();
+        let id = SubscriptionId::new(header.first(ID).unwrap_or(value));
+        let destination = Destination::new(value).map_err(|_| Violation::InvalidHeader {
+            header: DESTINATION,
+            value: value.to_owned(),
+        })?;
+        let ack = match header.first(ACK) {
+            Some(value) => AckMode::from_str(value).map_err(|_| Violation::InvalidHeader {
+                header: ACK,
+                value: value.to_owned(),
+            })?,
+            None => AckMode::default(),
+        };
+
+        if self.subscriptions.contains(&id) {
+            return Err(Violation::DuplicateSubscription(id));
+        }
+
+        if let (Some(quotas), Some(vhost)) = (&self.vhost_quotas, &self.vhost) {
+            quotas
+                .subscribe(vhost, destination.as_str())
+                .map_err(Violation::QuotaExceeded)?;
+        }
+        self.subscriptions
+            .insert(id, destination, ack)
+            .map_err(|e| Violation::DuplicateSubscription(e.0))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// The `id` of a subscription, or under STOMP 1.0, which does not require one, its destination.
+fn subscription_id(header: &Header) -> Option<SubscriptionId> {
+    header
+        .first(ID)
+        .or_else(|| header.first(DESTINATION))
+        .map(SubscriptionId::new)
+}
+
+fn error_frame(violation: &Violation, receipt: Option<&str>) -> OwnedFrame {
+    let mut header = Header::new();
+    header.push(MESSAGE, violation.to_string());
+
+    if let Some(receipt) = receipt {
+        header.push(RECEIPT_ID, receipt.to_owned());
+    }
+    OwnedFrame::new(Command::Error, header, Vec::new())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::Body;
+    use crate::quota::{Quota, Usage, VhostQuota, VhostUsage};
+
+    fn frame(command: Command, header: &[(&str, &str)]) -> OwnedFrame {
+        let mut h = Header::new();
+
+        for (k, v) in header {
+            h.push(*k, (*v).to_owned());
+        }
+        OwnedFrame::new(command, h, Vec::new())
+    }
+
+    fn handle(
+        session: &mut Session,
+        command: Command,
+        header: &[(&str, &str)],
+    ) -> Result<Option<OwnedFrame>, Rejection> {
+        session.handle(&frame(command, header).as_frame())
+    }
+
+    fn connected() -> Session {
+        let mut session = Session::new("s-1").server("test");
+        let connect = [("accept-version", "1.1,1.2"), ("host", "/")];
+        handle(&mut session, Command::Connect, &connect).unwrap();
+        session
+    }
+
+    #[test]
+    fn connect() {
+        let mut session = Session::new("s-1").server("test");
+        assert_eq!(SessionState::Connecting, session.state());
+
+        let connect = [("accept-version", "1.1,1.2"), ("host", "/")];
+        let response = handle(&mut session, Command::Connect, &connect)
+            .unwrap()
+            .unwrap();
+        assert_eq!(Command::Connected, response.command);
+        assert_eq!(vec!["1.2".to_owned()], response.header["version"]);
+        assert_eq!(vec!["s-1".to_owned()], response.header["session"]);
+        assert_eq!(vec!["test".to_owned()], response.header["server"]);
+        assert_eq!(SessionState::Connected, session.state());
+        assert_eq!(Some(Version::V1_2), session.version());
+    }
+
+    #[test]
+    fn connect_compression() {
+        let mut session = Session::new("s-1").compression(&["gzip", "zstd"]);
+        let connect = [
+            ("accept-version", "1.2"),
+            ("host", "/"),
+            ("compression", "zstd"),
+        ];
+        let response = handle(&mut session, Command::Connect, &connect)
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["zstd".to_owned()], response.header["compression"]);
+        assert_eq!(Some("zstd"), session.negotiated_compression());
+    }
+
+    #[test]
+    fn connect_legacy_without_accept_version() {
+        let mut session = Session::new("s-1");
+        let response = handle(&mut session, Command::Connect, &[])
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["1.0".to_owned()], response.header["version"]);
+    }
+
+    #[test]
+    fn connect_restricted_versions() {
+        let mut session = Session::new("s-1").versions(&[Version::V1_1]);
+        let connect = [("accept-version", "1.1,1.2"), ("host", "/")];
+        let response = handle(&mut session, Command::Connect, &connect)
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["1.1".to_owned()], response.header["version"]);
+    }
+
+    #[test]
+    fn connect_unsupported_version() {
+        let mut session = Session::new("s-1");
+        let connect = [("accept-version", "2.0"), ("host", "/")];
+        let rejection = handle(&mut session, Command::Connect, &connect).unwrap_err();
+        assert_eq!(
+            Violation::UnsupportedVersion("2.0".to_owned()),
+            rejection.violation
+        );
+        assert_eq!(Command::Error, rejection.error.command);
+        assert_eq!(SessionState::Closed, session.state());
+    }
+
+    #[test]
+    fn frame_before_connect() {
+        let mut session = Session::new("s-1");
+        let rejection = handle(&mut session, Command::Send, &[("destination", "/a")]).unwrap_err();
+        assert_eq!(Violation::NotConnected(Command::Send), rejection.violation);
+    }
+
+    #[test]
+    fn connect_twice() {
+        let mut session = connected();
+        let connect = [("accept-version", "1.2"), ("host", "/")];
+        let rejection = handle(&mut session, Command::Connect, &connect).unwrap_err();
+        assert_eq!(Violation::AlreadyConnected, rejection.violation);
+    }
+
+    #[test]
+    fn receipt() {
+        let mut session = connected();
+        let send = [("destination", "/queue/a"), ("receipt", "r-1")];
+        let response = handle(&mut session, Command::Send, &send).unwrap().unwrap();
+        assert_eq!(Command::Receipt, response.command);
+        assert_eq!(vec!["r-1".to_owned()], response.header["receipt-id"]);
+
+        let send = [("destination", "/queue/a")];
+        assert_eq!(None, handle(&mut session, Command::Send, &send).unwrap());
+    }
+
+    #[test]
+    fn missing_header() {
+        let mut session = connected();
+        let rejection = handle(&mut session, Command::Send, &[("receipt", "r-1")]).unwrap_err();
+        assert_eq!(
+            Violation::MissingHeader {
+                command: Command::Send,
+                header: "destination"
+            },
+            rejection.violation
+        );
+        assert_eq!(vec!["r-1".to_owned()], rejection.error.header["receipt-id"]);
+    }
+
+    #[test]
+    fn subscriptions() {
+        let mut session = connected();
+        let subscribe = [("id", "0"), ("destination", "/queue/a"), ("ack", "client")];
+        handle(&mut session, Command::Subscribe, &subscribe).unwrap();
+
+        let entry = session
+            .subscriptions()
+            .get(&SubscriptionId::from("0"))
+            .unwrap();
+        assert_eq!(AckMode::Client, entry.ack);
+
+        let rejection = handle(&mut session, Command::Subscribe, &subscribe).unwrap_err();
+        assert_eq!(
+            Violation::DuplicateSubscription(SubscriptionId::from("0")),
+            rejection.violation
+        );
+    }
+
+    #[test]
+    fn unsubscribe_unknown() {
+        let mut session = connected();
+        let subscribe = [("id", "0"), ("destination", "/queue/a")];
+        handle(&mut session, Command::Subscribe, &subscribe).unwrap();
+        handle(&mut session, Command::Unsubscribe, &[("id", "0")]).unwrap();
+
+        let rejection = handle(&mut session, Command::Unsubscribe, &[("id", "0")]).unwrap_err();
+        assert_eq!(
+            Violation::UnknownSubscription(SubscriptionId::from("0")),
+            rejection.violation
+        );
+    }
+
+    #[test]
+    fn legacy_versions() {
+        let mut session = Session::new("s-1");
+        handle(&mut session, Command::Connect, &[]).unwrap();
+        assert_eq!(Some(Version::V1_0), session.version());
+
+        let subscribe = [("destination", "/queue/a")];
+        handle(&mut session, Command::Subscribe, &subscribe).unwrap();
+        assert!(session
+            .subscriptions()
+            .contains(&SubscriptionId::from("/queue/a")));
+        handle(&mut session, Command::Ack, &[("message-id", "m-1")]).unwrap();
+        handle(&mut session, Command::Unsubscribe, &subscribe).unwrap();
+        assert!(session.subscriptions().is_empty());
+
+        let rejection = handle(&mut session, Command::Unsubscribe, &[]).unwrap_err();
+        assert_eq!(
+            Violation::MissingHeader {
+                command: Command::Unsubscribe,
+                header: "id"
+            },
+            rejection.violation
+        );
+
+        let mut session = Session::new("s-2").versions(&[Version::V1_1]);
+        let connect = [("accept-version", "1.1"), ("host", "/")];
+        handle(&mut session, Command::Connect, &connect).unwrap();
+        let subscribe = [("id", "0"), ("destination", "/queue/a")];
+        handle(&mut session, Command::Subscribe, &subscribe).unwrap();
+
+        let ack = [("message-id", "m-1"), ("subscription", "0")];
+        handle(&mut session, Command::Ack, &ack).unwrap();
+        handle(&mut session, Command::Nack, &ack).unwrap();
+        let rejection = handle(&mut session, Command::Ack, &[("id", "m-1")]).unwrap_err();
+        assert_eq!(
+            Violation::MissingHeader {
+                command: Command::Ack,
+                header: "message-id"
+            },
+            rejection.violation
+        );
+    }
+
+    #[test]
+    fn transactions() {
+        let mut session = connected();
+        handle(&mut session, Command::Begin, &[("transaction", "tx")]).unwrap();
+        assert!(session.in_transaction("tx"));
+
+        let send = [("destination", "/queue/a"), ("transaction", "tx")];
+        handle(&mut session, Command::Send, &send).unwrap();
+        handle(&mut session, Command::Commit, &[("transaction", "tx")]).unwrap();
+        assert!(!session.in_transaction("tx"));
+
+        let rejection = handle(&mut session, Command::Abort, &[("transaction", "tx")]).unwrap_err();
+        assert_eq!(
+            Violation::UnknownTransaction("tx".to_owned()),
+            rejection.violation
+        );
+    }
+
+    #[test]
+    fn duplicate_transaction() {
+        let mut session = connected();
+        handle(&mut session, Command::Begin, &[("transaction", "tx")]).unwrap();
+        let rejection = handle(&mut session, Command::Begin, &[("transaction", "tx")]).unwrap_err();
+        assert_eq!(
+            Violation::DuplicateTransaction("tx".to_owned()),
+            rejection.violation
+        );
+    }
+
+    #[test]
+    fn disconnect() {
+        let mut session = connected();
+        let response = handle(&mut session, Command::Disconnect, &[("receipt", "r-1")])
+            .unwrap()
+            .unwrap();
+        assert_eq!(Command::Receipt, response.command);
+        assert_eq!(SessionState::Closed, session.state());
+
+        let rejection = handle(&mut session, Command::Send, &[("destination", "/a")]).unwrap_err();
+        assert_eq!(Violation::Closed(Command::Send), rejection.violation);
+    }
+
+    #[test]
+    fn client_sends_server_frame() {
+        let mut session = connected();
+        let frame = Frame::new(Command::Message, Header::new(), Body::empty());
+        let rejection = session.handle(&frame).unwrap_err();
+        assert_eq!(
+            Violation::UnexpectedCommand(Command::Message),
+            rejection.violation
+        );
+    }
+
+    #[test]
+    fn quotas() {
+        let tracker = Arc::new(QuotaTracker::new(Quota {
+            max_connections: Some(1),
+            bytes_per_day: Some(10),
+            ..Quota::default()
+        }));
+        let connect = [
+            ("accept-version", "1.2"),
+            ("host", "/"),
+            ("login", "team-a"),
+        ];
+        let mut session = Session::new("s-1").quotas(tracker.clone());
+        handle(&mut session, Command::Connect, &connect).unwrap();
+        assert_eq!(Some("team-a"), session.principal());
+
+        let mut second = Session::new("s-2").quotas(tracker.clone());
+        let rejection = handle(&mut second, Command::Connect, &connect).unwrap_err();
+        assert!(matches!(
+            rejection.violation,
+            Violation::QuotaExceeded(QuotaExceeded::Connections { limit: 1 })
+        ));
+
+        let send = [("destination", "/a"), ("content-length", "6")];
+        handle(&mut session, Command::Send, &send).unwrap();
+        let rejection = handle(&mut session, Command::Send, &send).unwrap_err();
+        assert!(matches!(
+            rejection.violation,
+            Violation::QuotaExceeded(QuotaExceeded::Bytes { limit: 10 })
+        ));
+        assert_eq!(
+            Usage {
+                connections: 0,
+                sends: 1,
+                bytes: 6,
+                rejected: 2,
+            },
+            tracker.usage("team-a")
+        );
+
+        let mut third = Session::new("s-3").quotas(tracker.clone());
+        handle(&mut third, Command::Connect, &connect).unwrap();
+        drop(third);
+        assert_eq!(0, tracker.usage("team-a").connections);
+    }
+
+    #[test]
+    fn vhost_connections() {
+        let vhosts = Arc::new(VhostTracker::new(VhostQuota::default()));
+        vhosts.set_quota(
+            "tenant-a",
+            VhostQuota {
+                max_connections: Some(1),
+                ..VhostQuota::default()
+            },
+        );
+        let principals = Arc::new(QuotaTracker::new(Quota {
+            max_connections: Some(1),
+            ..Quota::default()
+        }));
+        let connect = |session: &mut Session, host, login| {
+            let connect = [("accept-version", "1.2"), ("host", host), ("login", login)];
+            handle(session, Command::Connect, &connect)
+        };
+        let session = |id| {
+            Session::new(id)
+                .vhost_quotas(vhosts.clone())
+                .quotas(principals.clone())
+        };
+
+        let mut first = session("s-1");
+        connect(&mut first, "tenant-a", "alice").unwrap();
+        assert_eq!(Some("tenant-a"), first.vhost());
+
+        let rejection = connect(&mut session("s-2"), "tenant-a", "bob").unwrap_err();
+        assert!(matches!(
+            rejection.violation,
+            Violation::QuotaExceeded(QuotaExceeded::Connections { limit: 1 })
+        ));
+        assert_eq!(0, principals.usage("bob").connections);
+
+        let mut third = session("s-3");
+        connect(&mut third, "tenant-b", "carol").unwrap();
+        connect(&mut session("s-4"), "tenant-b", "alice").unwrap_err();
+        assert_eq!(1, vhosts.usage("tenant-b").connections);
+        assert_eq!(1, vhosts.usage("tenant-a").connections);
+
+        drop(first);
+        assert_eq!(0, vhosts.usage("tenant-a").connections);
+        connect(&mut session("s-5"), "tenant-a", "bob").unwrap();
+    }
+
+    #[test]
+    fn vhost_destinations() {
+        let vhosts = Arc::new(VhostTracker::new(VhostQuota {
+            max_destinations: Some(1),
+            ..VhostQuota::default()
+        }));
+        let connect = || {
+            let mut session = Session::new("s-1").vhost_quotas(vhosts.clone());
+            let connect = [("accept-version", "1.2"), ("host", "tenant-a")];
+            handle(&mut session, Command::Connect, &connect).unwrap();
+            session
+        };
+        let subscribe = |session: &mut Session, id, destination| {
+            handle(
+                session,
+                Command::Subscribe,
+                &[("id", id), ("destination", destination)],
+            )
+        };
+
+        let mut first = connect();
+        subscribe(&mut first, "0", "/queue/a").unwrap();
+        let mut second = connect();
+        subscribe(&mut second, "0", "/queue/a").unwrap();
+
+        let rejection = subscribe(&mut second, "1", "/queue/b").unwrap_err();
+        assert!(matches!(
+            rejection.violation,
+            Violation::QuotaExceeded(QuotaExceeded::Destinations { limit: 1 })
+        ));
+        assert_eq!(
+            Some("quota exceeded: more than 1 destinations"),
+            rejection.error.header.first("message")
+        );
+        assert_eq!(1, vhosts.usage("tenant-a").destinations);
+
+        handle(&mut first, Command::Unsubscribe, &[("id", "0")]).unwrap();
+        assert_eq!(0, vhosts.usage("tenant-a").destinations);
+
+        subscribe(&mut connect(), "0", "/queue/b").unwrap();
+        subscribe(&mut connect(), "0", "/queue/c").unwrap();
+        assert_eq!(0, vhosts.usage("tenant-a").destinations);
+    }
+
+    #[test]
+    fn vhost_queued_bytes() {
+        let vhosts = Arc::new(VhostTracker::new(VhostQuota {
+            max_queued_bytes: Some(10),
+            ..VhostQuota::default()
+        }));
+        let principals = Arc::new(QuotaTracker::new(Quota {
+            sends_per_second: Some(1),
+            ..Quota::default()
+        }));
+        let connect = |principals: Option<&Arc<QuotaTracker>>| {
+            let mut session = Session::new("s-1").vhost_quotas(vhosts.clone());
+
+            if let Some(principals) = principals {
+                session = session.quotas(principals.clone());
+            }
+            let connect = [("accept-version", "1.2"), ("host", "tenant-a")];
+            handle(&mut session, Command::Connect, &connect).unwrap();
+            session
+        };
+        let send = [("destination", "/a"), ("content-length", "6")];
+
+        let mut session = connect(Some(&principals));
+        handle(&mut session, Command::Send, &send).unwrap();
+        let rejection = handle(&mut session, Command::Send, &send).unwrap_err();
+        assert!(matches!(
+            rejection.violation,
+            Violation::QuotaExceeded(QuotaExceeded::QueuedBytes { limit: 10 })
+        ));
+
+        // Refused by the principal's rate, so not left queued on the vhost.
+        let small = [("destination", "/a"), ("content-length", "1")];
+        let rejection = handle(&mut connect(Some(&principals)), Command::Send, &small).unwrap_err();
+        assert!(matches!(
+            rejection.violation,
+            Violation::QuotaExceeded(QuotaExceeded::SendRate { limit: 1 })
+        ));
+        assert_eq!(6, vhosts.usage("tenant-a").queued_bytes);
+
+        vhosts.dequeue("tenant-a", 6);
+        handle(&mut connect(None), Command::Send, &send).unwrap();
+
+        let rejection =
+            handle(&mut connect(None), Command::Send, &[("destination", "/a")]).unwrap_err();
+        assert_eq!(
+            Violation::MissingHeader {
+                command: Command::Send,
+                header: "content-length"
+            },
+            rejection.violation
+        );
+        assert_eq!(
+            VhostUsage {
+                connections: 0,
+                destinations: 0,
+                queued_bytes: 6,
+                rejected: 1,
+            },
+            vhosts.usage("tenant-a")
+        );
+    }
+
+    #[test]
+    fn byte_quota_needs_content_length() {
+        let tracker = Arc::new(QuotaTracker::new(Quota {
+            bytes_per_day: Some(10),
+            ..Quota::default()
+        }));
+        tracker.set_quota("ops", Quota::default());
+        let connect = |login| {
+            let mut session = Session::new("s-1").quotas(tracker.clone());
+            let connect = [("accept-version", "1.2"), ("host", "/"), ("login", login)];
+            handle(&mut session, Command::Connect, &connect).unwrap();
+            session
+        };
+
+        let rejection = handle(
+            &mut connect("team-a"),
+            Command::Send,
+            &[("destination", "/a")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            Violation::MissingHeader {
+                command: Command::Send,
+                header: "content-length"
+            },
+            rejection.violation
+        );
+
+        let send = [("destination", "/a"), ("content-length", "-1")];
+        let rejection = handle(&mut connect("team-a"), Command::Send, &send).unwrap_err();
+        assert_eq!(
+            Violation::InvalidHeader {
+                header: "content-length",
+                value: "-1".to_owned()
+            },
+            rejection.violation
+        );
+        handle(&mut connect("ops"), Command::Send, &[("destination", "/a")]).unwrap();
+        assert_eq!(0, tracker.usage("team-a").sends);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::destination::Destination;
+use crate::frame::AckMode;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId(String);
+
+impl SubscriptionId {
+    pub fn new<T: Into<String>>(id: T) -> Self {
+        SubscriptionId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for SubscriptionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SubscriptionId({})", self.0)
+    }
+}
+
+impl Display for SubscriptionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for SubscriptionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for SubscriptionId {
+    fn from(id: &str) -> Self {
+        SubscriptionId::new(id)
+    }
+}
+
+impl From<String> for SubscriptionId {
+    fn from(id: String) -> Self {
+        SubscriptionId::new(id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateSubscription(pub SubscriptionId);
+
+impl Display for DuplicateSubscription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "subscription {} already exists", self.0)
+    }
+}
+
+impl Error for DuplicateSubscription {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionEntry {
+    pub destination: Destination,
+    pub ack: AckMode,
+}
+
+/// Tracks the active subscriptions of one connection, on either side of it.
+#[derive(Default)]
+pub struct SubscriptionRegistry {
+    entries: BTreeMap<SubscriptionId, SubscriptionEntry>,
+    next_id: u64,
+}
+
+impl SubscriptionRegistry {
+    pub fn new() -> Self {
+        SubscriptionRegistry::default()
+    }
+
+    /// Returns an id that is not currently registered.
+    pub fn next_id(&mut self) -> SubscriptionId {
+        loop {
+            let id = SubscriptionId(format!("sub-{}", self.next_id));
+            self.next_id += 1;
+
+            if !self.entries.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        id: SubscriptionId,
+        destination: Destination,
+        ack: AckMode,
+    ) -> Result<(), DuplicateSubscription> {
+        if self.entries.contains_key(&id) {
+            return Err(DuplicateSubscription(id));
+        }
+        self.entries
+            .insert(id, SubscriptionEntry { destination, ack });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &SubscriptionId) -> Option<SubscriptionEntry> {
+        self.entries.remove(id)
+    }
+
+    pub fn get(&self, id: &SubscriptionId) -> Option<&SubscriptionEntry> {
+        self.entries.get(id)
+    }
+
+    pub fn contains(&self, id: &SubscriptionId) -> bool {
+        self.entries.contains_key(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SubscriptionId, &SubscriptionEntry)> {
+        self.entries.iter()
+    }
+}
+
+impl Debug for SubscriptionRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.entries
+                    .iter()
+                    .map(|(id, e)| (id.as_str(), format!("{} ({})", e.destination, e.ack))),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_duplicate() {
+        let mut registry = SubscriptionRegistry::new();
+        let destination = Destination::new("/queue/a").unwrap();
+        registry
+            .insert("1".into(), destination.clone(), AckMode::Auto)
+            .unwrap();
+
+        let result = registry.insert("1".into(), destination, AckMode::Client);
+        assert_eq!(Err(DuplicateSubscription("1".into())), result);
+        assert_eq!(AckMode::Auto, registry.get(&"1".into()).unwrap().ack);
+    }
+
+    #[test]
+    fn next_id_skips_registered() {
+        let mut registry = SubscriptionRegistry::new();
+        let destination = Destination::new("/queue/a").unwrap();
+        registry
+            .insert("sub-0".into(), destination, AckMode::Auto)
+            .unwrap();
+        assert_eq!(SubscriptionId::new("sub-1"), registry.next_id());
+    }
+
+    #[test]
+    fn remove() {
+        let mut registry = SubscriptionRegistry::new();
+        let destination = Destination::new("/queue/a").unwrap();
+        let id = registry.next_id();
+        registry
+            .insert(id.clone(), destination, AckMode::ClientIndividual)
+            .unwrap();
+        assert_eq!(1, registry.len());
+        assert!(registry.remove(&id).is_some());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn debug() {
+        let mut registry = SubscriptionRegistry::new();
+        let destination = Destination::new("/queue/a").unwrap();
+        registry
+            .insert("1".into(), destination, AckMode::Client)
+            .unwrap();
+        assert_eq!("{\"1\": \"/queue/a (client)\"}", format!("{:?}", registry));
+        assert_eq!(
+            "SubscriptionId(1)",
+            format!("{:?}", SubscriptionId::new("1"))
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::str::Chars;
+
+/// A parsed JSON document, compared structurally: object keys in any order, whitespace ignored
+/// and numbers by value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+pub fn parse(input: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: input.chars(),
+        peeked: None,
+    };
+    let value = parser.value()?;
+
+    match parser.next_token() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected {:?} after the document", c)),
+    }
+}
+
+struct Parser<'a> {
+    chars: Chars<'a>,
+    peeked: Option<char>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<char> {
+        self.peeked.take().or_else(|| self.chars.next())
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        if self.peeked.is_none() {
+            self.peeked = self.chars.next();
+        }
+        self.peeked
+    }
+
+    /// Skips whitespace and returns the next character.
+    fn next_token(&mut self) -> Option<char> {
+        loop {
+            match self.next() {
+                Some(' ') | Some('\t') | Some('\n') | Some('\r') => (),
+                c => return c,
+            }
+        }
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        for expected in word.chars() {
+            if self.next() != Some(expected) {
+                return Err(format!("expected {}", word));
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.next_token() {
+            Some('n') => self.expect("ull").map(|_| Value::Null),
+            Some('t') => self.expect("rue").map(|_| Value::Bool(true)),
+            Some('f') => self.expect("alse").map(|_| Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(c),
+            Some(c) => Err(format!("unexpected {:?}", c)),
+            None => Err("unexpected end of input".to_owned()),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        let mut values = Vec::new();
+
+        if self.skip_whitespace_to(']') {
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            match self.next_token() {
+                Some(',') => (),
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return Err("expected , or ] in array".to_owned()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        let mut entries = BTreeMap::new();
+
+        if self.skip_whitespace_to('}') {
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            if self.next_token() != Some('"') {
+                return Err("expected a string key in object".to_owned());
+            }
+            let key = self.string()?;
+
+            if self.next_token() != Some(':') {
+                return Err(format!("expected : after key {:?}", key));
+            }
+            entries.insert(key, self.value()?);
+
+            match self.next_token() {
+                Some(',') => (),
+                Some('}') => return Ok(Value::Object(entries)),
+                _ => return Err("expected , or } in object".to_owned()),
+            }
+        }
+    }
+
+    /// Consumes whitespace, then `close` if it comes next, returning whether it did.
+    fn skip_whitespace_to(&mut self, close: char) -> bool {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.next();
+        }
+
+        if self.peek() == Some(close) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut output = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(output),
+                Some('\\') => match self.next() {
+                    Some('"') => output.push('"'),
+                    Some('\\') => output.push('\\'),
+                    Some('/') => output.push('/'),
+                    Some('b') => output.push('\u{8}'),
+                    Some('f') => output.push('\u{c}'),
+                    Some('n') => output.push('\n'),
+                    Some('r') => output.push('\r'),
+                    Some('t') => output.push('\t'),
+                    Some('u') => output.push(self.unicode_escape()?),
+                    c => return Err(format!("invalid escape {:?}", c)),
+                },
+                Some(c) => output.push(c),
+                None => return Err("unterminated string".to_owned()),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+
+        if !(0xD800..0xDC00).contains(&high) {
+            return std::char::from_u32(high).ok_or_else(|| "invalid \\u escape".to_owned());
+        }
+        self.expect("\\u")?;
+        let low = self.hex4()?;
+
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err("invalid surrogate pair".to_owned());
+        }
+        std::char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| "invalid surrogate pair".to_owned())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+
+        for _ in 0..4 {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| "invalid \\u escape".to_owned())?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self, first: char) -> Result<Value, String> {
+        let mut text = first.to_string();
+
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-')) {
+                break;
+            }
+            text.push(c);
+            self.next();
+        }
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| format!("invalid number {}", text))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_documents() {
+        assert_eq!(
+            parse(r#"{"b": [1, 2.0e0, null], "a": {"x": "é\n😀"}}"#),
+            parse("{\"a\":{\"x\":\"é\\n😀\"},\"b\":[1,2,null]}")
+        );
+        assert_eq!(Ok(Value::Bool(true)), parse(" true "));
+        assert_ne!(parse("[1, 2]"), parse("[2, 1]"));
+
+        for input in ["", "{", "[1,]", "{\"a\" 1}", "tru", "\"a", "1 2"].iter() {
+            assert!(parse(input).is_err(), "{:?}", input);
+        }
+    }
+}
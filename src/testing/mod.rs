@@ -0,0 +1,261 @@
+//! Assertions over frames for tests, such as those driving a `server::Session` as a mock broker.
+//! Compiled for the crate's own tests and with the `testing` feature.
+
+mod json;
+
+use crate::frame::{Command, OwnedFrame};
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str;
+
+/// Checks a frame against an expected command, headers and body, and panics with what did not
+/// match. Each header is given as `name => value`; a name may be listed more than once to
+/// require each of its values. The body is matched by one of the `ExpectedFrame` methods:
+/// `body`, `contains`, `json` or `matches`, as in
+/// `assert_frame!(frame, Command::Send, { "destination" => "/queue/a" }, json = "{\"id\":1}")`.
+/// The frame is an `OwnedFrame`, such as a reply from `Session::handle`.
+#[macro_export]
+macro_rules! assert_frame {
+    ($frame:expr, $command:expr $(, { $($name:expr => $value:expr),* $(,)? })? $(, $matcher:ident = $body:expr)? $(,)?) => {{
+        let expected = $crate::testing::ExpectedFrame::new($command)
+            $($(.header($name, $value))*)?
+            $(.$matcher($body))?;
+
+        if let Err(e) = expected.check(&$frame) {
+            panic!("frame did not match: {}", e);
+        }
+    }};
+}
+
+type Predicate = Box<dyn Fn(&[u8]) -> bool>;
+
+enum BodyMatcher {
+    Equals(Vec<u8>),
+    Contains(Vec<u8>),
+    Json(json::Value),
+    Matches(Predicate),
+}
+
+/// What a frame is expected to look like, checked with `ExpectedFrame::check`. Headers not
+/// listed are not checked.
+pub struct ExpectedFrame {
+    command: Command,
+    headers: Vec<(String, String)>,
+    body: Option<BodyMatcher>,
+}
+
+impl ExpectedFrame {
+    pub fn new(command: Command) -> Self {
+        ExpectedFrame {
+            command,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Requires `name` to have `value` among its values.
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Requires the body to be exactly `body`.
+    pub fn body<B: AsRef<[u8]>>(mut self, body: B) -> Self {
+        self.body = Some(BodyMatcher::Equals(body.as_ref().to_vec()));
+        self
+    }
+
+    /// Requires the body to contain `part`.
+    pub fn contains<B: AsRef<[u8]>>(mut self, part: B) -> Self {
+        self.body = Some(BodyMatcher::Contains(part.as_ref().to_vec()));
+        self
+    }
+
+    /// Requires the body to be a JSON document equal to `document`, ignoring whitespace and the
+    /// order of object keys.
+    ///
+    /// # Panics
+    ///
+    /// If `document` is not valid JSON.
+    pub fn json(mut self, document: &str) -> Self {
+        let value = json::parse(document)
+            .unwrap_or_else(|e| panic!("invalid expected JSON {:?}: {}", document, e));
+        self.body = Some(BodyMatcher::Json(value));
+        self
+    }
+
+    /// Requires `predicate` to accept the body, for checks the other matchers do not cover,
+    /// such as a regular expression from a crate the tests depend on.
+    pub fn matches<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + 'static,
+    {
+        self.body = Some(BodyMatcher::Matches(Box::new(predicate)));
+        self
+    }
+
+    pub fn check(&self, frame: &OwnedFrame) -> Result<(), Mismatch> {
+        if frame.command != self.command {
+            return Err(Mismatch::Command {
+                expected: self.command,
+                got: frame.command,
+            });
+        }
+
+        for (name, value) in &self.headers {
+            let values = frame.header.get(name).cloned().unwrap_or_default();
+
+            if !values.contains(value) {
+                return Err(Mismatch::Header {
+                    name: name.clone(),
+                    expected: value.clone(),
+                    got: values,
+                });
+            }
+        }
+        let body = &frame.body;
+        let (matched, expected) = match &self.body {
+            None => return Ok(()),
+            Some(BodyMatcher::Equals(expected)) => (body == expected, printable(expected)),
+            Some(BodyMatcher::Contains(part)) => (
+                part.is_empty() || body.windows(part.len()).any(|w| w == &part[..]),
+                format!("containing {}", printable(part)),
+            ),
+            Some(BodyMatcher::Json(expected)) => (
+                str::from_utf8(body)
+                    .ok()
+                    .and_then(|s| json::parse(s).ok())
+                    .as_ref()
+                    == Some(expected),
+                format!("JSON equal to {:?}", expected),
+            ),
+            Some(BodyMatcher::Matches(predicate)) => {
+                (predicate(body), "accepted by the predicate".to_owned())
+            }
+        };
+
+        if !matched {
+            return Err(Mismatch::Body {
+                expected,
+                got: printable(body),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Command {
+        expected: Command,
+        got: Command,
+    },
+    Header {
+        name: String,
+        expected: String,
+        got: Vec<String>,
+    },
+    Body {
+        expected: String,
+        got: String,
+    },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::Mismatch::*;
+
+        match self {
+            Command { expected, got } => write!(f, "expected a {} frame, got {}", expected, got),
+            Header {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "expected header {} to have value {:?}, got {:?}",
+                name, expected, got
+            ),
+            Body { expected, got } => write!(f, "expected body {}, got {}", expected, got),
+        }
+    }
+}
+
+impl Error for Mismatch {}
+
+/// The body as a quoted string if it is UTF-8, and as bytes otherwise.
+fn printable(body: &[u8]) -> String {
+    match str::from_utf8(body) {
+        Ok(s) => format!("{:?}", s),
+        Err(_) => format!("{:?}", body),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Body, Frame, Header};
+    use crate::server::Session;
+
+    fn send(destination: &str, body: &[u8]) -> OwnedFrame {
+        let mut header = Header::new();
+        header.push("destination", destination.to_owned());
+        header.push("x", "1".to_owned());
+        header.push("x", "2".to_owned());
+        OwnedFrame::new(Command::Send, header, body.to_vec())
+    }
+
+    #[test]
+    fn check() {
+        let frame = send("/queue/a", br#"{"a": [1, 2], "b": null}"#);
+
+        assert_frame!(frame, Command::Send, { "destination" => "/queue/a", "x" => "2" });
+        assert_frame!(frame, Command::Send, {}, json = r#"{"b":null,"a":[1,2]}"#);
+        assert_frame!(frame, Command::Send, {}, contains = "[1, 2]");
+        assert_frame!(frame, Command::Send, {}, matches = |b: &[u8]| b.len() == 24);
+
+        let mismatches = [
+            ExpectedFrame::new(Command::Message),
+            ExpectedFrame::new(Command::Send).header("x", "3"),
+            ExpectedFrame::new(Command::Send).header("missing", ""),
+            ExpectedFrame::new(Command::Send).body("{}"),
+            ExpectedFrame::new(Command::Send).json(r#"{"a": [2, 1], "b": null}"#),
+            ExpectedFrame::new(Command::Send).contains("[2"),
+        ];
+
+        for expected in mismatches.iter() {
+            assert!(expected.check(&frame).is_err());
+        }
+        assert_eq!(
+            Err(Mismatch::Header {
+                name: "x".to_owned(),
+                expected: "3".to_owned(),
+                got: vec!["1".to_owned(), "2".to_owned()],
+            }),
+            mismatches[1].check(&frame)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected body \"b\", got \"a\"")]
+    fn assert_frame_panics() {
+        assert_frame!(send("/queue/a", b"a"), Command::Send, {}, body = "b");
+    }
+
+    #[test]
+    fn session_replies() {
+        let mut session = Session::new("s-1").server("test");
+        let mut header = Header::new();
+        header.push("accept-version", "1.2".to_owned());
+        header.push("host", "/".to_owned());
+        let connect = Frame::new(Command::Connect, header, Body::empty());
+
+        let reply = session.handle(&connect).unwrap().unwrap();
+        assert_frame!(reply, Command::Connected, {
+            "version" => "1.2",
+            "session" => "s-1",
+            "server" => "test",
+        });
+    }
+}
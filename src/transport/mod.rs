@@ -0,0 +1,40 @@
+pub mod websocket;
+
+use std::io;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+/// A connection a `Client` can run over. The client reads and writes through separate handles
+/// from `Transport::try_clone`, so that a blocked read does not hold up writes.
+///
+/// The crate only implements this for `TcpStream`. For TLS, implement it over the stream type of
+/// a TLS library, with handles that share one session.
+pub trait Transport: Read + Write {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>;
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Sends a heart-beat for `Client::send_heartbeat`. STOMP's is a single EOL; a transport
+    /// with keepalives of its own, such as WebSocket pings, can send one of those instead.
+    fn send_heartbeat(&mut self) -> io::Result<()> {
+        self.write_all(b"\n")?;
+        self.flush()
+    }
+}
+
+impl Transport for TcpStream {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(TcpStream::try_clone(self)?))
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
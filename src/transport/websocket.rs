@@ -0,0 +1,699 @@
+use crate::transport::Transport;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{Shutdown, TcpStream};
+use std::str;
+use std::time::Duration;
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+const FIN: u8 = 0x80;
+const MASKED: u8 = 0x80;
+const NORMAL_CLOSURE: [u8; 2] = [0x03, 0xe8];
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_RESPONSE_HEAD: usize = 8192;
+const MAX_CONTROL_PAYLOAD: u64 = 125;
+
+/// A WebSocket connection that carries STOMP, such as RabbitMQ Web-STOMP. Reading yields the
+/// payloads of incoming data messages as one stream, so a `FrameReader` can parse frames split
+/// across or packed into messages. Writing buffers bytes until `flush`, which sends them as one
+/// message; `FrameWriter` flushes after every frame, so each frame becomes a message.
+///
+/// Pings are answered while reading, and a close message ends the stream. A frame head cut
+/// short by a read timeout is kept and the next read picks up where it stopped.
+pub struct WebSocket<S: Read + Write> {
+    stream: S,
+    client: bool,
+    opcode: u8,
+    ping_heartbeats: bool,
+    head: Vec<u8>,
+    remaining: u64,
+    mask: Option<[u8; 4]>,
+    mask_offset: usize,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+impl<S: Read + Write> WebSocket<S> {
+    /// Wraps the client side of an upgraded connection. Outgoing messages are masked.
+    pub fn client(stream: S) -> Self {
+        WebSocket::new(stream, true)
+    }
+
+    /// Wraps the server side of an upgraded connection.
+    pub fn server(stream: S) -> Self {
+        WebSocket::new(stream, false)
+    }
+
+    fn new(stream: S, client: bool) -> Self {
+        WebSocket {
+            stream,
+            client,
+            opcode: BINARY,
+            ping_heartbeats: false,
+            head: Vec::new(),
+            remaining: 0,
+            mask: None,
+            mask_offset: 0,
+            outgoing: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Sends text messages instead of binary ones. Frame bodies must then be valid UTF-8, and
+    /// flushing a message that is not fails with `io::ErrorKind::InvalidData`.
+    pub fn text_messages(mut self) -> Self {
+        self.opcode = TEXT;
+        self
+    }
+
+    /// Sends heart-beats as WebSocket pings instead of messages holding an EOL, for gateways
+    /// that keep connections alive with pings and do not pass EOLs on to the broker.
+    pub fn ping_heartbeats(mut self) -> Self {
+        self.ping_heartbeats = true;
+        self
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Sends a close message. Reading continues until the peer answers with its own.
+    pub fn close(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.send(CLOSE, &NORMAL_CLOSURE)
+    }
+
+    /// Sends a heart-beat: a ping with `WebSocket::ping_heartbeats`, and otherwise a message
+    /// holding a single EOL. Buffered bytes are sent first.
+    pub fn keepalive(&mut self) -> io::Result<()> {
+        if self.ping_heartbeats {
+            self.flush()?;
+            return self.send(PING, &[]);
+        }
+        self.write_all(b"\n")?;
+        self.flush()
+    }
+
+    /// Reads frame headers until the next data frame, answering control frames on the way.
+    /// Returns false at the end of the stream.
+    fn next_data_frame(&mut self) -> io::Result<bool> {
+        if !self.fill_head(2)? {
+            return Ok(false);
+        }
+        let opcode = self.head[0] & 0x0f;
+        let masked = self.head[1] & MASKED != 0;
+        let (length_end, length) = match self.head[1] & 0x7f {
+            126 => (4, None),
+            127 => (10, None),
+            n => (2, Some(u64::from(n))),
+        };
+        let mask_end = length_end + if masked { 4 } else { 0 };
+        self.fill_head(mask_end)?;
+
+        if !self.client && !masked {
+            return Err(invalid_data("unmasked WebSocket frame from a client"));
+        }
+        let length = match length {
+            Some(n) => n,
+            None if length_end == 4 => u64::from(u16::from_be_bytes([self.head[2], self.head[3]])),
+            None => {
+                let mut length = [0; 8];
+                length.copy_from_slice(&self.head[2..10]);
+                u64::from_be_bytes(length)
+            }
+        };
+        let mask = if masked {
+            let mut mask = [0; 4];
+            mask.copy_from_slice(&self.head[length_end..mask_end]);
+            Some(mask)
+        } else {
+            None
+        };
+
+        match opcode {
+            CONTINUATION | TEXT | BINARY => {
+                self.head.clear();
+                self.remaining = length;
+                self.mask = mask;
+                self.mask_offset = 0;
+                Ok(true)
+            }
+            CLOSE | PING | PONG if length <= MAX_CONTROL_PAYLOAD => {
+                self.fill_head(mask_end + length as usize)?;
+                let mut payload = self.head.split_off(mask_end);
+                self.head.clear();
+                apply_mask(&mut payload, mask, 0);
+
+                match opcode {
+                    PING => self.send(PONG, &payload)?,
+                    CLOSE => {
+                        if !self.closed {
+                            self.closed = true;
+                            self.send(CLOSE, &payload[..payload.len().min(2)])?;
+                        }
+                        return Ok(false);
+                    }
+                    _ => (),
+                }
+                Ok(true)
+            }
+            CLOSE | PING | PONG => Err(invalid_data("WebSocket control frame too long")),
+            _ => Err(invalid_data(format!("unknown WebSocket opcode {}", opcode))),
+        }
+    }
+
+    /// Reads the frame head, and the payload of a control frame, until `self.head` holds `len`
+    /// bytes. What was read is kept if a read fails, so a read that timed out can be resumed.
+    /// Returns false at the end of the stream before a new frame.
+    fn fill_head(&mut self, len: usize) -> io::Result<bool> {
+        let mut buf = [0; 14 + MAX_CONTROL_PAYLOAD as usize];
+
+        while self.head.len() < len {
+            let bytes_read = self.stream.read(&mut buf[..len - self.head.len()])?;
+
+            if bytes_read == 0 {
+                if self.head.is_empty() {
+                    return Ok(false);
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.head.extend_from_slice(&buf[..bytes_read]);
+        }
+        Ok(true)
+    }
+
+    fn send(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame: Vec<u8> = Vec::with_capacity(payload.len() + 14);
+        frame.push(FIN | opcode);
+        let mask_bit = if self.client { MASKED } else { 0 };
+
+        if payload.len() < 126 {
+            frame.push(mask_bit | payload.len() as u8);
+        } else if payload.len() <= usize::from(u16::MAX) {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        } else {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        }
+        let start = frame.len();
+
+        if self.client {
+            let random = random_bytes();
+            let mask = [random[0], random[1], random[2], random[3]];
+            frame.extend_from_slice(&mask);
+            frame.extend_from_slice(payload);
+            apply_mask(&mut frame[start + 4..], Some(mask), 0);
+        } else {
+            frame.extend_from_slice(payload);
+        }
+        self.stream.write_all(&frame)?;
+        self.stream.flush()
+    }
+}
+
+impl<S: Read + Write> Read for WebSocket<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.remaining == 0 {
+            if self.closed || !self.next_data_frame()? {
+                return Ok(0);
+            }
+        }
+        let length = (buf.len() as u64).min(self.remaining) as usize;
+        let bytes_read = self.stream.read(&mut buf[..length])?;
+
+        if bytes_read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        apply_mask(&mut buf[..bytes_read], self.mask, self.mask_offset);
+        self.mask_offset += bytes_read;
+        self.remaining -= bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<S: Read + Write> Write for WebSocket<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.outgoing.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.outgoing.is_empty() {
+            return self.stream.flush();
+        }
+
+        if self.opcode == TEXT && str::from_utf8(&self.outgoing).is_err() {
+            self.outgoing.clear();
+            return Err(invalid_data("WebSocket text message is not valid UTF-8"));
+        }
+        let payload = mem::take(&mut self.outgoing);
+        self.send(self.opcode, &payload)
+    }
+}
+
+/// The read and write handles each keep their own message state, so both must be used from
+/// one thread at a time, as `Client` does. Shutting down closes the TCP stream without a
+/// WebSocket close message.
+impl Transport for WebSocket<TcpStream> {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        let mut clone = WebSocket::new(self.stream.try_clone()?, self.client);
+        clone.opcode = self.opcode;
+        clone.ping_heartbeats = self.ping_heartbeats;
+        Ok(Box::new(clone))
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    fn send_heartbeat(&mut self) -> io::Result<()> {
+        self.keepalive()
+    }
+}
+
+/// Performs the client side of the WebSocket opening handshake for `path` on `host`, asking for
+/// the STOMP subprotocols.
+pub fn connect<S: Read + Write>(stream: S, host: &str, path: &str) -> io::Result<WebSocket<S>> {
+    connect_with_key(stream, host, path, &base64(&random_bytes()))
+}
+
+fn connect_with_key<S: Read + Write>(
+    mut stream: S,
+    host: &str,
+    path: &str,
+    key: &str,
+) -> io::Result<WebSocket<S>> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Protocol: v12.stomp, v11.stomp, v10.stomp\r\n\r\n",
+        path, host, key
+    );
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let head = read_response_head(&mut stream)?;
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap_or_default();
+
+    if status.split(' ').nth(1) != Some("101") {
+        return Err(invalid_data(format!(
+            "WebSocket upgrade refused: {}",
+            status
+        )));
+    }
+    let accept = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-accept"))
+        .map(|(_, value)| value.trim());
+
+    if accept != Some(accept_key(key).as_str()) {
+        return Err(invalid_data("invalid Sec-WebSocket-Accept header"));
+    }
+    Ok(WebSocket::client(stream))
+}
+
+/// Reads the status line and headers of an HTTP response, byte by byte so that nothing after
+/// them is consumed.
+fn read_response_head<S: Read>(stream: &mut S) -> io::Result<String> {
+    let mut head: Vec<u8> = Vec::new();
+    let mut byte = [0; 1];
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_HEAD {
+            return Err(invalid_data("WebSocket handshake response too long"));
+        }
+        stream.read_exact(&mut byte)?;
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).map_err(invalid_data)
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+fn apply_mask(data: &mut [u8], mask: Option<[u8; 4]>, offset: usize) {
+    if let Some(mask) = mask {
+        for (i, b) in data.iter_mut().enumerate() {
+            *b ^= mask[(offset + i) % 4];
+        }
+    }
+}
+
+/// Sixteen unpredictable bytes, from the randomly seeded keys of `RandomState`.
+fn random_bytes() -> [u8; 16] {
+    let mut bytes = [0; 16];
+
+    for chunk in bytes.chunks_mut(8) {
+        let value = RandomState::new().build_hasher().finish();
+        chunk.copy_from_slice(&value.to_ne_bytes());
+    }
+    bytes
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// SHA-1, which the handshake uses to prove the server understood the request. It is not used
+/// for anything security sensitive.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *s = s.wrapping_add(*v);
+        }
+    }
+    let mut digest = [0; 20];
+
+    for (chunk, s) in digest.chunks_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&s.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Body, Command, Frame, FrameReader, FrameWriter, Header};
+    use std::io::Cursor;
+
+    /// Reads from one buffer and writes to another.
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Duplex {
+        fn new(input: Vec<u8>) -> Self {
+            Duplex {
+                input: Cursor::new(input),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Reads the given chunks one at a time, with `None` standing for a read that timed out.
+    struct Chunked {
+        chunks: Vec<Option<Vec<u8>>>,
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+
+            match self.chunks.remove(0) {
+                Some(mut chunk) => {
+                    let n = chunk.len().min(buf.len());
+                    buf[..n].copy_from_slice(&chunk[..n]);
+
+                    if n < chunk.len() {
+                        self.chunks.insert(0, Some(chunk.split_off(n)));
+                    }
+                    Ok(n)
+                }
+                None => Err(io::ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    impl Write for Chunked {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A frame as a client sends it, masked.
+    fn masked(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![FIN | opcode, MASKED | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend_from_slice(payload);
+        apply_mask(&mut frame[6..], Some(mask), 0);
+        frame
+    }
+
+    #[test]
+    fn accept_key_matches_rfc() {
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        assert_eq!("", base64(b""));
+        assert_eq!("Zm9vYg==", base64(b"foob"));
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut writer = FrameWriter::new(WebSocket::client(Duplex::new(Vec::new())));
+
+        for body in [&b"hello"[..], &b"world"[..]].iter() {
+            let mut header = Header::new();
+            header.push("destination", "/queue/a".to_owned());
+            let mut frame = Frame::new(Command::Send, header, Body::new(*body));
+            writer.write_frame(&mut frame).unwrap();
+        }
+        let sent = writer.get_ref().get_ref().output.clone();
+        assert_eq!(FIN | BINARY, sent[0]);
+        assert_ne!(0, sent[1] & MASKED);
+
+        let reader = FrameReader::new(WebSocket::server(Duplex::new(sent)));
+
+        for body in [&b"hello"[..], &b"world"[..]].iter() {
+            let mut frame = reader.read_frame().unwrap();
+            assert_eq!(Command::Send, frame.command);
+            let mut buffer: Vec<u8> = Vec::new();
+            frame.body.read_to_end(&mut buffer).unwrap();
+            assert_eq!(body.to_vec(), buffer);
+        }
+    }
+
+    #[test]
+    fn ping_and_close() {
+        let mut input = masked(PING, b"hi");
+        input.extend_from_slice(&masked(TEXT, b"abc"));
+        input.extend_from_slice(&masked(CLOSE, &[0x03, 0xe8]));
+        input.extend_from_slice(&masked(TEXT, b"x"));
+
+        let mut socket = WebSocket::server(Duplex::new(input));
+        let mut buffer: Vec<u8> = Vec::new();
+        socket.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"abc".to_vec(), buffer);
+        assert_eq!(
+            vec![FIN | PONG, 2, b'h', b'i', FIN | CLOSE, 2, 0x03, 0xe8],
+            socket.get_ref().output
+        );
+    }
+
+    #[test]
+    fn head_split_by_timeout() {
+        let frame = masked(BINARY, b"abc");
+        let ping = masked(PING, b"hi");
+        let chunks = vec![
+            Some(frame[..1].to_vec()),
+            None,
+            Some(frame[1..4].to_vec()),
+            None,
+            Some(frame[4..].to_vec()),
+            Some(ping[..7].to_vec()),
+            None,
+            Some(ping[7..].to_vec()),
+            Some(masked(BINARY, b"d")),
+        ];
+        let mut socket = WebSocket::server(Chunked { chunks });
+        let mut buffer = [0; 8];
+
+        for _ in 0..2 {
+            let error = socket.read(&mut buffer).unwrap_err();
+            assert_eq!(io::ErrorKind::TimedOut, error.kind());
+        }
+        assert_eq!(3, socket.read(&mut buffer).unwrap());
+        assert_eq!(b"abc", &buffer[..3]);
+
+        let error = socket.read(&mut buffer).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, error.kind());
+        assert_eq!(1, socket.read(&mut buffer).unwrap());
+        assert_eq!(b'd', buffer[0]);
+    }
+
+    #[test]
+    fn server_rejects_unmasked_frames() {
+        let mut socket = WebSocket::server(Duplex::new(vec![FIN | TEXT, 1, b'x']));
+        let error = socket.read(&mut [0; 8]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        let mut socket = WebSocket::client(Duplex::new(vec![FIN | TEXT, 1, b'x']));
+        assert_eq!(1, socket.read(&mut [0; 8]).unwrap());
+    }
+
+    #[test]
+    fn text_messages_must_be_utf8() {
+        let mut socket = WebSocket::server(Duplex::new(Vec::new())).text_messages();
+        socket.write_all(b"ok").unwrap();
+        socket.flush().unwrap();
+        assert_eq!(vec![FIN | TEXT, 2, b'o', b'k'], socket.get_ref().output);
+
+        socket.write_all(&[0xff]).unwrap();
+        let error = socket.flush().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        socket.flush().unwrap();
+        assert_eq!(4, socket.get_ref().output.len());
+    }
+
+    #[test]
+    fn heartbeats() {
+        let mut socket = WebSocket::server(Duplex::new(Vec::new()));
+        socket.keepalive().unwrap();
+        assert_eq!(vec![FIN | BINARY, 1, b'\n'], socket.get_ref().output);
+
+        let mut socket = WebSocket::server(Duplex::new(Vec::new())).ping_heartbeats();
+        socket.write_all(b"x").unwrap();
+        socket.keepalive().unwrap();
+        assert_eq!(
+            vec![FIN | BINARY, 1, b'x', FIN | PING, 0],
+            socket.get_ref().output
+        );
+    }
+
+    #[test]
+    fn handshake() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let mut response = b"HTTP/1.1 101 Switching Protocols\r\n\
+            Upgrade: websocket\r\n\
+            sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n"
+            .to_vec();
+        response.extend_from_slice(&[FIN | TEXT, 2, b'o', b'k']);
+
+        let mut socket = connect_with_key(Duplex::new(response), "localhost", "/ws", key).unwrap();
+        let request = String::from_utf8(socket.get_ref().output.clone()).unwrap();
+        assert!(request.starts_with("GET /ws HTTP/1.1\r\nHost: localhost\r\n"));
+        assert!(request.contains("Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"));
+
+        let mut buffer: Vec<u8> = Vec::new();
+        socket.read_to_end(&mut buffer).unwrap();
+        assert_eq!(b"ok".to_vec(), buffer);
+
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Accept: nope\r\n\r\n";
+        let result = connect_with_key(Duplex::new(response.to_vec()), "localhost", "/", key);
+        assert_eq!(io::ErrorKind::InvalidData, result.err().unwrap().kind());
+
+        let response = b"HTTP/1.1 404 Not Found\r\n\r\n";
+        let result = connect(Duplex::new(response.to_vec()), "localhost", "/");
+        assert!(result.err().unwrap().to_string().contains("404"));
+    }
+}